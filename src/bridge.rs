@@ -0,0 +1,98 @@
+use std::{
+    io::{self, Read, Write},
+    time::Instant,
+};
+
+use bytes::{Buf, Bytes};
+use tokio::sync::mpsc::{Receiver, Sender};
+use tracing::warn;
+
+use crate::{
+    metrics::{self, Counter},
+    ts::SRT_PAYLOAD_SIZE,
+};
+
+/// Bridges SRT payloads arriving on a Tokio MPSC channel to a blocking `Read` for FFmpeg input.
+///
+/// `read` blocks on the channel, so the bridge must be driven from a blocking thread
+/// (e.g. `tokio::task::spawn_blocking`). A closed channel is reported as end of stream.
+pub struct ReadBridge {
+    receiver: Receiver<Bytes>,
+    pending: Bytes,
+}
+
+impl ReadBridge {
+    pub fn new(receiver: Receiver<Bytes>) -> Self {
+        Self {
+            receiver,
+            pending: Bytes::new(),
+        }
+    }
+}
+
+impl Read for ReadBridge {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pending.is_empty() {
+            match self.receiver.blocking_recv() {
+                Some(bytes) => self.pending = bytes,
+                None => return Ok(0),
+            }
+        }
+
+        let n = buf.len().min(self.pending.len());
+        buf[..n].copy_from_slice(&self.pending[..n]);
+        self.pending.advance(n);
+        Ok(n)
+    }
+}
+
+/// Bridges FFmpeg muxer output to a Tokio MPSC channel for async SRT sending.
+///
+/// Output is cut into 1316-byte messages. When the channel is full the chunk is dropped
+/// (and counted) rather than blocking the muxer; a warning is logged once per overload.
+pub struct WriteBridge {
+    sender: Sender<(Instant, Bytes)>,
+    dropped: Counter,
+    /// Whether chunks are being dropped, until one goes through again.
+    throttled: bool,
+}
+
+impl WriteBridge {
+    pub fn new(sender: Sender<(Instant, Bytes)>) -> Self {
+        Self {
+            sender,
+            dropped: metrics::counter(
+                "ts_chunks_dropped_total",
+                "TS chunks dropped because the SRT send channel was full",
+            ),
+            throttled: false,
+        }
+    }
+}
+
+impl Write for WriteBridge {
+    fn write(&mut self, w: &[u8]) -> io::Result<usize> {
+        for chunk in w.chunks(SRT_PAYLOAD_SIZE) {
+            // NOTE: Instant::now() is not ideal
+            // This should be directly derived from the PTS of the packet, which is not available here
+            if self
+                .sender
+                .try_send((Instant::now(), Bytes::copy_from_slice(chunk)))
+                .is_ok()
+            {
+                self.throttled = false;
+            } else {
+                self.dropped.inc();
+                if !self.throttled {
+                    warn!("Sender was throttled and buffer exhausted, dropping packets");
+                }
+                self.throttled = true;
+            }
+        }
+        Ok(w.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
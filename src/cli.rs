@@ -0,0 +1,61 @@
+//! Subcommands of the `rust-srt` binary and what they share: connecting to a peer.
+
+pub mod camera;
+pub mod daemon;
+pub mod hls;
+pub mod impair;
+pub mod play;
+pub mod probe;
+pub mod recv;
+pub mod relay;
+pub mod send;
+pub mod text;
+pub mod view;
+
+use std::time::Duration;
+
+use anyhow::anyhow;
+use rust_srt::{endpoint::Endpoint, error::Error, shutdown, stats::StatsSocket};
+use tracing::{Span, field, info, warn};
+
+/// Connects an `srt://` endpoint, its stats sampled as `name`. Callers retry every second until
+/// the listener is up, but not once it rejected them. Fails if shutdown is requested before a
+/// connection was made.
+///
+/// The peer and stream ID are recorded on the current span, so later events carry them.
+pub async fn connect(endpoint: &Endpoint, name: &str) -> anyhow::Result<StatsSocket> {
+    let socket = shutdown::until(connect_until_up(endpoint, name))
+        .await
+        .ok_or_else(|| anyhow!("no connection on {endpoint} yet"))??;
+    let settings = socket.get_ref().settings();
+    let span = Span::current();
+    span.record("peer", field::display(settings.remote));
+    span.record("stream_id", settings.stream_id.as_deref());
+    Ok(socket)
+}
+
+async fn connect_until_up(endpoint: &Endpoint, name: &str) -> anyhow::Result<StatsSocket> {
+    if endpoint.is_listener() {
+        info!("Waiting for a connection on {endpoint} …");
+        let socket = endpoint.connect_srt(name).await?;
+        info!("Connection established");
+        return Ok(socket);
+    }
+
+    info!("Calling {endpoint} …");
+    loop {
+        match endpoint.connect_srt(name).await {
+            Ok(socket) => {
+                info!("Connected to {endpoint}");
+                return Ok(socket);
+            }
+            Err(e) if matches!(e.downcast_ref(), Some(Error::HandshakeRejected { .. })) => {
+                return Err(e);
+            }
+            Err(e) => {
+                warn!("Connecting to {endpoint} failed: {e:#}. Retrying in 1s …");
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! camera: the former `tenant` and `slave` binaries.
+
+use std::{process::ExitCode, time::Instant};
+
+use anyhow::Context;
+use bytes::Bytes;
+use clap::Args;
+use futures_util::sink::SinkExt;
+use opencv::{core::Vector, imgcodecs, prelude::*, videoio};
+use rust_srt::{
+    endpoint::Endpoint,
+    error::Error,
+    latency, metrics,
+    protocol::{CLOCK_PROBES, Message, now_us},
+    shutdown,
+};
+use tokio::time::{Duration, sleep};
+use tracing::{debug, info};
+
+use super::connect;
+
+#[derive(Args)]
+pub struct CameraArgs {
+    /// `srt://host:port` calls the viewer (retrying until it's up), `srt://:port` waits for it
+    #[arg(long, default_value = "srt://127.0.0.1:2223")]
+    srt: Endpoint,
+    /// OpenCV capture device index
+    #[arg(long, default_value_t = 0)]
+    device: i32,
+    /// Frames per second to send at most
+    #[arg(long, default_value_t = 30)]
+    fps: u32,
+}
+
+pub async fn run(args: CameraArgs) -> anyhow::Result<ExitCode> {
+    let frames_captured = metrics::counter(
+        "camera_frames_captured_total",
+        "Frames read from the camera",
+    );
+    let frames_encoded = metrics::counter("camera_frames_encoded_total", "Frames encoded to JPEG");
+    let frames_sent = metrics::counter("camera_frames_sent_total", "Frames sent to the viewer");
+
+    let mut socket = connect(&args.srt, "camera").await?;
+
+    // Answer the viewer's clock probes so it can map our capture timestamps onto its clock
+    let answered =
+        latency::answer_clock_probes(&mut socket, CLOCK_PROBES, Duration::from_secs(5)).await?;
+    info!(
+        "{answered} clock probes answered, opening device {} …",
+        args.device
+    );
+
+    let capture_error = || Error::Capture {
+        device: args.device.to_string(),
+    };
+    let mut cam =
+        videoio::VideoCapture::new(args.device, videoio::CAP_ANY).with_context(capture_error)?;
+    if !videoio::VideoCapture::is_opened(&cam).with_context(capture_error)? {
+        anyhow::bail!(capture_error());
+    }
+
+    let frame_interval = Duration::from_secs(1) / args.fps.max(1);
+    let mut frame_count = 0u64;
+
+    while !shutdown::requested() {
+        let mut frame = Mat::default();
+        cam.read(&mut frame).with_context(capture_error)?;
+        let captured_at_us = now_us();
+        if frame.empty() {
+            continue;
+        }
+        frames_captured.inc();
+
+        // Encode frame to JPEG
+        let mut buf = Vector::<u8>::new();
+        imgcodecs::imencode(".jpg", &frame, &mut buf, &Vector::<i32>::new()).with_context(
+            || Error::Codec {
+                codec: "jpeg".to_string(),
+            },
+        )?;
+        frames_encoded.inc();
+
+        debug!("Sending frame {}: {} bytes", frame_count, buf.len());
+
+        // Send (timestamp, sequence number + capture time + bytes)
+        let message = Message::Frame {
+            seq: frame_count,
+            captured_at_us,
+            payload: Bytes::from(buf.to_vec()),
+        };
+        socket.send((Instant::now(), message.encode())).await?;
+        frames_sent.inc();
+        frame_count += 1;
+
+        sleep(frame_interval).await;
+    }
+
+    // Let the viewer receive what's still buffered before the connection goes
+    socket.close().await?;
+    info!("Stopped after {frame_count} frames");
+    Ok(ExitCode::SUCCESS)
+}
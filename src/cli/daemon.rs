@@ -0,0 +1,19 @@
+use std::{net::SocketAddr, path::PathBuf, process::ExitCode};
+
+use clap::Args;
+use rust_srt::daemon;
+
+#[derive(Args)]
+pub struct DaemonArgs {
+    /// TOML file listing the `[[route]]`s to run
+    #[arg(required_unless_present = "api")]
+    config: Option<PathBuf>,
+    /// Serve the HTTP control API on this address, e.g. 127.0.0.1:8081
+    #[arg(long)]
+    api: Option<SocketAddr>,
+}
+
+pub async fn run(args: DaemonArgs) -> anyhow::Result<ExitCode> {
+    daemon::run(args.config.as_deref(), args.api).await?;
+    Ok(ExitCode::SUCCESS)
+}
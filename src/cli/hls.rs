@@ -0,0 +1,102 @@
+use std::{path::PathBuf, process::ExitCode};
+
+use ac_ffmpeg::format::{demuxer::Demuxer, io::IO};
+use anyhow::Context;
+use clap::Args;
+use futures::SinkExt;
+use rust_srt::{
+    bridge::ReadBridge,
+    endpoint::Endpoint,
+    error::Error,
+    hls::{HlsConfig, HlsPackager},
+    http, protocol,
+    sequence::StreamCheck,
+    shutdown,
+};
+use tokio::sync::mpsc::channel;
+use tokio_stream::StreamExt;
+use tracing::{error, info, warn};
+
+use super::connect;
+
+#[derive(Args)]
+pub struct HlsArgs {
+    /// Where `index.m3u8` and the segments are written
+    output_dir: PathBuf,
+    /// `srt://:port` waits for the sender, `srt://host:port` calls it
+    #[arg(long, default_value = "srt://:1234?latency=1000")]
+    srt: Endpoint,
+    /// Also serve the output directory over HTTP on this port
+    #[arg(long)]
+    http: Option<u16>,
+}
+
+pub async fn run(args: HlsArgs) -> anyhow::Result<ExitCode> {
+    let output_dir = args.output_dir;
+
+    // Optional static server so players can fetch the playlist without a CDN in front
+    if let Some(port) = args.http {
+        let root = output_dir.clone();
+        info!(
+            "Serving {} on http://127.0.0.1:{port}/index.m3u8",
+            root.display()
+        );
+        tokio::spawn(async move {
+            if let Err(e) = http::serve_dir(("0.0.0.0", port), root).await {
+                error!("HTTP server failed: {e}");
+            }
+        });
+    }
+
+    let mut socket = connect(&args.srt, "hls_receiver").await?;
+
+    let (tx, rx) = channel(1024);
+
+    // Demuxing and segment muxing are blocking FFmpeg calls, keep them off the runtime
+    let demux_error = Error::Demux {
+        input: args.srt.to_string(),
+    };
+    let packager_task = tokio::task::spawn_blocking(move || {
+        let io = IO::from_read_stream(ReadBridge::new(rx));
+        let demuxer = Demuxer::builder()
+            .build(io)
+            .and_then(|demuxer| demuxer.find_stream_info(None).map_err(|(_, e)| e))
+            .context(demux_error)?;
+
+        HlsPackager::run(HlsConfig::new(output_dir), demuxer)
+    });
+
+    let mut check = StreamCheck::new("hls_receiver");
+    // On shutdown the packager still gets to finish its last segment and the playlist
+    while let Some(item) = shutdown::until(socket.next()).await.flatten() {
+        match item {
+            Ok((_instant, bytes)) => {
+                let Some(payload) = check.accept(protocol::unwrap_data(bytes)?) else {
+                    if check.ended() {
+                        break;
+                    }
+                    continue;
+                };
+                if tx.send(payload).await.is_err() {
+                    // Packager stopped, its error is reported below
+                    break;
+                }
+            }
+            Err(e) => {
+                warn!("Error receiving data: {e:?}");
+                check.fail(e);
+                break;
+            }
+        }
+    }
+    drop(tx);
+    socket.close().await.ok();
+
+    packager_task.await??;
+    let completion = check.finish();
+    info!(
+        "Stream {completion}, playlist finalized ({})",
+        check.totals()
+    );
+    Ok(completion.exit_code())
+}
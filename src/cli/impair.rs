@@ -0,0 +1,75 @@
+use std::{net::SocketAddr, process::ExitCode, time::Duration};
+
+use clap::Args;
+use rust_srt::{
+    impair::{ImpairProxy, Impairment, ProxyConfig},
+    shutdown,
+};
+use tracing::info;
+
+#[derive(Args)]
+pub struct ImpairArgs {
+    /// Address SRT callers connect to
+    listen: SocketAddr,
+    /// The SRT listener to relay to
+    upstream: SocketAddr,
+    /// `loss`, `dup` (probabilities), `delay`, `jitter`, `queue` (ms), `rate` (bits/s), `seed`;
+    /// prefix with `up.` or `down.` for one direction (up = towards the upstream listener)
+    options: Vec<String>,
+}
+
+pub async fn run(args: ImpairArgs) -> anyhow::Result<ExitCode> {
+    let mut config = ProxyConfig::new(args.listen, args.upstream);
+    for option in &args.options {
+        if let Some(seed) = option.strip_prefix("seed=") {
+            config.seed = seed.parse()?;
+        } else if let Some(option) = option.strip_prefix("up.") {
+            config.forward.set(option)?;
+        } else if let Some(option) = option.strip_prefix("down.") {
+            config.backward.set(option)?;
+        } else {
+            config.forward.set(option)?;
+            config.backward.set(option)?;
+        }
+    }
+    log_direction("up", &config.forward);
+    log_direction("down", &config.backward);
+
+    let proxy = ImpairProxy::start(config.clone()).await?;
+    info!(
+        "Relaying {} -> {} (seed {})",
+        proxy.local_addr(),
+        config.upstream,
+        config.seed
+    );
+
+    let mut interval = tokio::time::interval(Duration::from_secs(5));
+    interval.tick().await;
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let (up, down) = (proxy.forward_stats(), proxy.backward_stats());
+                info!(
+                    "Up {}/{} sent ({} lost, {} overflowed, {} duplicated), down {}/{} sent ({} lost, {} overflowed, {} duplicated)",
+                    up.sent, up.received, up.lost, up.overflowed, up.duplicated,
+                    down.sent, down.received, down.lost, down.overflowed, down.duplicated
+                );
+            }
+            _ = shutdown::wait() => break,
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn log_direction(name: &str, impairment: &Impairment) {
+    let rate = impairment
+        .rate
+        .map_or("unlimited".to_string(), |rate| format!("{rate} bit/s"));
+    info!(
+        "{name}: loss {:.1}%, delay {:?} ± {:?}, dup {:.1}%, rate {rate}",
+        impairment.loss * 100.0,
+        impairment.delay,
+        impairment.jitter,
+        impairment.duplicate * 100.0
+    );
+}
@@ -0,0 +1,407 @@
+//! play: the former `ts_streamer`, `sender_debug` and `streamer_server` binaries.
+
+use std::{future::Future, io, process::ExitCode, time::Instant};
+
+use ac_ffmpeg::{
+    format::{
+        demuxer::{Demuxer, DemuxerWithStreamInfo},
+        io::IO,
+        muxer::{Muxer, OutputFormat},
+    },
+    time::Timestamp,
+};
+use anyhow::{Context, anyhow};
+use bytes::Bytes;
+use clap::Args;
+use futures::SinkExt;
+use rust_srt::{
+    bridge::WriteBridge,
+    endpoint::Endpoint,
+    error::Error,
+    probe::StreamInfo,
+    protocol::{self, Message},
+    shutdown,
+    stats::StatsSocket,
+    stdio::Input,
+    testsrc,
+    ts::{GopCache, TsChunker},
+};
+use srt_tokio::ConnectionRequest;
+use tokio::{
+    sync::mpsc::{Receiver, Sender, channel, error::TrySendError},
+    task::JoinSet,
+    time::sleep_until,
+};
+use tokio_stream::{StreamExt, wrappers::ReceiverStream};
+use tracing::{Instrument, debug, info, info_span, warn};
+
+use super::connect;
+
+#[derive(Args)]
+pub struct PlayArgs {
+    /// Media file, `-` for stdin, or `testsrc[:WxH][@FPS][:SECS]` for generated bars and tone
+    #[arg(default_value = testsrc::PREFIX)]
+    input: String,
+    /// `srt://:port` streams to every receiver that calls in, `srt://host:port` calls one
+    #[arg(long, default_value = "srt://:1234?latency=1000")]
+    srt: Endpoint,
+    /// Start the input over when it ends instead of ending the stream
+    #[arg(long = "loop")]
+    repeat: bool,
+    /// Number the TS messages and end with the totals, so `recv` can check them (also `?seq=1`).
+    /// Other SRT receivers such as ffplay only play raw TS.
+    #[arg(long)]
+    sequence: bool,
+}
+
+impl PlayArgs {
+    fn sequenced(&self) -> bool {
+        self.sequence || self.srt.sequenced()
+    }
+}
+
+pub async fn run(args: PlayArgs) -> anyhow::Result<ExitCode> {
+    // Open the input before connecting, so a bad path fails right away
+    let demuxer = open(&args.input)?;
+
+    // Same fields as `probe`, without the packet measurements
+    for (index, stream) in demuxer.streams().iter().enumerate() {
+        let info = StreamInfo::new(index, stream);
+        info!("Stream #{index}: {}", serde_json::to_string(&info)?);
+    }
+
+    if args.srt.is_listener() {
+        serve(&args, demuxer).await?;
+    } else {
+        call(&args, demuxer).await?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Streams to the one receiver at `--srt`.
+async fn call(args: &PlayArgs, demuxer: DemuxerWithStreamInfo<Input>) -> anyhow::Result<()> {
+    let mut socket = connect(&args.srt, "play").await?;
+
+    let (tx, mut rx) = channel(1024);
+    let player = tokio::spawn(play(args.input.clone(), args.repeat, demuxer, tx));
+
+    // Muxer writes aren't cut on packet boundaries, nor to the size of a numbered message
+    let sequenced = args.sequenced();
+    let mut chunker = TsChunker::new(protocol::ts_payload_size(sequenced));
+    // Numbered across loops, so the receiver sees one continuous stream
+    let (mut frames, mut bytes) = (0u64, 0u64);
+    let mut ended = false;
+    while !ended {
+        let (instant, payloads) = match rx.recv().await {
+            Some((instant, data)) => (instant, chunker.push(&data)),
+            None => {
+                ended = true;
+                (Instant::now(), chunker.flush().into_iter().collect())
+            }
+        };
+        for payload in payloads {
+            bytes += payload.len() as u64;
+            let message = if sequenced {
+                Message::Data {
+                    seq: frames,
+                    payload,
+                }
+                .encode()
+            } else {
+                payload
+            };
+            frames += 1;
+            socket.send((instant, message)).await?;
+        }
+    }
+    player.await??;
+
+    if sequenced {
+        // Announce the totals before draining and closing
+        socket
+            .send((
+                Instant::now(),
+                Message::EndOfStream { frames, bytes }.encode(),
+            ))
+            .await?;
+    }
+    socket.close().await?;
+    info!(frames, bytes, "Finished");
+    Ok(())
+}
+
+/// Streams to every receiver calling the listener at `--srt`, each starting from the last
+/// keyframe. Playback waits for the first one, so a clip isn't half over when it joins.
+async fn serve(args: &PlayArgs, demuxer: DemuxerWithStreamInfo<Input>) -> anyhow::Result<()> {
+    let (_listener, mut incoming) = args.srt.bind_srt().await?;
+    info!("Waiting for a connection on {} …", args.srt);
+    let first = shutdown::until(incoming.incoming().next())
+        .await
+        .flatten()
+        .ok_or_else(|| anyhow!("no connection on {} yet", args.srt))?;
+
+    let mut fanout = Fanout::new(args.sequenced());
+    let mut subscribers = JoinSet::new();
+    subscribers.spawn(fanout.subscribe(first));
+
+    let (tx, mut rx) = channel(1024);
+    let player = tokio::spawn(play(args.input.clone(), args.repeat, demuxer, tx));
+    loop {
+        tokio::select! {
+            chunk = rx.recv() => match chunk {
+                Some((instant, data)) => fanout.push(instant, &data),
+                None => break,
+            },
+            Some(request) = incoming.incoming().next() => {
+                subscribers.spawn(fanout.subscribe(request));
+            }
+        }
+    }
+    player.await??;
+
+    fanout.finish().await;
+    while let Some(result) = subscribers.join_next().await {
+        if let Err(e) = result? {
+            warn!("Subscriber failed: {e:#}");
+        }
+    }
+    Ok(())
+}
+
+/// Payloads a subscriber may fall behind by, on top of the cache it starts with.
+const SUBSCRIBER_QUEUE: usize = 1024;
+
+/// The receivers of a listening player. When numbered, each gets its own numbering and
+/// totals, so one that joined late still checks out complete.
+struct Fanout {
+    /// Lines the muxer output up on TS packets, which the cache needs.
+    chunker: TsChunker,
+    cache: GopCache,
+    sequenced: bool,
+    subscribers: Vec<Subscriber>,
+}
+
+impl Fanout {
+    fn new(sequenced: bool) -> Self {
+        Self {
+            chunker: TsChunker::new(protocol::ts_payload_size(sequenced)),
+            cache: GopCache::default(),
+            sequenced,
+            subscribers: Vec::new(),
+        }
+    }
+
+    /// Queues the cached tables and GOP for a new subscriber, so its decoder can start right away.
+    /// Returns the task that accepts its handshake and sends it everything queued.
+    fn subscribe(
+        &mut self,
+        request: ConnectionRequest,
+    ) -> impl Future<Output = anyhow::Result<()>> + use<> {
+        let cache = self
+            .cache
+            .snapshot(protocol::ts_payload_size(self.sequenced));
+        let (tx, rx) = channel(cache.len() + SUBSCRIBER_QUEUE);
+        let mut subscriber = Subscriber {
+            tx,
+            joined: Instant::now(),
+            sequenced: self.sequenced,
+            frames: 0,
+            bytes: 0,
+            lagging: false,
+        };
+        debug!(
+            payloads = cache.len(),
+            gop_bytes = self.cache.gop_bytes(),
+            "Priming the subscriber with the last GOP"
+        );
+        for payload in cache {
+            subscriber.send(subscriber.joined, payload);
+        }
+        self.subscribers.push(subscriber);
+
+        let peer = request.remote();
+        let stream_id = request.stream_id().map(ToString::to_string);
+        let span = info_span!("subscriber", %peer, stream_id = stream_id.as_deref());
+        stream_to(request, rx).instrument(span)
+    }
+
+    fn push(&mut self, instant: Instant, data: &[u8]) {
+        for payload in self.chunker.push(data) {
+            self.send(instant, payload);
+        }
+    }
+
+    fn send(&mut self, instant: Instant, payload: Bytes) {
+        self.cache.push(&payload);
+        self.subscribers
+            .retain_mut(|subscriber| subscriber.send(instant, payload.clone()));
+    }
+
+    /// Sends what the chunker still holds, then every subscriber's totals if numbered. Dropping
+    /// the queues afterwards lets the subscriber tasks finish.
+    async fn finish(mut self) {
+        if let Some(rest) = self.chunker.flush() {
+            self.send(Instant::now(), rest);
+        }
+        if !self.sequenced {
+            return;
+        }
+        for subscriber in self.subscribers {
+            let (frames, bytes) = (subscriber.frames, subscriber.bytes);
+            // Waits for room, unlike payloads the totals mustn't be dropped
+            let end = Message::EndOfStream { frames, bytes }.encode();
+            let _ = subscriber.tx.send((Instant::now(), end)).await;
+        }
+    }
+}
+
+struct Subscriber {
+    tx: Sender<(Instant, Bytes)>,
+    /// Nothing is stamped earlier, cached payloads included.
+    joined: Instant,
+    sequenced: bool,
+    frames: u64,
+    bytes: u64,
+    lagging: bool,
+}
+
+impl Subscriber {
+    /// Numbers (if asked to) and queues `payload`. One dropped because the queue is full still
+    /// counts, so the receiver sees the gap. Returns `false` once the subscriber is gone.
+    fn send(&mut self, instant: Instant, payload: Bytes) -> bool {
+        self.bytes += payload.len() as u64;
+        let message = if self.sequenced {
+            Message::Data {
+                seq: self.frames,
+                payload,
+            }
+            .encode()
+        } else {
+            payload
+        };
+        self.frames += 1;
+
+        match self.tx.try_send((instant.max(self.joined), message)) {
+            Ok(()) => {
+                self.lagging = false;
+                true
+            }
+            Err(TrySendError::Full(_)) => {
+                if !self.lagging {
+                    warn!("A subscriber fell behind, dropping payloads");
+                }
+                self.lagging = true;
+                true
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+}
+
+async fn stream_to(
+    request: ConnectionRequest,
+    rx: Receiver<(Instant, Bytes)>,
+) -> anyhow::Result<()> {
+    let peer = request.remote();
+    let socket = request
+        .accept(None)
+        .await
+        .with_context(|| format!("can't accept {peer}"))?;
+    let mut socket = StatsSocket::from_env(socket, "play");
+    info!("Subscriber connected");
+
+    socket
+        .send_all(&mut ReceiverStream::new(rx).map(Ok::<_, io::Error>))
+        .await?;
+    socket.close().await?;
+    info!("Subscriber finished");
+    Ok(())
+}
+
+/// Remuxes the input into `tx` until it ends, or over and over with `--loop`.
+async fn play(
+    input: String,
+    repeat: bool,
+    mut demuxer: DemuxerWithStreamInfo<Input>,
+    tx: Sender<(Instant, Bytes)>,
+) -> anyhow::Result<()> {
+    loop {
+        remux_paced(input.clone(), demuxer, tx.clone()).await?;
+        if !repeat || shutdown::requested() {
+            return Ok(());
+        }
+        info!("Finished {input}, looping again …");
+        demuxer = open(&input)?;
+    }
+}
+
+fn open(path: &str) -> anyhow::Result<DemuxerWithStreamInfo<Input>> {
+    let demux_error = || Error::Demux {
+        input: path.to_string(),
+    };
+    let demuxer = Demuxer::builder()
+        .build(Input::open(path)?.into_io())
+        .with_context(demux_error)?
+        .find_stream_info(None)
+        .map_err(|(_, e)| e)
+        .with_context(demux_error)?;
+    if demuxer.streams().is_empty() {
+        anyhow::bail!("no streams found in {path}");
+    }
+    Ok(demuxer)
+}
+
+/// Remuxes every packet of `input` to mpegts, released at the pace of the packet timestamps,
+/// until the input ends or shutdown is requested. Either way the muxer is closed, so the TS
+/// ends cleanly.
+async fn remux_paced(
+    input: String,
+    mut demuxer: DemuxerWithStreamInfo<Input>,
+    tx: Sender<(Instant, Bytes)>,
+) -> anyhow::Result<()> {
+    let mux_error = || Error::Mux {
+        output: "mpegts".to_string(),
+    };
+    let mut muxer_builder = Muxer::builder();
+    for stream in demuxer.streams() {
+        muxer_builder
+            .add_stream(&stream.codec_parameters())
+            .with_context(mux_error)?;
+    }
+    let mut muxer = muxer_builder
+        .build(
+            IO::from_write_stream(WriteBridge::new(tx)),
+            OutputFormat::find_by_name("mpegts").context("no mpegts muxer")?,
+        )
+        .with_context(mux_error)?;
+
+    let mut last_pts_inst: Option<(Timestamp, Instant)> = None;
+    while let Some(packet) = demuxer.take().with_context(|| Error::Demux {
+        input: input.clone(),
+    })? {
+        if shutdown::requested() {
+            info!("Stopping, finishing the stream …");
+            break;
+        }
+        let pts = packet.pts();
+        let inst = match last_pts_inst {
+            Some((last_pts, last_inst)) if pts >= last_pts => {
+                let deadline = last_inst + (pts - last_pts);
+                sleep_until(deadline.into()).await;
+                last_pts_inst = Some((pts, deadline));
+                deadline
+            }
+            // Timestamps going back (B-frames, other streams) don't hold the packet up
+            Some((_, last_inst)) => last_inst,
+            None => {
+                let now = Instant::now();
+                last_pts_inst = Some((pts, now));
+                now
+            }
+        };
+        debug!("Packet @ {:?} ({} bytes)", inst, packet.data().len());
+        muxer.push(packet).with_context(mux_error)?;
+    }
+    muxer.close().with_context(mux_error)?;
+    Ok(())
+}
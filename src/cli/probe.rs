@@ -0,0 +1,46 @@
+use std::{process::ExitCode, time::Duration};
+
+use clap::Args;
+use rust_srt::{endpoint::Endpoint, probe};
+use tracing::info;
+
+#[derive(Args)]
+pub struct ProbeArgs {
+    /// File, `-`, `testsrc[:...]`, or a live srt://, udp:// or tcp:// input
+    input: String,
+    /// Media to measure rates over; live inputs are recorded this long first
+    #[arg(long, default_value_t = probe::DEFAULT_WINDOW.as_secs_f64(), value_parser = parse_seconds)]
+    seconds: f64,
+    /// Indent the JSON
+    #[arg(long)]
+    pretty: bool,
+}
+
+/// A positive number of seconds that fits a [`Duration`].
+fn parse_seconds(value: &str) -> Result<f64, String> {
+    let seconds: f64 = value.parse().map_err(|e| format!("{e}"))?;
+    match Duration::try_from_secs_f64(seconds) {
+        Ok(duration) if !duration.is_zero() => Ok(seconds),
+        _ => Err(format!("{value} isn't a positive number of seconds")),
+    }
+}
+
+pub async fn run(args: ProbeArgs) -> anyhow::Result<ExitCode> {
+    let window = Duration::from_secs_f64(args.seconds);
+    let input = args.input;
+    let report = if input.contains("://") {
+        let endpoint: Endpoint = input.parse()?;
+        info!("Recording {endpoint} for {window:?} …");
+        probe::probe_endpoint(&endpoint, window).await?
+    } else {
+        tokio::task::spawn_blocking(move || probe::probe_path(&input, window)).await??
+    };
+
+    let json = if args.pretty {
+        serde_json::to_string_pretty(&report)?
+    } else {
+        serde_json::to_string(&report)?
+    };
+    println!("{json}");
+    Ok(ExitCode::SUCCESS)
+}
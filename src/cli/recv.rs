@@ -0,0 +1,119 @@
+//! recv: the former `receiver`, `receiver_debug` and `streamer_client` binaries.
+
+use std::{path::PathBuf, process::ExitCode, time::Duration};
+
+use anyhow::Result;
+use clap::Args;
+use futures::{SinkExt, TryStreamExt};
+use rust_srt::{
+    endpoint::Endpoint,
+    protocol,
+    sequence::StreamCheck,
+    shutdown, stdio,
+    transfer::{self, Outcome},
+};
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info, warn};
+
+use super::connect;
+
+#[derive(Args)]
+pub struct RecvArgs {
+    /// Write the stream here (`-` for stdout, logs go to stderr); with --transfer, the directory
+    /// to save into (`.` by default). Without it the stream is only checked.
+    output: Option<String>,
+    /// Receive a file transfer, verifying it and resuming after a dropped link
+    #[arg(long)]
+    transfer: bool,
+    /// `srt://host:port` calls the sender (retrying until it's up), `srt://:port` waits for it
+    #[arg(long, default_value = "srt://127.0.0.1:2223")]
+    srt: Endpoint,
+}
+
+pub async fn run(args: RecvArgs) -> Result<ExitCode> {
+    if args.transfer {
+        let dir = PathBuf::from(args.output.unwrap_or_else(|| ".".to_string()));
+        return receive_file(&args.srt, dir).await;
+    }
+
+    let mut socket = connect(&args.srt, "receiver").await?;
+    let mut output = match &args.output {
+        Some(path) => Some(stdio::create_async_output(path).await?),
+        None => None,
+    };
+
+    info!("Awaiting frames …");
+    let mut frame_index: u64 = 0;
+    let mut check = StreamCheck::new("receiver");
+
+    loop {
+        let Some(next) = shutdown::until(socket.try_next()).await else {
+            info!("Interrupted after {} frames", frame_index);
+            break;
+        };
+        match next {
+            Ok(Some((instant, bytes))) => {
+                let Some(payload) = check.accept(protocol::unwrap_data(bytes)?) else {
+                    if check.ended() {
+                        info!("End of stream after {} frames", frame_index);
+                        break;
+                    }
+                    continue;
+                };
+                debug!(
+                    "Got frame {} at {:?} ({} bytes)",
+                    frame_index,
+                    instant,
+                    payload.len()
+                );
+                if let Some(output) = &mut output {
+                    output.write_all(&payload).await?;
+                }
+                frame_index += 1;
+            }
+            Ok(None) if !check.numbered() => {
+                info!("Raw TS ended after {} frames", frame_index);
+                break;
+            }
+            Ok(None) => {
+                warn!("Connection closed by sender after {} frames", frame_index);
+                break;
+            }
+            Err(e) => {
+                warn!("Error receiving at frame {}: {:?}", frame_index, e);
+                check.fail(e);
+                break;
+            }
+        }
+    }
+
+    if let Some(output) = &mut output {
+        output.flush().await?;
+    }
+    socket.close().await.ok();
+    let completion = check.finish();
+    info!("Done, {} ({})", completion, check.totals());
+    Ok(completion.exit_code())
+}
+
+/// Receives a file transfer into `dir`, reconnecting and resuming after a dropped link.
+async fn receive_file(endpoint: &Endpoint, dir: PathBuf) -> Result<ExitCode> {
+    tokio::fs::create_dir_all(&dir).await?;
+    loop {
+        let mut socket = connect(endpoint, "receiver").await?;
+        let Some(outcome) = shutdown::until(transfer::receive_file(&mut socket, &dir)).await else {
+            socket.close().await.ok();
+            info!("Transfer interrupted, the partial file is kept to resume later");
+            return Ok(ExitCode::from(shutdown::EXIT_INTERRUPTED));
+        };
+        let outcome = outcome?;
+        socket.close().await.ok();
+        if outcome == Outcome::Interrupted {
+            info!("Transfer interrupted, reconnecting to resume …");
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            continue;
+        }
+        info!("Transfer done: {:?}", outcome);
+        return Ok(outcome.exit_code());
+    }
+}
@@ -0,0 +1,15 @@
+use std::process::ExitCode;
+
+use clap::Args;
+use rust_srt::{endpoint::Endpoint, gateway};
+
+#[derive(Args)]
+pub struct RelayArgs {
+    input: Endpoint,
+    output: Endpoint,
+}
+
+pub async fn run(args: RelayArgs) -> anyhow::Result<ExitCode> {
+    gateway::forward(&args.input, &args.output).await?;
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,235 @@
+//! send: the former `sender` binary.
+
+use std::{path::Path, process::ExitCode, time::Instant};
+
+use anyhow::Result;
+use bytes::Bytes;
+use clap::Args;
+use futures::SinkExt;
+use rust_srt::{
+    endpoint::Endpoint,
+    protocol::{self, Message},
+    shutdown,
+    stats::StatsSocket,
+    stdio, testsrc,
+    transfer::{self, Manifest, Outcome},
+    ts::{Pacing, TsChunker, TsPacer},
+};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tracing::{debug, error, info};
+
+use super::connect;
+
+const FRAME_CHUNK_SIZE: usize = 1024 * 256; // e.g., 256KB chunks
+const FRAME_INTERVAL_MS: u64 = 33; // ~30fps
+const TS_READ_SIZE: usize = 64 * 1024;
+
+#[derive(Args)]
+pub struct SendArgs {
+    /// File to send, `-` for stdin, or `testsrc[:WxH][@FPS][:SECS]` for generated bars and tone
+    #[arg(default_value = testsrc::PREFIX)]
+    input: String,
+    /// Forward mpegts untouched in 1316-byte messages, paced by its PCRs (implied by .ts/.m2ts)
+    #[arg(long, conflicts_with = "transfer")]
+    ts: bool,
+    /// Number the TS messages and end with the totals, so `recv` can check them (also `?seq=1`).
+    /// Other SRT receivers such as ffplay only play raw TS.
+    #[arg(long, conflicts_with = "transfer")]
+    sequence: bool,
+    /// Send a manifest and checksum first so the receiver can verify and resume the file
+    #[arg(long)]
+    transfer: bool,
+    /// Pace at a fixed rate in bits/s instead (implies --ts unless transferring)
+    #[arg(long)]
+    bitrate: Option<u64>,
+    /// `srt://:port` waits for the receiver, `srt://host:port` calls it
+    #[arg(long, default_value = "srt://:2223")]
+    srt: Endpoint,
+}
+
+pub async fn run(args: SendArgs) -> Result<ExitCode> {
+    let path = args.input;
+    if args.transfer {
+        let bitrate = args.bitrate.unwrap_or(transfer::DEFAULT_BITRATE);
+        return send_file(&args.srt, Path::new(&path), bitrate).await;
+    }
+    let ts_mode = args.ts
+        || args.bitrate.is_some()
+        || path.ends_with(".ts")
+        || path.ends_with(".m2ts")
+        || testsrc::is_test_source(&path);
+
+    let mut socket = connect(&args.srt, "sender").await?;
+    info!("Starting frame stream …");
+
+    let mut file = stdio::open_async_input(&path).await?;
+
+    // Blocks aren't TS, only this repo's receivers take them, so they are always numbered
+    let sequenced = !ts_mode || args.sequence || args.srt.sequenced();
+    let (frames, bytes) = if ts_mode {
+        let pacing = match args.bitrate {
+            Some(bitrate) => Pacing::Bitrate(bitrate),
+            None => Pacing::Pcr,
+        };
+        send_ts(&mut socket, &mut file, pacing, sequenced).await?
+    } else {
+        send_blocks(&mut socket, &mut file).await?
+    };
+
+    if sequenced {
+        // Announce the totals so the receiver can tell a complete transfer from a dropped link
+        socket
+            .send((
+                Instant::now(),
+                Message::EndOfStream { frames, bytes }.encode(),
+            ))
+            .await?;
+        info!("End of stream sent");
+    }
+    // Flush and close rather than sleeping: closing drains what is still buffered
+    socket.flush().await?;
+    info!("Closing …");
+
+    socket.close().await?;
+    info!("Closed socket.");
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Transfers `path` with a manifest and checksum, listening again after a dropped link so the
+/// receiver can reconnect and resume.
+async fn send_file(endpoint: &Endpoint, path: &Path, bitrate: u64) -> Result<ExitCode> {
+    if stdio::is_stdio(path) {
+        anyhow::bail!("--transfer needs a file, stdin can't be hashed up front or resumed");
+    }
+    let manifest = Manifest::for_file(path).await?;
+    info!(
+        "{} is {} bytes, sha256 {}",
+        manifest.name,
+        manifest.size,
+        transfer::hex(&manifest.sha256)
+    );
+
+    loop {
+        let mut socket = connect(endpoint, "sender").await?;
+        info!("Starting transfer …");
+
+        let Some(outcome) =
+            shutdown::until(transfer::send_file(&mut socket, path, &manifest, bitrate)).await
+        else {
+            socket.close().await.ok();
+            info!("Transfer interrupted, the receiver can resume it later");
+            return Ok(ExitCode::from(shutdown::EXIT_INTERRUPTED));
+        };
+        let outcome = outcome?;
+        socket.close().await.ok();
+        match outcome {
+            Outcome::Verified => info!("Receiver verified {}", manifest.name),
+            Outcome::ChecksumMismatch => error!("Receiver reported a checksum mismatch"),
+            Outcome::Interrupted => {
+                info!("Transfer interrupted, waiting for the receiver to resume …");
+                continue;
+            }
+        }
+        return Ok(outcome.exit_code());
+    }
+}
+
+/// Sends the input as opaque 256 KB blocks at a fixed ~30 fps, returning the frames and bytes sent.
+async fn send_blocks(
+    socket: &mut StatsSocket,
+    file: &mut (dyn AsyncRead + Unpin + Send),
+) -> Result<(u64, u64)> {
+    let mut buf = vec![0u8; FRAME_CHUNK_SIZE];
+    let mut frame_index: u64 = 0;
+    let mut bytes_sent: u64 = 0;
+
+    loop {
+        // Shutdown reads as the end of the input, so the stream still ends cleanly
+        let n = shutdown::until(file.read(&mut buf))
+            .await
+            .transpose()?
+            .unwrap_or(0);
+        if n == 0 {
+            info!("End‐of‐file, sent {} frames", frame_index);
+            return Ok((frame_index, bytes_sent));
+        }
+        let data = &buf[..n];
+        let bytes = Message::Data {
+            seq: frame_index,
+            payload: Bytes::copy_from_slice(data),
+        }
+        .encode();
+        let now = Instant::now();
+
+        // Send single “frame” as one message
+        socket
+            .send_all(&mut futures::stream::iter(std::iter::once(Ok((
+                now, bytes,
+            )))))
+            .await?;
+
+        debug!("Sent frame {} ({} bytes)", frame_index, n);
+        frame_index += 1;
+        bytes_sent += n as u64;
+
+        // wait for next frame interval
+        tokio::time::sleep(tokio::time::Duration::from_millis(FRAME_INTERVAL_MS)).await;
+    }
+}
+
+/// Forwards mpegts as-is in messages of whole TS packets, paced in real time: raw 1316-byte
+/// payloads, or numbered messages of six packets when `sequenced`.
+async fn send_ts(
+    socket: &mut StatsSocket,
+    file: &mut (dyn AsyncRead + Unpin + Send),
+    pacing: Pacing,
+    sequenced: bool,
+) -> Result<(u64, u64)> {
+    let mut chunker = TsChunker::new(protocol::ts_payload_size(sequenced));
+    let mut pacer = TsPacer::new(pacing);
+    let mut buf = vec![0u8; TS_READ_SIZE];
+    let mut messages: u64 = 0;
+    let mut bytes_sent: u64 = 0;
+
+    loop {
+        let n = shutdown::until(file.read(&mut buf))
+            .await
+            .transpose()?
+            .unwrap_or(0);
+        let payloads = if n == 0 {
+            chunker.flush().into_iter().collect()
+        } else {
+            chunker.push(&buf[..n])
+        };
+
+        for payload in payloads {
+            // The deadline doubles as the SRT origin time, so the receiver plays out at the same pace
+            let deadline = pacer.deadline(&payload);
+            tokio::time::sleep_until(deadline.into()).await;
+
+            bytes_sent += payload.len() as u64;
+            let message = if sequenced {
+                Message::Data {
+                    seq: messages,
+                    payload,
+                }
+                .encode()
+            } else {
+                payload
+            };
+            socket.send((deadline, message)).await?;
+            messages += 1;
+            if messages.is_multiple_of(1000) {
+                info!("Sent {} TS messages ({} bytes)", messages, bytes_sent);
+            }
+        }
+
+        if n == 0 {
+            info!(
+                "End‐of‐file, sent {} TS messages ({} bytes)",
+                messages, bytes_sent
+            );
+            return Ok((messages, bytes_sent));
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! text: the former `server` and `client` binaries.
+
+use std::{
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use clap::Args;
+use futures::{SinkExt, TryStreamExt};
+use rust_srt::{endpoint::Endpoint, shutdown};
+use tokio::time::sleep;
+use tracing::{Instrument, field, info, info_span};
+
+use super::connect;
+
+#[derive(Args)]
+pub struct TextArgs {
+    /// Messages to send; without any, listen and print what arrives
+    messages: Vec<String>,
+    /// Defaults to calling srt://127.0.0.1:2223 when sending and listening on srt://:2223 otherwise
+    #[arg(long)]
+    srt: Option<Endpoint>,
+}
+
+pub async fn run(args: TextArgs) -> anyhow::Result<ExitCode> {
+    if args.messages.is_empty() {
+        let endpoint = args.srt.unwrap_or("srt://:2223".parse()?);
+        return listen(&endpoint).await;
+    }
+    let endpoint = args.srt.unwrap_or("srt://127.0.0.1:2223".parse()?);
+
+    let mut tx = connect(&endpoint, "client").await?;
+    for msg in &args.messages {
+        if shutdown::requested() {
+            break;
+        }
+        info!("Sending: {}", msg);
+        tx.send((Instant::now(), Bytes::from(msg.clone()))).await?;
+        sleep(Duration::from_millis(50)).await;
+    }
+
+    // Wait to ensure server has time to read
+    sleep(Duration::from_secs(1)).await;
+
+    tx.close().await?;
+    info!("Client finished sending.");
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints every message received to stdout, then waits for the next client until shutdown.
+async fn listen(endpoint: &Endpoint) -> anyhow::Result<ExitCode> {
+    loop {
+        // One span per client, so its peer doesn't stick to the next one's events
+        let span = info_span!("client", peer = field::Empty, stream_id = field::Empty);
+        let client = async {
+            let mut rx = connect(endpoint, "server").await?;
+            info!("Client connected!");
+
+            while let Some(Ok(Some((_ts, data)))) = shutdown::until(rx.try_next()).await {
+                println!("Received: {}", String::from_utf8_lossy(&data));
+            }
+            if shutdown::requested() {
+                rx.close().await.ok();
+            }
+            anyhow::Ok(())
+        };
+        client.instrument(span).await?;
+
+        if shutdown::requested() {
+            info!("Server shutting down");
+            return Ok(ExitCode::SUCCESS);
+        }
+        info!("Client disconnected — still listening...");
+    }
+}
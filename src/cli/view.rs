@@ -0,0 +1,472 @@
+//! view: the former `controller` and `master` binaries.
+
+use std::{collections::HashSet, path::PathBuf, process::ExitCode, time::Instant};
+
+use anyhow::Context;
+use bytes::Bytes;
+use clap::Args;
+use futures::{SinkExt, future, stream::StreamExt};
+use opencv::{
+    core::{Point, Scalar, Vector},
+    highgui, imgcodecs, imgproc,
+    prelude::*,
+};
+use rust_srt::{
+    endpoint::Endpoint,
+    latency::{self, ClockSync, LatencyStats},
+    metrics::{self, Counter},
+    mosaic::Mosaic,
+    pipeline::{self, FrameContext, Pipeline, ProcessorSpec},
+    playout::PlayoutBuffer,
+    protocol::{CLOCK_PROBES, Message, now_us},
+    sequence::SequenceTracker,
+    shutdown,
+    stats::StatsSocket,
+};
+use srt_tokio::{
+    ConnectionRequest, SrtIncoming, SrtListener,
+    access::{RejectReason, ServerRejectReason},
+};
+use tokio::{
+    sync::mpsc::{Receiver, Sender, channel},
+    task::JoinSet,
+    time::{Duration, interval, sleep_until},
+};
+use tracing::{Instrument, Span, debug, info, info_span, warn};
+
+use super::connect;
+
+#[derive(Args)]
+pub struct ViewArgs {
+    /// `srt://:port` takes any number of cameras, told apart by stream ID; `srt://host:port` calls one
+    #[arg(long, default_value = "srt://:2223")]
+    srt: Endpoint,
+    /// Window title
+    #[arg(long, default_value = "Camera")]
+    title: String,
+    /// Milliseconds frames are held back to smooth out jitter; frames arriving later are dropped
+    #[arg(long, default_value_t = 100)]
+    delay: u64,
+    /// Don't open a window
+    #[arg(long)]
+    headless: bool,
+    /// Write the mosaic and every tile as PNG into this directory, once a second and on exit
+    #[arg(long)]
+    save: Option<PathBuf>,
+    /// Process every decoded frame, in the order given: `resize=WxH`, `crop=WxH+X+Y`,
+    /// `rotate=90|180|270`, `timestamp`, `motion[=FRACTION[,WxH+X+Y]...]`, `snapshot=DIR`,
+    /// `record=DIR` (after `motion`); nothing that draws may come before `motion`
+    #[arg(long = "process", value_name = "SPEC")]
+    processors: Vec<ProcessorSpec>,
+}
+
+/// Frames buffered at most per camera, whatever the delay.
+const PLAYOUT_CAPACITY: usize = 120;
+/// Mosaic redraws per second.
+const REFRESH_RATE: u32 = 25;
+const SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+struct Frame {
+    seq: u64,
+    captured_at_us: u64,
+    mat: Mat,
+}
+
+/// A JPEG frame as it arrived, on its way to the camera's processing thread.
+struct Received {
+    seq: u64,
+    captured_at_us: u64,
+    delivered_at: Instant,
+    payload: Bytes,
+}
+
+/// What the camera tasks tell the mosaic.
+enum Event {
+    Frame { id: String, mat: Mat },
+    Left { id: String },
+}
+
+#[derive(Clone)]
+struct Counters {
+    received: Counter,
+    decoded: Counter,
+    decode_failures: Counter,
+}
+
+/// What every camera task is set up with.
+#[derive(Clone)]
+struct Settings {
+    delay: Duration,
+    processors: Vec<ProcessorSpec>,
+    counters: Counters,
+}
+
+pub async fn run(args: ViewArgs) -> anyhow::Result<ExitCode> {
+    pipeline::check_order(&args.processors)?;
+    let settings = Settings {
+        delay: Duration::from_millis(args.delay),
+        processors: args.processors.clone(),
+        counters: Counters {
+            received: metrics::counter(
+                "view_frames_received_total",
+                "Frames received from the cameras",
+            ),
+            decoded: metrics::counter("view_frames_decoded_total", "Frames decoded successfully"),
+            decode_failures: metrics::counter(
+                "view_frame_decode_failures_total",
+                "Frames that failed to decode",
+            ),
+        },
+    };
+    for spec in &args.processors {
+        info!("Processing frames with {spec}");
+    }
+
+    let (events_tx, mut events) = channel(64);
+    let mut cameras = JoinSet::new();
+    let mut incoming = if args.srt.is_listener() {
+        let (listener, incoming) = args.srt.bind_srt().await?;
+        info!("Waiting for a connection on {} …", args.srt);
+        Some((listener, incoming))
+    } else {
+        let socket = connect(&args.srt, "view").await?;
+        let id = args.srt.param("streamid").unwrap_or("camera").to_string();
+        let task = camera(socket, id.clone(), settings.clone(), events_tx.clone());
+        let task = until_left(task, id.clone(), events_tx.clone());
+        cameras.spawn(task.instrument(info_span!("camera", %id)));
+        None
+    };
+
+    let mut mosaic = Mosaic::default();
+    // Stream IDs with a camera connected right now, each shown on its own tile
+    let mut connected = HashSet::new();
+    let mut refresh = interval(Duration::from_secs(1) / REFRESH_RATE);
+    let mut saved_at: Option<Instant> = None;
+    loop {
+        tokio::select! {
+            () = shutdown::wait() => break,
+            Some(event) = events.recv() => match event {
+                Event::Frame { id, mat } => mosaic.update(&id, &mat, Instant::now())?,
+                Event::Left { id } => {
+                    connected.remove(&id);
+                    mosaic.disconnected(&id);
+                    // A called camera is the only one there will be
+                    if incoming.is_none() {
+                        break;
+                    }
+                }
+            },
+            Some(request) = next_request(&mut incoming) => {
+                // Cameras without a stream ID are shown under their address
+                let id = match request.stream_id() {
+                    Some(id) => id.to_string(),
+                    None => request.remote().to_string(),
+                };
+                let span = info_span!("camera", %id, peer = %request.remote());
+                if connected.insert(id.clone()) {
+                    let task = accept(request, id.clone(), settings.clone(), events_tx.clone());
+                    let task = until_left(task, id, events_tx.clone());
+                    cameras.spawn(task.instrument(span));
+                } else {
+                    cameras.spawn(reject_duplicate(request).instrument(span));
+                }
+            }
+            _ = refresh.tick() => {
+                let now = Instant::now();
+                if let Some(dir) = &args.save
+                    && saved_at.is_none_or(|at| now.duration_since(at) >= SAVE_INTERVAL)
+                {
+                    mosaic.save(dir, now)?;
+                    saved_at = Some(now);
+                }
+                if !args.headless {
+                    highgui::imshow(&args.title, &mosaic.compose(now)?)?;
+                    if highgui::wait_key(1)? == 27 {
+                        info!("ESC pressed, exiting");
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    // Winds the camera tasks down as well
+    shutdown::request();
+    drop(events);
+    while let Some(result) = cameras.join_next().await {
+        if let Err(e) = result? {
+            warn!("Camera failed: {e:#}");
+        }
+    }
+    if let Some(dir) = &args.save {
+        mosaic.save(dir, Instant::now())?;
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+async fn next_request(
+    incoming: &mut Option<(SrtListener, SrtIncoming)>,
+) -> Option<ConnectionRequest> {
+    match incoming {
+        Some((_listener, incoming)) => incoming.incoming().next().await,
+        None => future::pending().await,
+    }
+}
+
+/// Decodes every frame of one camera and runs it through `pipeline`, until the camera task stops
+/// sending. Blocks, so it runs on its own thread.
+fn process_frames(
+    mut received: Receiver<Received>,
+    processed: Sender<(Instant, Frame)>,
+    mut pipeline: Pipeline,
+    stream: String,
+    clock: ClockSync,
+    counters: Counters,
+) {
+    while let Some(Received {
+        seq,
+        captured_at_us,
+        delivered_at,
+        payload,
+    }) = received.blocking_recv()
+    {
+        // Decoded on arrival, so showing it on time only takes the blit
+        let vec_u8 = Vector::<u8>::from_slice(&payload);
+        let mut mat = match imgcodecs::imdecode(&vec_u8, imgcodecs::IMREAD_COLOR) {
+            Ok(mat) => {
+                counters.decoded.inc();
+                mat
+            }
+            Err(e) => {
+                counters.decode_failures.inc();
+                warn!("Failed to decode frame {seq}: {e}");
+                continue;
+            }
+        };
+
+        let mut context = FrameContext {
+            stream: stream.clone(),
+            seq,
+            captured_at_us: clock.to_local_us(captured_at_us),
+            motion: false,
+            changed: 0.0,
+        };
+        if let Err(e) = pipeline.process(&mut mat, &mut context) {
+            warn!("Dropping frame {seq}: {e:#}");
+            continue;
+        }
+
+        let frame = Frame {
+            seq,
+            captured_at_us,
+            mat,
+        };
+        if processed.blocking_send((delivered_at, frame)).is_err() {
+            break;
+        }
+    }
+    if let Err(e) = pipeline.finish() {
+        warn!("{e:#}");
+    }
+}
+
+/// Refuses the handshake of a camera whose stream ID is already connected, instead of letting
+/// the two fight over one tile.
+async fn reject_duplicate(request: ConnectionRequest) -> anyhow::Result<()> {
+    let peer = request.remote();
+    warn!("Stream ID already connected, rejecting {peer}");
+    request
+        .reject(RejectReason::Server(ServerRejectReason::Conflict))
+        .await
+        .with_context(|| format!("can't reject {peer}"))
+}
+
+/// Runs the camera `task`, then tells the mosaic that `id` left, however the task ended.
+async fn until_left(
+    task: impl Future<Output = anyhow::Result<()>>,
+    id: String,
+    events: Sender<Event>,
+) -> anyhow::Result<()> {
+    let result = task.await;
+    let _ = events.send(Event::Left { id }).await;
+    result
+}
+
+async fn accept(
+    request: ConnectionRequest,
+    id: String,
+    settings: Settings,
+    events: Sender<Event>,
+) -> anyhow::Result<()> {
+    let peer = request.remote();
+    let socket = request
+        .accept(None)
+        .await
+        .with_context(|| format!("can't accept {peer}"))?;
+    info!("Camera connected");
+    camera(StatsSocket::from_env(socket, "view"), id, settings, events).await
+}
+
+/// Receives one camera until it leaves: frames are decoded and run through its own processing
+/// pipeline on a blocking thread, then held in its playout buffer and passed on to the mosaic
+/// when due.
+async fn camera(
+    mut socket: StatsSocket,
+    id: String,
+    settings: Settings,
+    events: Sender<Event>,
+) -> anyhow::Result<()> {
+    let Settings {
+        delay,
+        processors,
+        counters,
+    } = settings;
+
+    // Estimate the camera's clock offset so its capture timestamps are comparable with ours
+    let clock = latency::probe_clock(&mut socket, CLOCK_PROBES, Duration::from_secs(1)).await?;
+    match clock.round_trip() {
+        Some(rtt) => {
+            info!(
+                "Clock offset to camera: {:.1} ms (best round trip {:.1} ms over {} probes)",
+                clock.offset_us() as f64 / 1000.0,
+                rtt.as_secs_f64() * 1000.0,
+                clock.samples()
+            );
+            socket.record_round_trip(rtt);
+        }
+        None => warn!("Camera didn't answer clock probes, assuming synchronized clocks"),
+    }
+
+    info!("Waiting for frames...");
+
+    let mut latencies = LatencyStats::new(1000);
+    let mut frame_count = 0u64;
+    let mut sequence = SequenceTracker::new(&id);
+    // Warn once per overload instead of once per dropped frame
+    let mut overloaded = false;
+    let mut playout = PlayoutBuffer::new(&id, delay, PLAYOUT_CAPACITY);
+
+    // Decoding, motion detection and recording are blocking OpenCV calls, keep them off the runtime
+    let (received_tx, received_rx) = channel(PLAYOUT_CAPACITY);
+    let (processed_tx, mut processed) = channel(PLAYOUT_CAPACITY);
+    let pipeline = Pipeline::from_specs(&processors);
+    let (stream, local_clock, worker_counters) = (id.clone(), clock.clone(), counters.clone());
+    let span = Span::current();
+    let worker = tokio::task::spawn_blocking(move || {
+        let _entered = span.enter();
+        process_frames(
+            received_rx,
+            processed_tx,
+            pipeline,
+            stream,
+            local_clock,
+            worker_counters,
+        );
+    });
+
+    loop {
+        let due = playout.next_due();
+        tokio::select! {
+            next = shutdown::until(socket.next()) => {
+                let Some(Some(frame_res)) = next else {
+                    break;
+                };
+                let (delivered_at, bytes) = match frame_res {
+                    Ok(frame) => frame,
+                    Err(e) => {
+                        warn!("Error receiving frame: {e}");
+                        continue;
+                    }
+                };
+                let (seq, captured_at_us, payload) = match Message::decode(bytes) {
+                    Ok(Message::Frame {
+                        seq,
+                        captured_at_us,
+                        payload,
+                    }) => {
+                        sequence.track(seq);
+                        (seq, captured_at_us, payload)
+                    }
+                    Ok(_) => continue,
+                    Err(e) => {
+                        warn!("Invalid message: {e}");
+                        continue;
+                    }
+                };
+                counters.received.inc();
+                debug!("Received frame {seq}: {} bytes", payload.len());
+                if payload.is_empty() {
+                    warn!("Empty frame {seq}");
+                    continue;
+                }
+
+                let received = Received { seq, captured_at_us, delivered_at, payload };
+                if received_tx.try_send(received).is_ok() {
+                    overloaded = false;
+                } else {
+                    if !overloaded {
+                        warn!("Processing can't keep up, dropping frames from {seq}");
+                    }
+                    overloaded = true;
+                }
+            }
+            Some((delivered_at, frame)) = processed.recv() => {
+                let seq = frame.seq;
+                if !playout.push(delivered_at, Instant::now(), frame) {
+                    debug!("Frame {seq} arrived after its playout time, dropped");
+                }
+            }
+            () = sleep_until(due.unwrap_or_else(Instant::now).into()), if due.is_some() => {
+                let Some(Frame { seq, captured_at_us, mut mat }) = playout.pop(Instant::now()) else {
+                    continue;
+                };
+                debug!("Showing frame {seq}, {} more buffered", playout.len());
+
+                let captured_at_local = clock.to_local_us(captured_at_us);
+                latencies.record(Duration::from_micros(now_us().saturating_sub(captured_at_local)));
+                frame_count += 1;
+
+                if let Some(summary) = latencies.summary() {
+                    if frame_count.is_multiple_of(30) {
+                        info!("Glass-to-glass latency: {summary}");
+                        info!("Playout: {}", playout.totals());
+                    }
+                    let label = format!(
+                        "latency p50 {:.0} ms p99 {:.0} ms",
+                        summary.p50.as_secs_f64() * 1000.0,
+                        summary.p99.as_secs_f64() * 1000.0
+                    );
+                    imgproc::put_text(
+                        &mut mat,
+                        &label,
+                        Point::new(10, 60),
+                        imgproc::FONT_HERSHEY_SIMPLEX,
+                        0.7,
+                        Scalar::new(0.0, 255.0, 0.0, 0.0),
+                        2,
+                        imgproc::LINE_AA,
+                        false,
+                    )?;
+                }
+
+                let shown = Event::Frame { id: id.clone(), mat };
+                if events.send(shown).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    // The processing thread finishes its pipeline once it has nothing left to work on
+    drop(received_tx);
+    drop(processed);
+    worker.await?;
+    socket.close().await.ok();
+    if let Some(summary) = latencies.summary() {
+        info!("Final glass-to-glass latency: {summary}");
+    }
+    info!("Frames: {}", sequence.totals());
+    info!("Playout: {}", playout.totals());
+    Ok(())
+}
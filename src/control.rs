@@ -0,0 +1,90 @@
+//! Local HTTP JSON API to control a running daemon:
+//!
+//! - `GET /sessions` lists the open SRT sockets: peer, stream ID, uptime and latest statistics,
+//! - `GET /routes` and `GET /routes/{name}` report route status,
+//! - `POST /routes` creates a route from a JSON body shaped like a `[[route]]` config entry,
+//! - `DELETE /routes/{name}` stops and removes a route,
+//! - `POST /routes/{name}/pause` and `/resume` hold or continue a playout.
+//!
+//! Errors come back as `{"error": "..."}` with a 4xx status.
+
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+use serde_json::json;
+use tokio::net::TcpListener;
+
+use crate::{
+    daemon::{RouteConfig, RouteStatus, Supervisor},
+    http::{self, Request, Response},
+    stats,
+};
+
+/// Serves the API for `supervisor` on `listener` until the task is dropped.
+pub async fn serve_listener(
+    listener: TcpListener,
+    supervisor: Arc<Mutex<Supervisor>>,
+) -> io::Result<()> {
+    http::serve_listener(listener, move |request| {
+        let response = handle(&supervisor, request);
+        async move { response }
+    })
+    .await
+}
+
+fn error(status: u16, message: impl std::fmt::Display) -> Response {
+    Response::json(status, &json!({ "error": message.to_string() }))
+}
+
+fn route_or_404(name: &str, status: Option<RouteStatus>) -> Response {
+    match status {
+        Some(status) => Response::json(200, &status),
+        None => error(404, format!("no route {name:?}")),
+    }
+}
+
+fn handle(supervisor: &Mutex<Supervisor>, request: Request) -> Response {
+    let path = request.path.split('?').next().unwrap_or("/");
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let mut supervisor = supervisor.lock().unwrap();
+
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["sessions"]) => Response::json(200, &stats::sessions()),
+        ("GET", ["routes"]) => Response::json(200, &supervisor.statuses()),
+        ("POST", ["routes"]) => create(&mut supervisor, &request.body),
+        ("GET", ["routes", name]) => route_or_404(name, supervisor.status(name)),
+        ("DELETE", ["routes", name]) => {
+            if supervisor.remove(name) {
+                Response::text(204, "")
+            } else {
+                error(404, format!("no route {name:?}"))
+            }
+        }
+        ("POST", ["routes", name, "pause"]) => {
+            route_or_404(name, supervisor.set_paused(name, true))
+        }
+        ("POST", ["routes", name, "resume"]) => {
+            route_or_404(name, supervisor.set_paused(name, false))
+        }
+        (_, ["sessions" | "routes", ..]) => error(405, "method not allowed"),
+        _ => error(404, "not found"),
+    }
+}
+
+fn create(supervisor: &mut Supervisor, body: &[u8]) -> Response {
+    let route: RouteConfig = match serde_json::from_slice(body) {
+        Ok(route) => route,
+        Err(e) => return error(400, format!("invalid route: {e}")),
+    };
+    if let Err(e) = route.validate() {
+        return error(400, e);
+    }
+
+    let name = route.name.clone();
+    if !supervisor.add(route) {
+        return error(409, format!("route {name:?} is already running"));
+    }
+    Response::json(201, &supervisor.status(&name))
+}
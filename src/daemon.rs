@@ -0,0 +1,599 @@
+//! Runs many routes from one TOML config: each route forwards an input to one or more outputs
+//! in its own task, is restarted when it stops and survives config reloads it isn't part of.
+//!
+//! ```toml
+//! [[route]]
+//! name = "studio-a"
+//! input = "udp://0.0.0.0:12345"
+//! outputs = ["srt://:1234", "udp://239.0.0.1:5000?ttl=4"]
+//!
+//! [[route]]
+//! name = "playout"
+//! input = "bars.ts"          # files and testsrc play out at their PCR rate
+//! process = "sequence"       # number the messages so `recv` can check them
+//! outputs = ["srt://127.0.0.1:2223"]
+//! restart = "on-failure"     # "always" (default), "on-failure" or "never"
+//! ```
+//!
+//! Routes can also be created, removed and paused over the [control API](crate::control).
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    net::SocketAddr,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, bail};
+use bytes::Bytes;
+use futures::{SinkExt, TryStreamExt, future};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    net::TcpListener,
+    signal::unix::{SignalKind, signal},
+    sync::watch,
+    task::JoinHandle,
+};
+use tracing::{Instrument, error, info, info_span, warn};
+
+use crate::{
+    control,
+    endpoint::ByteSink,
+    endpoint::Endpoint,
+    gateway, metrics,
+    protocol::{self, Message},
+    shutdown, stats,
+    ts::{Pacing, TsChunker, TsPacer},
+};
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// A route that failed after running this long is restarted without the accumulated backoff.
+const HEALTHY_RUN: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default, rename = "route")]
+    pub routes: Vec<RouteConfig>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RouteConfig {
+    pub name: String,
+    pub input: Source,
+    #[serde(default)]
+    pub process: Process,
+    pub outputs: Vec<Endpoint>,
+    #[serde(default)]
+    pub restart: Restart,
+}
+
+/// A `srt://`, `udp://` or `tcp://` URL, or anything `send` accepts as a file.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Source {
+    Endpoint(Endpoint),
+    File(String),
+}
+
+impl TryFrom<String> for Source {
+    type Error = anyhow::Error;
+
+    fn try_from(input: String) -> anyhow::Result<Self> {
+        if input.contains("://") {
+            Ok(Self::Endpoint(input.parse()?))
+        } else {
+            Ok(Self::File(input))
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Endpoint(endpoint) => endpoint.fmt(f),
+            Self::File(path) => f.write_str(path),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Process {
+    /// Forward the TS payloads as they are.
+    #[default]
+    None,
+    /// Wrap them in numbered messages of six TS packets and finish with the end-of-stream
+    /// totals, like `send --sequence`.
+    Sequence,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Restart {
+    /// Also when the input ended, e.g. to loop a file or wait for the next TCP peer.
+    #[default]
+    Always,
+    OnFailure,
+    Never,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("cannot read {}", path.display()))?;
+        let config: Self =
+            toml::from_str(&text).with_context(|| format!("invalid config {}", path.display()))?;
+
+        let mut names = HashSet::new();
+        for route in &config.routes {
+            if !names.insert(&route.name) {
+                bail!("route {:?} is defined twice", route.name);
+            }
+            route.validate()?;
+        }
+        Ok(config)
+    }
+}
+
+impl RouteConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.name.is_empty() || self.name.contains('/') {
+            bail!("invalid route name {:?}", self.name);
+        }
+        if self.outputs.is_empty() {
+            bail!("route {:?} has no outputs", self.name);
+        }
+        Ok(())
+    }
+}
+
+/// Where a route was defined: config reloads leave the ones created over the API alone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Origin {
+    Config,
+    Api,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RouteState {
+    /// Opening its outputs and input.
+    #[default]
+    Starting,
+    Running,
+    Paused,
+    /// Waiting to restart after it stopped.
+    Backoff,
+    /// Done for good, per its restart policy.
+    Stopped,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RouteStatus {
+    pub name: String,
+    pub input: String,
+    pub outputs: Vec<String>,
+    pub process: Process,
+    pub restart: Restart,
+    pub origin: Origin,
+    pub state: RouteState,
+    /// Time since the current run started forwarding.
+    pub uptime_ms: Option<u128>,
+    /// Messages and bytes forwarded by the current run.
+    pub messages: u64,
+    pub bytes: u64,
+    pub restarts: u64,
+    pub last_error: Option<String>,
+}
+
+#[derive(Default)]
+struct Progress {
+    state: RouteState,
+    running_since: Option<Instant>,
+    messages: u64,
+    bytes: u64,
+    restarts: u64,
+    last_error: Option<String>,
+}
+
+/// What a route's task shares with the supervisor.
+struct RouteShared {
+    paused: watch::Sender<bool>,
+    progress: Mutex<Progress>,
+}
+
+impl RouteShared {
+    fn update(&self, f: impl FnOnce(&mut Progress)) {
+        f(&mut self.progress.lock().unwrap());
+    }
+}
+
+/// Runs `route` once: opens its outputs, then its input, and forwards until the input ends.
+///
+/// While paused, a file input is held back and resumes where it stopped; a live input can't be
+/// held, so it's dropped until the route is resumed. On shutdown the input stops early but the
+/// outputs are still finished and closed.
+async fn run_route(route: &RouteConfig, shared: &RouteShared) -> anyhow::Result<()> {
+    let open = async {
+        // Outputs first like `relay`, so listening outputs have their peers before data flows
+        let sinks = future::try_join_all(route.outputs.iter().map(Endpoint::open_output)).await?;
+        let (source, pacer) = match &route.input {
+            Source::Endpoint(endpoint) => (
+                gateway::align_ts(endpoint.open_input().await?, endpoint.scheme),
+                None,
+            ),
+            Source::File(path) => (
+                gateway::read_ts_file(path).await?,
+                Some(TsPacer::new(Pacing::Pcr)),
+            ),
+        };
+        anyhow::Ok((sinks, source, pacer))
+    };
+    let Some(opened) = shutdown::until(open).await else {
+        return Ok(());
+    };
+    let (mut sinks, mut source, mut pacer) = opened?;
+    info!("Forwarding {}", route.input);
+    shared.update(|progress| {
+        progress.state = RouteState::Running;
+        progress.running_since = Some(Instant::now());
+    });
+
+    let mut paused = shared.paused.subscribe();
+    // Numbered messages carry fewer packets, so that with the header they still fit 1316 bytes
+    let mut rechunk = (route.process == Process::Sequence)
+        .then(|| TsChunker::new(protocol::SEQUENCED_TS_PAYLOAD_SIZE));
+    let (mut messages, mut bytes) = (0u64, 0u64);
+    while let Some(payload) = shutdown::until(source.try_next())
+        .await
+        .transpose()?
+        .flatten()
+    {
+        if *paused.borrow_and_update() {
+            let Some(pacer) = pacer.as_mut() else {
+                continue;
+            };
+            let since = Instant::now();
+            match shutdown::until(paused.wait_for(|paused| !paused)).await {
+                Some(resumed) => drop(resumed?),
+                None => break,
+            }
+            pacer.delay(since.elapsed());
+        }
+        if let Some(pacer) = pacer.as_mut() {
+            tokio::time::sleep_until(pacer.deadline(&payload).into()).await;
+        }
+
+        let payloads = match rechunk.as_mut() {
+            Some(chunker) => chunker.push(&payload),
+            None => vec![payload],
+        };
+        for payload in payloads {
+            bytes += payload.len() as u64;
+            let message = match route.process {
+                Process::None => payload,
+                Process::Sequence => Message::Data {
+                    seq: messages,
+                    payload,
+                }
+                .encode(),
+            };
+            send_all(&mut sinks, message).await?;
+            messages += 1;
+        }
+        shared.update(|progress| {
+            progress.messages = messages;
+            progress.bytes = bytes;
+        });
+    }
+
+    if let Some(mut chunker) = rechunk {
+        if let Some(payload) = chunker.flush() {
+            bytes += payload.len() as u64;
+            let message = Message::Data {
+                seq: messages,
+                payload,
+            };
+            send_all(&mut sinks, message.encode()).await?;
+            messages += 1;
+        }
+        let end = Message::EndOfStream {
+            frames: messages,
+            bytes,
+        };
+        send_all(&mut sinks, end.encode()).await?;
+    }
+    for sink in &mut sinks {
+        sink.close().await?;
+    }
+    info!(messages, bytes, "Input ended");
+    Ok(())
+}
+
+async fn send_all(sinks: &mut [ByteSink], message: Bytes) -> anyhow::Result<()> {
+    for sink in sinks {
+        sink.send(message.clone()).await?;
+    }
+    Ok(())
+}
+
+/// Runs `route` until its restart policy says to stop, backing off between failed attempts.
+async fn supervise(route: RouteConfig, shared: Arc<RouteShared>) {
+    let restarts = metrics::counter_with(
+        "daemon_route_restarts_total",
+        "Times a route was restarted",
+        &[("route", &route.name)],
+    );
+    let mut backoff = MIN_BACKOFF;
+
+    loop {
+        shared.update(|progress| {
+            progress.state = RouteState::Starting;
+            progress.running_since = None;
+            progress.messages = 0;
+            progress.bytes = 0;
+        });
+        let started = Instant::now();
+        let result = run_route(&route, &shared).await;
+        if let Err(e) = &result {
+            error!("Failed: {e:#}");
+            shared.update(|progress| progress.last_error = Some(format!("{e:#}")));
+        }
+
+        let restart = match route.restart {
+            _ if shutdown::requested() => false,
+            Restart::Always => true,
+            Restart::OnFailure => result.is_err(),
+            Restart::Never => false,
+        };
+        if !restart {
+            info!("Stopped");
+            shared.update(|progress| progress.state = RouteState::Stopped);
+            return;
+        }
+
+        if result.is_ok() || started.elapsed() >= HEALTHY_RUN {
+            backoff = MIN_BACKOFF;
+        }
+        info!("Restarting in {backoff:?}");
+        shared.update(|progress| progress.state = RouteState::Backoff);
+        if shutdown::until(tokio::time::sleep(backoff)).await.is_none() {
+            shared.update(|progress| progress.state = RouteState::Stopped);
+            return;
+        }
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+        restarts.inc();
+        shared.update(|progress| progress.restarts += 1);
+    }
+}
+
+struct Running {
+    config: RouteConfig,
+    origin: Origin,
+    shared: Arc<RouteShared>,
+    task: JoinHandle<()>,
+}
+
+impl Running {
+    fn start(config: RouteConfig, origin: Origin) -> Self {
+        info!(route = %config.name, "Starting route");
+        let shared = Arc::new(RouteShared {
+            paused: watch::Sender::new(false),
+            progress: Mutex::default(),
+        });
+        // Everything the route logs, its SRT sessions included, carries its name
+        let span = info_span!(parent: None, "route", route = %config.name);
+        let task = tokio::spawn(supervise(config.clone(), shared.clone()).instrument(span));
+        Self {
+            config,
+            origin,
+            shared,
+            task,
+        }
+    }
+
+    fn stop(&self) {
+        info!(route = %self.config.name, "Stopping route");
+        self.task.abort();
+    }
+
+    fn status(&self) -> RouteStatus {
+        let progress = self.shared.progress.lock().unwrap();
+        let state = match progress.state {
+            RouteState::Running if *self.shared.paused.borrow() => RouteState::Paused,
+            state => state,
+        };
+        RouteStatus {
+            name: self.config.name.clone(),
+            input: self.config.input.to_string(),
+            outputs: self
+                .config
+                .outputs
+                .iter()
+                .map(Endpoint::to_string)
+                .collect(),
+            process: self.config.process,
+            restart: self.config.restart,
+            origin: self.origin,
+            state,
+            uptime_ms: progress
+                .running_since
+                .map(|since| since.elapsed().as_millis()),
+            messages: progress.messages,
+            bytes: progress.bytes,
+            restarts: progress.restarts,
+            last_error: progress.last_error.clone(),
+        }
+    }
+}
+
+/// The supervised routes, keyed by name.
+#[derive(Default)]
+pub struct Supervisor {
+    routes: HashMap<String, Running>,
+}
+
+impl Supervisor {
+    /// Starts the routes of `config`. Routes it no longer lists are stopped and changed ones are
+    /// restarted; unchanged routes keep running undisturbed unless they had stopped. Routes
+    /// created over the API are left alone.
+    pub fn apply(&mut self, config: Config) {
+        let mut wanted: HashMap<_, _> = config
+            .routes
+            .into_iter()
+            .map(|route| (route.name.clone(), route))
+            .collect();
+
+        self.routes.retain(|name, running| {
+            if running.origin == Origin::Api {
+                return true;
+            }
+            let keep = wanted.get(name) == Some(&running.config) && !running.task.is_finished();
+            if keep {
+                wanted.remove(name);
+            } else {
+                running.stop();
+            }
+            keep
+        });
+
+        for (name, route) in wanted {
+            if self.routes.contains_key(&name) {
+                warn!(route = %name, "Route was created over the API, ignoring the config");
+                continue;
+            }
+            self.routes
+                .insert(name, Running::start(route, Origin::Config));
+        }
+    }
+
+    /// Starts `route` on behalf of the API. Returns `false` if a route by that name is still
+    /// running; a stopped one is replaced.
+    pub fn add(&mut self, route: RouteConfig) -> bool {
+        if self
+            .routes
+            .get(&route.name)
+            .is_some_and(|running| !running.task.is_finished())
+        {
+            return false;
+        }
+        self.routes
+            .insert(route.name.clone(), Running::start(route, Origin::Api));
+        true
+    }
+
+    /// Stops and forgets the route `name`, whichever way it was defined.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let Some(running) = self.routes.remove(name) else {
+            return false;
+        };
+        running.stop();
+        true
+    }
+
+    pub fn set_paused(&mut self, name: &str, paused: bool) -> Option<RouteStatus> {
+        let running = self.routes.get(name)?;
+        running.shared.paused.send_replace(paused);
+        info!(
+            route = name,
+            "Route {}",
+            if paused { "paused" } else { "resumed" }
+        );
+        Some(running.status())
+    }
+
+    pub fn status(&self, name: &str) -> Option<RouteStatus> {
+        self.routes.get(name).map(Running::status)
+    }
+
+    /// Status of every route, sorted by name.
+    pub fn statuses(&self) -> Vec<RouteStatus> {
+        let mut statuses: Vec<_> = self.routes.values().map(Running::status).collect();
+        statuses.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        statuses
+    }
+
+    /// Forgets every route, returning a future that resolves once their tasks have ended, e.g.
+    /// after a shutdown request let them finish their outputs.
+    pub fn drain(&mut self) -> impl Future<Output = ()> + use<> {
+        let tasks: Vec<_> = self
+            .routes
+            .drain()
+            .map(|(_, running)| running.task)
+            .collect();
+        async move {
+            future::join_all(tasks).await;
+        }
+    }
+
+    /// Names of the routes currently supervised, sorted.
+    pub fn route_names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.routes.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        for running in self.routes.values() {
+            running.task.abort();
+        }
+    }
+}
+
+/// Runs the routes of the config at `path`, reloading it on SIGHUP, and serves the control API
+/// on `api` until shutdown. A config that fails to load on reload is reported and the running
+/// routes are left alone.
+pub async fn run(path: Option<&Path>, api: Option<SocketAddr>) -> anyhow::Result<()> {
+    // Before any route opens a socket, so every session listed by the API has statistics
+    if api.is_some() {
+        stats::sample_always();
+    }
+    let supervisor = Arc::new(Mutex::new(Supervisor::default()));
+    if let Some(path) = path {
+        let config = Config::load(path)?;
+        info!(
+            "Running {} routes from {}",
+            config.routes.len(),
+            path.display()
+        );
+        supervisor.lock().unwrap().apply(config);
+    }
+
+    if let Some(addr) = api {
+        let listener = TcpListener::bind(addr).await?;
+        info!("Control API on http://{}", listener.local_addr()?);
+        let supervisor = supervisor.clone();
+        tokio::spawn(async move {
+            if let Err(e) = control::serve_listener(listener, supervisor).await {
+                error!("Control API failed: {e}");
+            }
+        });
+    }
+
+    let mut hangup = signal(SignalKind::hangup())?;
+    while let Some(Some(())) = shutdown::until(hangup.recv()).await {
+        let Some(path) = path else {
+            warn!("SIGHUP, but there is no config file to reload");
+            continue;
+        };
+        info!("SIGHUP, reloading {}", path.display());
+        match Config::load(path) {
+            Ok(config) => supervisor.lock().unwrap().apply(config),
+            Err(e) => error!("Keeping the running routes, {e:#}"),
+        }
+    }
+
+    info!("Stopping every route …");
+    let stopped = supervisor.lock().unwrap().drain();
+    stopped.await;
+    info!("All routes stopped");
+    Ok(())
+}
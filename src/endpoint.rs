@@ -0,0 +1,342 @@
+//! URL-style stream endpoints (`srt://`, `udp://`, `tcp://`) opened as byte streams and sinks.
+//!
+//! `srt://host:port` calls, `srt://:port` listens; `tcp://host:port?listen` accepts one
+//! connection instead of connecting (matching ffmpeg's `?listen=1`). A multicast `udp://`
+//! input joins the group, optionally on `?iface=<ipv4>`. IPv6 hosts are bracketed as in
+//! `srt://[::1]:1234`.
+
+use std::{
+    collections::HashMap,
+    fmt, io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    pin::Pin,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, anyhow, bail};
+use bytes::Bytes;
+use futures::{Sink, SinkExt, Stream, StreamExt, TryStreamExt, future, sink, stream};
+use serde::Deserialize;
+use srt_tokio::{SrtIncoming, SrtListener, SrtSocket};
+
+use crate::{error::Error, stats::StatsSocket};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream, UdpSocket},
+};
+use tracing::info;
+
+pub type ByteStream = Pin<Box<dyn Stream<Item = io::Result<Bytes>> + Send>>;
+pub type ByteSink = Pin<Box<dyn Sink<Bytes, Error = io::Error> + Send>>;
+
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scheme {
+    Srt,
+    Udp,
+    Tcp,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Endpoint {
+    pub scheme: Scheme,
+    /// Empty for listeners bound on all interfaces (`srt://:1234`). IPv6 hosts are kept without
+    /// their brackets.
+    pub host: String,
+    pub port: u16,
+    pub params: HashMap<String, String>,
+}
+
+impl FromStr for Endpoint {
+    type Err = anyhow::Error;
+
+    fn from_str(url: &str) -> anyhow::Result<Self> {
+        let (scheme, rest) = url
+            .split_once("://")
+            .ok_or_else(|| anyhow!("missing scheme in {url:?}"))?;
+        let scheme = match scheme {
+            "srt" => Scheme::Srt,
+            "udp" => Scheme::Udp,
+            "tcp" => Scheme::Tcp,
+            other => bail!("unsupported scheme {other:?} in {url:?}"),
+        };
+
+        let (authority, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let (host, port) = authority
+            .rsplit_once(':')
+            .ok_or_else(|| anyhow!("missing port in {url:?}"))?;
+        let host = match host.strip_prefix('[') {
+            Some(bracketed) => bracketed
+                .strip_suffix(']')
+                .ok_or_else(|| anyhow!("unclosed [ in {url:?}"))?,
+            None if host.contains(':') => bail!("IPv6 host in {url:?} must be in brackets"),
+            None => host,
+        };
+        let port = port
+            .parse()
+            .with_context(|| format!("invalid port in {url:?}"))?;
+
+        let params = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (key.to_string(), value.to_string())
+            })
+            .collect();
+
+        Ok(Self {
+            scheme,
+            host: host.to_string(),
+            port,
+            params,
+        })
+    }
+}
+
+impl TryFrom<String> for Endpoint {
+    type Error = anyhow::Error;
+
+    fn try_from(url: String) -> anyhow::Result<Self> {
+        url.parse()
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scheme = match self.scheme {
+            Scheme::Srt => "srt",
+            Scheme::Udp => "udp",
+            Scheme::Tcp => "tcp",
+        };
+        write!(f, "{scheme}://{}", authority(&self.host, self.port))
+    }
+}
+
+/// `host:port`, with an IPv6 host in brackets.
+fn authority(host: &str, port: u16) -> String {
+    if host.contains(':') {
+        format!("[{host}]:{port}")
+    } else {
+        format!("{host}:{port}")
+    }
+}
+
+impl Endpoint {
+    pub fn param(&self, key: &str) -> Option<&str> {
+        self.params.get(key).map(String::as_str)
+    }
+
+    /// Whether `?seq=1` asks for numbered messages with end-of-stream totals instead of raw TS.
+    pub fn sequenced(&self) -> bool {
+        matches!(self.param("seq"), Some("" | "1" | "true"))
+    }
+
+    /// Listeners wait for the peer: SRT without a host, or an explicit `?listen`/`?mode=listener`.
+    pub fn is_listener(&self) -> bool {
+        self.params.contains_key("listen")
+            || self.param("mode") == Some("listener")
+            || (self.scheme == Scheme::Srt && self.host.is_empty())
+    }
+
+    fn bind_host(&self) -> &str {
+        if self.host.is_empty() {
+            "0.0.0.0"
+        } else {
+            &self.host
+        }
+    }
+
+    fn latency(&self) -> anyhow::Result<Duration> {
+        let millis = self
+            .param("latency")
+            .map(str::parse)
+            .transpose()
+            .context("invalid latency")?
+            .unwrap_or(120);
+        Ok(Duration::from_millis(millis))
+    }
+
+    /// Opens an `srt://` endpoint as a message socket, sampled under `name` for stats and metrics.
+    ///
+    /// Fails with [`Error::HandshakeRejected`] if the listener refused the call, and with
+    /// [`Error::Connection`] otherwise.
+    pub async fn connect_srt(&self, name: &str) -> anyhow::Result<StatsSocket> {
+        if self.scheme != Scheme::Srt {
+            bail!("{self} is not an srt:// endpoint");
+        }
+        let builder = SrtSocket::builder().latency(self.latency()?);
+        let result = if self.is_listener() {
+            builder
+                .listen_on(authority(self.bind_host(), self.port).as_str())
+                .await
+        } else {
+            builder
+                .call(
+                    authority(&self.host, self.port).as_str(),
+                    self.param("streamid"),
+                )
+                .await
+        };
+        let socket = result.map_err(|e| {
+            // srt-tokio reports a rejected handshake as a refused connection
+            let error = if e.kind() == io::ErrorKind::ConnectionRefused && !self.is_listener() {
+                Error::HandshakeRejected {
+                    endpoint: self.to_string(),
+                }
+            } else {
+                self.connection_error()
+            };
+            anyhow::Error::new(e).context(error)
+        })?;
+        Ok(StatsSocket::from_env(socket, name))
+    }
+
+    /// Binds an `srt://:port` listener that takes any number of callers, each to be accepted
+    /// from the returned incoming requests.
+    pub async fn bind_srt(&self) -> anyhow::Result<(SrtListener, SrtIncoming)> {
+        if self.scheme != Scheme::Srt || !self.is_listener() {
+            bail!("{self} is not an srt:// listener");
+        }
+        SrtListener::builder()
+            .latency(self.latency()?)
+            .bind(authority(self.bind_host(), self.port).as_str())
+            .await
+            .with_context(|| self.connection_error())
+    }
+
+    fn connection_error(&self) -> Error {
+        Error::Connection {
+            endpoint: self.to_string(),
+        }
+    }
+
+    async fn connect_tcp(&self) -> anyhow::Result<TcpStream> {
+        if self.is_listener() {
+            let listener = TcpListener::bind((self.bind_host(), self.port))
+                .await
+                .with_context(|| self.connection_error())?;
+            let (stream, peer) = listener.accept().await?;
+            info!(%peer, "TCP peer {peer} connected on {self}");
+            Ok(stream)
+        } else {
+            Ok(TcpStream::connect((self.host.as_str(), self.port))
+                .await
+                .with_context(|| self.connection_error())?)
+        }
+    }
+
+    /// Opens the endpoint for reading; every item is one received message or read.
+    pub async fn open_input(&self) -> anyhow::Result<ByteStream> {
+        match self.scheme {
+            Scheme::Srt => {
+                let socket = self.connect_srt(&self.to_string()).await?;
+                Ok(socket.map_ok(|(_instant, bytes)| bytes).boxed())
+            }
+            Scheme::Udp => {
+                let socket = self
+                    .bind_udp_input()
+                    .await
+                    .with_context(|| self.connection_error())?;
+                let buf = vec![0u8; READ_BUFFER_SIZE];
+                Ok(
+                    stream::try_unfold((socket, buf), |(socket, mut buf)| async move {
+                        let n = socket.recv(&mut buf).await?;
+                        let bytes = Bytes::copy_from_slice(&buf[..n]);
+                        Ok(Some((bytes, (socket, buf))))
+                    })
+                    .boxed(),
+                )
+            }
+            Scheme::Tcp => {
+                let stream = self.connect_tcp().await?;
+                let buf = vec![0u8; READ_BUFFER_SIZE];
+                Ok(
+                    stream::try_unfold((stream, buf), |(mut stream, mut buf)| async move {
+                        let n = stream.read(&mut buf).await?;
+                        if n == 0 {
+                            return Ok(None);
+                        }
+                        let bytes = Bytes::copy_from_slice(&buf[..n]);
+                        Ok(Some((bytes, (stream, buf))))
+                    })
+                    .boxed(),
+                )
+            }
+        }
+    }
+
+    /// Opens the endpoint for writing; every item is sent as one message or datagram.
+    pub async fn open_output(&self) -> anyhow::Result<ByteSink> {
+        match self.scheme {
+            Scheme::Srt => {
+                let socket = self.connect_srt(&self.to_string()).await?;
+                Ok(Box::pin(socket.with(|bytes: Bytes| {
+                    future::ready(Ok::<_, io::Error>((Instant::now(), bytes)))
+                })))
+            }
+            Scheme::Udp => {
+                let any = if self.host.contains(':') {
+                    IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+                } else {
+                    IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+                };
+                let socket = UdpSocket::bind((any, 0)).await?;
+                if let Some(ttl) = self.param("ttl") {
+                    socket.set_multicast_ttl_v4(ttl.parse().context("invalid ttl")?)?;
+                }
+                socket.connect((self.host.as_str(), self.port)).await?;
+                Ok(Box::pin(sink::unfold(
+                    socket,
+                    |socket, bytes: Bytes| async move {
+                        socket.send(&bytes).await?;
+                        Ok::<_, io::Error>(socket)
+                    },
+                )))
+            }
+            Scheme::Tcp => {
+                let stream = self.connect_tcp().await?;
+                Ok(Box::pin(sink::unfold(
+                    stream,
+                    |mut stream, bytes: Bytes| async move {
+                        stream.write_all(&bytes).await?;
+                        Ok::<_, io::Error>(stream)
+                    },
+                )))
+            }
+        }
+    }
+
+    async fn bind_udp_input(&self) -> anyhow::Result<UdpSocket> {
+        let group = self
+            .host
+            .parse::<IpAddr>()
+            .ok()
+            .filter(IpAddr::is_multicast);
+        let socket = match group {
+            Some(IpAddr::V4(group)) => {
+                let socket =
+                    UdpSocket::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, self.port))).await?;
+                let iface = self
+                    .param("iface")
+                    .map(str::parse)
+                    .transpose()
+                    .context("invalid iface")?
+                    .unwrap_or(Ipv4Addr::UNSPECIFIED);
+                socket.join_multicast_v4(group, iface)?;
+                socket
+            }
+            Some(IpAddr::V6(group)) => {
+                let socket =
+                    UdpSocket::bind(SocketAddr::from((Ipv6Addr::UNSPECIFIED, self.port))).await?;
+                socket.join_multicast_v6(&group, 0)?;
+                socket
+            }
+            None => UdpSocket::bind((self.bind_host(), self.port)).await?,
+        };
+        Ok(socket)
+    }
+}
@@ -0,0 +1,80 @@
+//! What went wrong, for the failures worth telling apart, each with its own exit code.
+//!
+//! Functions keep returning `anyhow::Result`: an [`Error`] is attached as context where the
+//! failure happens, above its cause, and [`exit_code`] finds it again however much context was
+//! added on the way up.
+//!
+//! Every exit code of the commands (also listed in the README):
+//!
+//! | Code  | Meaning                                                        |
+//! |-------|----------------------------------------------------------------|
+//! | `0`   | success                                                        |
+//! | `1`   | any other error                                                |
+//! | `2`   | usage error, reported by clap                                  |
+//! | `4`   | [`Error::Connection`]                                          |
+//! | `5`   | [`Error::HandshakeRejected`]                                   |
+//! | `6`   | [`Error::Protocol`]                                            |
+//! | `7`   | [`Error::Demux`]                                               |
+//! | `8`   | [`Error::Mux`]                                                 |
+//! | `9`   | [`Error::Codec`]                                               |
+//! | `10`  | [`Error::Capture`]                                             |
+//! | `11`  | stream truncated or transfer interrupted, [`EXIT_TRUNCATED`]   |
+//! | `12`  | transfer checksum mismatch, [`EXIT_CHECKSUM_MISMATCH`]         |
+//! | `130` | interrupted, see [`shutdown`](crate::shutdown)                 |
+
+use std::process::ExitCode;
+
+pub const EXIT_CONNECTION: u8 = 4;
+pub const EXIT_HANDSHAKE_REJECTED: u8 = 5;
+pub const EXIT_PROTOCOL: u8 = 6;
+pub const EXIT_DEMUX: u8 = 7;
+pub const EXIT_MUX: u8 = 8;
+pub const EXIT_CODEC: u8 = 9;
+pub const EXIT_CAPTURE: u8 = 10;
+/// A receiver whose stream ended without, or short of, the sender's end-of-stream totals, or a
+/// transfer that can resume later.
+pub const EXIT_TRUNCATED: u8 = 11;
+/// The transferred file doesn't match the SHA-256 of its manifest.
+pub const EXIT_CHECKSUM_MISMATCH: u8 = 12;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The peer couldn't be reached, or the socket couldn't be opened.
+    #[error("can't connect {endpoint}")]
+    Connection { endpoint: String },
+    /// The SRT peer refused the handshake, e.g. over the passphrase or stream ID.
+    #[error("{endpoint} rejected the handshake")]
+    HandshakeRejected { endpoint: String },
+    /// A message that doesn't decode, or doesn't belong on the stream it arrived on.
+    #[error("invalid message")]
+    Protocol,
+    #[error("can't demux {input}")]
+    Demux { input: String },
+    #[error("can't mux {output}")]
+    Mux { output: String },
+    #[error("{codec} codec failed")]
+    Codec { codec: String },
+    #[error("can't capture from camera {device}")]
+    Capture { device: String },
+}
+
+impl Error {
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::from(match self {
+            Self::Connection { .. } => EXIT_CONNECTION,
+            Self::HandshakeRejected { .. } => EXIT_HANDSHAKE_REJECTED,
+            Self::Protocol => EXIT_PROTOCOL,
+            Self::Demux { .. } => EXIT_DEMUX,
+            Self::Mux { .. } => EXIT_MUX,
+            Self::Codec { .. } => EXIT_CODEC,
+            Self::Capture { .. } => EXIT_CAPTURE,
+        })
+    }
+}
+
+/// The exit code for `error`: that of the outermost [`Error`] in it, `1` otherwise.
+pub fn exit_code(error: &anyhow::Error) -> ExitCode {
+    error
+        .downcast_ref::<Error>()
+        .map_or(ExitCode::FAILURE, Error::exit_code)
+}
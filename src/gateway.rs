@@ -0,0 +1,119 @@
+//! Forwards mpegts between endpoints, e.g. raw TS over UDP/TCP into SRT and back.
+
+use std::{collections::VecDeque, io};
+
+use anyhow::anyhow;
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt, TryStreamExt, stream};
+use tokio::io::AsyncReadExt;
+use tracing::info;
+
+use crate::{
+    endpoint::{ByteStream, Endpoint, Scheme},
+    protocol::{self, Data},
+    shutdown, stdio,
+    ts::TsChunker,
+};
+
+const FILE_READ_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ForwardStats {
+    pub messages: u64,
+    pub bytes: u64,
+}
+
+/// Re-chunks raw TS reads into SRT-sized payloads aligned on 188-byte packets.
+///
+/// SRT inputs already deliver aligned messages; only the sequence header and end-of-stream
+/// message of this repo's senders are stripped, so the output is plain TS.
+pub fn align_ts(input: ByteStream, scheme: Scheme) -> ByteStream {
+    if scheme == Scheme::Srt {
+        return input
+            .try_filter_map(|bytes| async move {
+                match protocol::unwrap_data(bytes)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+                {
+                    Data::Payload { payload, .. } => Ok(Some(payload)),
+                    Data::EndOfStream { .. } => Ok(None),
+                }
+            })
+            .boxed();
+    }
+
+    let state = (Some(input), TsChunker::default(), VecDeque::new());
+    stream::try_unfold(state, |(mut input, mut chunker, mut ready)| async move {
+        loop {
+            if let Some(bytes) = ready.pop_front() {
+                return Ok(Some((bytes, (input, chunker, ready))));
+            }
+            let Some(source) = input.as_mut() else {
+                return Ok(None);
+            };
+            match source.try_next().await? {
+                Some(bytes) => ready.extend(chunker.push(&bytes)),
+                None => {
+                    // Input ended, hand out the partial payload still buffered
+                    input = None;
+                    ready.extend(chunker.flush());
+                }
+            }
+        }
+    })
+    .boxed()
+}
+
+/// Reads a TS file (or `-`, or a `testsrc`) as SRT-sized payloads, as fast as it can be read;
+/// pace it with a [`TsPacer`](crate::ts::TsPacer) to play it out in real time.
+pub async fn read_ts_file(path: &str) -> anyhow::Result<ByteStream> {
+    let reader = stdio::open_async_input(path).await?;
+    let reads = stream::try_unfold(
+        (reader, vec![0u8; FILE_READ_SIZE]),
+        |(mut reader, mut buf)| async move {
+            let n = reader.read(&mut buf).await?;
+            if n == 0 {
+                return Ok(None);
+            }
+            let bytes = Bytes::copy_from_slice(&buf[..n]);
+            Ok(Some((bytes, (reader, buf))))
+        },
+    )
+    .boxed();
+
+    // File reads are chunked like raw TS from UDP or TCP
+    Ok(align_ts(reads, Scheme::Tcp))
+}
+
+/// Copies `input` to `output` until the input ends, either side fails or shutdown is requested.
+/// Fails if shutdown is requested while a listener is still waiting for its peer.
+pub async fn forward(input: &Endpoint, output: &Endpoint) -> anyhow::Result<ForwardStats> {
+    info!("Opening output {output}");
+    let mut sink = shutdown::until(output.open_output())
+        .await
+        .ok_or_else(|| anyhow!("no connection on {output} yet"))??;
+    info!("Opening input {input}");
+    let source = shutdown::until(input.open_input())
+        .await
+        .ok_or_else(|| anyhow!("no connection on {input} yet"))??;
+    let mut source = align_ts(source, input.scheme);
+    info!("Forwarding {input} -> {output}");
+
+    let mut stats = ForwardStats::default();
+    while let Some(bytes) = shutdown::until(source.try_next())
+        .await
+        .transpose()?
+        .flatten()
+    {
+        stats.messages += 1;
+        stats.bytes += bytes.len() as u64;
+        sink.send(bytes).await?;
+    }
+    sink.close().await?;
+
+    info!(
+        messages = stats.messages,
+        bytes = stats.bytes,
+        "Input ended"
+    );
+    Ok(stats)
+}
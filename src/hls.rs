@@ -0,0 +1,299 @@
+//! Segments an incoming MPEG-TS stream into an HLS playlist on the local filesystem.
+
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{Read, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
+use ac_ffmpeg::{
+    codec::CodecParameters,
+    format::{
+        demuxer::DemuxerWithStreamInfo,
+        io::IO,
+        muxer::{Muxer, OutputFormat},
+    },
+    packet::Packet,
+};
+use anyhow::Context;
+use tracing::{info, warn};
+
+use crate::error::Error;
+
+/// Slack for timestamps that land a hair past a whole number of seconds.
+const CUT_TOLERANCE: f64 = 0.001;
+
+pub struct HlsConfig {
+    pub output_dir: PathBuf,
+    pub playlist_name: String,
+    pub segment_prefix: String,
+    /// Longest segment, announced as `#EXT-X-TARGETDURATION` (rounded up to whole seconds).
+    /// Segments are cut on video keyframes, at the last one before the next GOP would run over;
+    /// a GOP longer than this still makes a longer segment.
+    pub target_duration: Duration,
+    /// Number of segments kept in the rolling playlist. One that rolls off stays on disk until
+    /// as many more have, so players still holding the previous playlist can fetch it.
+    pub playlist_size: usize,
+}
+
+impl HlsConfig {
+    pub fn new(output_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            output_dir: output_dir.into(),
+            playlist_name: "index.m3u8".to_string(),
+            segment_prefix: "segment".to_string(),
+            target_duration: Duration::from_secs(4),
+            playlist_size: 6,
+        }
+    }
+}
+
+struct Segment {
+    file_name: String,
+    duration: f64,
+}
+
+struct OpenSegment {
+    muxer: Muxer<File>,
+    file_name: String,
+    start: Option<f64>,
+    end: f64,
+}
+
+impl OpenSegment {
+    fn push(&mut self, packet: Packet) -> anyhow::Result<()> {
+        self.muxer.push(packet).with_context(|| Error::Mux {
+            output: self.file_name.clone(),
+        })
+    }
+}
+
+pub struct HlsPackager {
+    config: HlsConfig,
+    streams: Vec<CodecParameters>,
+    video_stream: Option<usize>,
+    /// Timestamp of the last cut point, to measure GOPs by.
+    last_keyframe: Option<f64>,
+    segments: VecDeque<Segment>,
+    /// Segments off the playlist, oldest first, waiting to be deleted.
+    expired: VecDeque<String>,
+    media_sequence: u64,
+    next_index: u64,
+    current: Option<OpenSegment>,
+}
+
+impl HlsPackager {
+    pub fn new(config: HlsConfig, streams: Vec<CodecParameters>) -> anyhow::Result<Self> {
+        fs::create_dir_all(&config.output_dir)?;
+        let video_stream = streams.iter().position(|params| params.is_video_codec());
+
+        Ok(Self {
+            config,
+            streams,
+            video_stream,
+            last_keyframe: None,
+            segments: VecDeque::new(),
+            expired: VecDeque::new(),
+            media_sequence: 0,
+            next_index: 0,
+            current: None,
+        })
+    }
+
+    /// Demuxes `demuxer` until end of stream, writing segments and the playlist as it goes.
+    ///
+    /// Fails with [`Error::Demux`] if the input breaks off, [`Error::Mux`] if a segment can't be
+    /// written.
+    pub fn run<T: Read>(
+        config: HlsConfig,
+        mut demuxer: DemuxerWithStreamInfo<T>,
+    ) -> anyhow::Result<()> {
+        let streams = demuxer
+            .streams()
+            .iter()
+            .map(|stream| stream.codec_parameters())
+            .collect::<Vec<_>>();
+
+        let mut packager = Self::new(config, streams)?;
+        while let Some(packet) = demuxer.take().context(Error::Demux {
+            input: "the incoming stream".to_string(),
+        })? {
+            packager.push(packet)?;
+        }
+        packager.finish()
+    }
+
+    pub fn push(&mut self, packet: Packet) -> anyhow::Result<()> {
+        let Some(pts) = packet.pts().as_f64() else {
+            // Packets without a timestamp can't start or measure a segment, keep them in the current one.
+            if let Some(current) = self.current.as_mut() {
+                current.push(packet)?;
+            }
+            return Ok(());
+        };
+
+        let is_cut_point = match self.video_stream {
+            Some(index) => packet.stream_index() == index && packet.is_key(),
+            None => true,
+        };
+
+        if is_cut_point {
+            let elapsed = self
+                .current
+                .as_ref()
+                .and_then(|current| current.start)
+                .map(|start| pts - start);
+            // Assume the next GOP lasts as long as the one just ended
+            let gop = self.last_keyframe.map_or(0.0, |last| pts - last);
+            self.last_keyframe = Some(pts);
+
+            match elapsed {
+                Some(elapsed)
+                    if elapsed > 0.0
+                        && elapsed + gop
+                            > self.config.target_duration.as_secs_f64() + CUT_TOLERANCE =>
+                {
+                    self.close_segment(elapsed)?;
+                    self.open_segment()?;
+                }
+                None if self.current.is_none() => self.open_segment()?,
+                _ => {}
+            }
+        }
+
+        // Nothing is written until the first keyframe, a decoder couldn't use it anyway.
+        let Some(current) = self.current.as_mut() else {
+            return Ok(());
+        };
+        if current.start.is_none() {
+            current.start = Some(pts);
+        }
+        current.end = current.end.max(pts);
+        current.push(packet)?;
+        Ok(())
+    }
+
+    /// Closes the last segment and marks the playlist as complete.
+    pub fn finish(mut self) -> anyhow::Result<()> {
+        if let Some(current) = self.current.as_ref() {
+            let duration = current
+                .start
+                .map(|start| current.end - start)
+                .unwrap_or(0.0);
+            self.close_segment(duration)?;
+        }
+        self.write_playlist(true)
+    }
+
+    fn open_segment(&mut self) -> anyhow::Result<()> {
+        let file_name = format!("{}{}.ts", self.config.segment_prefix, self.next_index);
+        self.next_index += 1;
+
+        let file = File::create(self.config.output_dir.join(&file_name))?;
+        let io = IO::from_write_stream(file);
+
+        let mux_error = || Error::Mux {
+            output: file_name.clone(),
+        };
+        let mut muxer_builder = Muxer::builder();
+        for params in &self.streams {
+            muxer_builder.add_stream(params).with_context(mux_error)?;
+        }
+        let muxer = muxer_builder
+            .build(
+                io,
+                OutputFormat::find_by_name("mpegts").context("mpegts muxer not available")?,
+            )
+            .with_context(mux_error)?;
+
+        self.current = Some(OpenSegment {
+            muxer,
+            file_name,
+            start: None,
+            end: 0.0,
+        });
+        Ok(())
+    }
+
+    fn close_segment(&mut self, duration: f64) -> anyhow::Result<()> {
+        let Some(current) = self.current.take() else {
+            return Ok(());
+        };
+
+        if duration.round() as u64 > self.target_duration() {
+            warn!(
+                segment = %current.file_name,
+                duration,
+                "{} runs {duration:.3}s, over the {}s target duration",
+                current.file_name,
+                self.target_duration()
+            );
+        }
+        let mut muxer = current.muxer;
+        let mux_error = || Error::Mux {
+            output: current.file_name.clone(),
+        };
+        muxer.flush().with_context(mux_error)?;
+        muxer.close().with_context(mux_error)?;
+
+        info!(
+            segment = %current.file_name,
+            duration,
+            "Wrote {} ({duration:.3}s)",
+            current.file_name
+        );
+        self.segments.push_back(Segment {
+            file_name: current.file_name,
+            duration,
+        });
+
+        // RFC 8216 6.2.2: keep a removed segment around for about the playlist's duration
+        let playlist_size = self.config.playlist_size.max(1);
+        while self.segments.len() > playlist_size {
+            if let Some(old) = self.segments.pop_front() {
+                self.media_sequence += 1;
+                self.expired.push_back(old.file_name);
+            }
+        }
+        while self.expired.len() > playlist_size {
+            if let Some(old) = self.expired.pop_front() {
+                let _ = fs::remove_file(self.config.output_dir.join(old));
+            }
+        }
+
+        self.write_playlist(false)
+    }
+
+    /// `#EXT-X-TARGETDURATION`, which must not change over the life of the playlist.
+    fn target_duration(&self) -> u64 {
+        (self.config.target_duration.as_secs_f64().ceil() as u64).max(1)
+    }
+
+    fn write_playlist(&self, ended: bool) -> anyhow::Result<()> {
+        let target_duration = self.target_duration();
+
+        let mut playlist = String::new();
+        playlist.push_str("#EXTM3U\n");
+        playlist.push_str("#EXT-X-VERSION:3\n");
+        playlist.push_str(&format!("#EXT-X-TARGETDURATION:{target_duration}\n"));
+        playlist.push_str(&format!("#EXT-X-MEDIA-SEQUENCE:{}\n", self.media_sequence));
+        for segment in &self.segments {
+            playlist.push_str(&format!(
+                "#EXTINF:{:.3},\n{}\n",
+                segment.duration, segment.file_name
+            ));
+        }
+        if ended {
+            playlist.push_str("#EXT-X-ENDLIST\n");
+        }
+
+        // Write then rename so players never fetch a half-written playlist.
+        let path = self.config.output_dir.join(&self.config.playlist_name);
+        let tmp = path.with_extension("m3u8.tmp");
+        File::create(&tmp)?.write_all(playlist.as_bytes())?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+//! Minimal HTTP/1.1 server used for local endpoints (HLS output, metrics, control).
+//!
+//! One request per connection, `Connection: close`. Good enough for loopback tooling,
+//! not meant to face the internet.
+
+use std::{
+    future::Future,
+    io,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::Serialize;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+};
+use tracing::debug;
+
+const MAX_BODY: usize = 1024 * 1024;
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub body: Vec<u8>,
+}
+
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: u16, content_type: &'static str, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            content_type,
+            body: body.into(),
+        }
+    }
+
+    pub fn text(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Self::new(status, "text/plain; charset=utf-8", body)
+    }
+
+    pub fn json(status: u16, value: &impl Serialize) -> Self {
+        match serde_json::to_vec(value) {
+            Ok(body) => Self::new(status, "application/json", body),
+            Err(e) => Self::text(500, format!("{e}\n")),
+        }
+    }
+
+    pub fn not_found() -> Self {
+        Self::text(404, "not found\n")
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Binds `addr` and serves every connection with `handler` until the task is dropped.
+pub async fn serve<A, F, Fut>(addr: A, handler: F) -> io::Result<()>
+where
+    A: ToSocketAddrs,
+    F: Fn(Request) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Response> + Send,
+{
+    let listener = TcpListener::bind(addr).await?;
+    serve_listener(listener, handler).await
+}
+
+/// Same as [`serve`] on an already bound listener (handy for tests binding port 0).
+pub async fn serve_listener<F, Fut>(listener: TcpListener, handler: F) -> io::Result<()>
+where
+    F: Fn(Request) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Response> + Send,
+{
+    let handler = Arc::new(handler);
+    loop {
+        let (stream, _peer) = listener.accept().await?;
+        let handler = handler.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, handler.as_ref()).await {
+                debug!("HTTP connection error: {e}");
+            }
+        });
+    }
+}
+
+async fn handle_connection<F, Fut>(stream: TcpStream, handler: &F) -> io::Result<()>
+where
+    F: Fn(Request) -> Fut,
+    Fut: Future<Output = Response>,
+{
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return write_response(reader.get_mut(), Response::text(400, "bad request\n")).await;
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut content_length = 0usize;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.trim().eq_ignore_ascii_case("content-length")
+        {
+            content_length = value.trim().parse().unwrap_or(0);
+        }
+    }
+
+    if content_length > MAX_BODY {
+        return write_response(reader.get_mut(), Response::text(400, "body too large\n")).await;
+    }
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body).await?;
+
+    let response = handler(Request { method, path, body }).await;
+    write_response(reader.get_mut(), response).await
+}
+
+async fn write_response(stream: &mut TcpStream, response: Response) -> io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason(response.status),
+        response.content_type,
+        response.body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&response.body).await?;
+    stream.shutdown().await
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("m3u8") => "application/vnd.apple.mpegurl",
+        Some("ts") => "video/mp2t",
+        Some("json") => "application/json",
+        Some("html") => "text/html; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Serves the files below `root` read-only, e.g. an HLS output directory standing in for a CDN.
+pub async fn serve_dir<A: ToSocketAddrs>(addr: A, root: PathBuf) -> io::Result<()> {
+    let root = Arc::new(root);
+    serve(addr, move |request| {
+        let root = root.clone();
+        async move {
+            if request.method != "GET" {
+                return Response::text(405, "method not allowed\n");
+            }
+
+            let relative = Path::new(
+                request
+                    .path
+                    .split('?')
+                    .next()
+                    .unwrap_or("/")
+                    .trim_start_matches('/'),
+            );
+            if relative
+                .components()
+                .any(|c| !matches!(c, Component::Normal(_)))
+            {
+                return Response::not_found();
+            }
+
+            let path = root.join(relative);
+            match tokio::fs::read(&path).await {
+                Ok(body) => Response::new(200, content_type(&path), body),
+                Err(_) => Response::not_found(),
+            }
+        }
+    })
+    .await
+}
@@ -0,0 +1,343 @@
+//! UDP proxy that degrades a link on purpose: loss, delay, jitter, duplication and a rate cap.
+//!
+//! It sits between an SRT caller and a listener, e.g. caller -> `127.0.0.1:4000` (proxy) ->
+//! `127.0.0.1:2223` (listener). Randomness is seeded, so a run can be replayed exactly.
+//! Jitter above the packet spacing also reorders packets.
+
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    net::SocketAddr,
+    str::FromStr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, bail};
+use bytes::Bytes;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use tokio::{
+    net::UdpSocket,
+    sync::mpsc::{UnboundedSender, unbounded_channel},
+    task::JoinHandle,
+    time::{Instant, sleep_until},
+};
+
+const MAX_DATAGRAM: usize = 65536;
+
+/// Impairments applied to one direction of the link.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Impairment {
+    /// Probability of dropping a packet.
+    pub loss: f64,
+    pub delay: Duration,
+    /// Extra delay drawn uniformly from `-jitter..=jitter` (never below zero in total).
+    pub jitter: Duration,
+    /// Probability of sending a packet twice.
+    pub duplicate: f64,
+    /// Link rate in bits/s; packets queue behind each other and are dropped past `queue`.
+    pub rate: Option<u64>,
+    pub queue: Duration,
+}
+
+impl Default for Impairment {
+    fn default() -> Self {
+        Self {
+            loss: 0.0,
+            delay: Duration::ZERO,
+            jitter: Duration::ZERO,
+            duplicate: 0.0,
+            rate: None,
+            queue: Duration::from_millis(500),
+        }
+    }
+}
+
+impl Impairment {
+    /// Applies one `key=value` option: `loss`, `dup` (probabilities), `delay`, `jitter`,
+    /// `queue` (ms) or `rate` (bits/s).
+    pub fn set(&mut self, option: &str) -> anyhow::Result<()> {
+        let (key, value) = option
+            .split_once('=')
+            .with_context(|| format!("expected key=value, got {option:?}"))?;
+        let ms = || value.parse().map(Duration::from_millis);
+        match key {
+            "loss" => self.loss = probability(value)?,
+            "dup" => self.duplicate = probability(value)?,
+            "delay" => self.delay = ms()?,
+            "jitter" => self.jitter = ms()?,
+            "queue" => self.queue = ms()?,
+            "rate" => self.rate = Some(value.parse()?),
+            _ => bail!("unknown impairment {key:?}"),
+        }
+        Ok(())
+    }
+}
+
+fn probability(value: &str) -> anyhow::Result<f64> {
+    let p = f64::from_str(value)?;
+    if !(0.0..=1.0).contains(&p) {
+        bail!("probability {p} is outside 0..=1");
+    }
+    Ok(p)
+}
+
+#[derive(Clone, Debug)]
+pub struct ProxyConfig {
+    pub listen: SocketAddr,
+    pub upstream: SocketAddr,
+    /// Caller to listener.
+    pub forward: Impairment,
+    /// Listener back to caller.
+    pub backward: Impairment,
+    pub seed: u64,
+}
+
+impl ProxyConfig {
+    pub fn new(listen: SocketAddr, upstream: SocketAddr) -> Self {
+        Self {
+            listen,
+            upstream,
+            forward: Impairment::default(),
+            backward: Impairment::default(),
+            seed: 0,
+        }
+    }
+
+    /// Same impairment both ways.
+    pub fn both(mut self, impairment: Impairment) -> Self {
+        self.forward = impairment;
+        self.backward = impairment;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+/// Packet counts of one direction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LinkStats {
+    pub received: u64,
+    pub sent: u64,
+    pub lost: u64,
+    /// Dropped because the rate-limited queue was full.
+    pub overflowed: u64,
+    pub duplicated: u64,
+}
+
+#[derive(Default)]
+struct LinkCounters {
+    received: AtomicU64,
+    sent: AtomicU64,
+    lost: AtomicU64,
+    overflowed: AtomicU64,
+    duplicated: AtomicU64,
+}
+
+impl LinkCounters {
+    fn snapshot(&self) -> LinkStats {
+        LinkStats {
+            received: self.received.load(Ordering::Relaxed),
+            sent: self.sent.load(Ordering::Relaxed),
+            lost: self.lost.load(Ordering::Relaxed),
+            overflowed: self.overflowed.load(Ordering::Relaxed),
+            duplicated: self.duplicated.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Decides the fate of each packet of one direction and hands survivors to its delay line.
+struct Link {
+    impairment: Impairment,
+    rng: StdRng,
+    /// When the rate-limited link finishes transmitting the previous packet.
+    busy_until: Instant,
+    seq: u64,
+    delay_line: UnboundedSender<(Reverse<(Instant, u64)>, Bytes)>,
+    counters: Arc<LinkCounters>,
+}
+
+impl Link {
+    fn new(
+        impairment: Impairment,
+        seed: u64,
+        socket: Arc<UdpSocket>,
+        peer: Arc<Mutex<Option<SocketAddr>>>,
+        counters: Arc<LinkCounters>,
+    ) -> (Self, JoinHandle<()>) {
+        let (delay_line, mut queued) = unbounded_channel();
+        let sent = counters.clone();
+
+        // Releases packets in deadline order, ties in arrival order
+        let task = tokio::spawn(async move {
+            let mut heap: BinaryHeap<(Reverse<(Instant, u64)>, Bytes)> = BinaryHeap::new();
+            loop {
+                let next = heap.peek().map(|(Reverse((deadline, _)), _)| *deadline);
+                tokio::select! {
+                    packet = queued.recv() => match packet {
+                        Some(packet) => heap.push(packet),
+                        None => return,
+                    },
+                    _ = sleep_until(next.unwrap_or_else(Instant::now)), if next.is_some() => {
+                        let Some((_, bytes)) = heap.pop() else { continue };
+                        let Some(peer) = *peer.lock().unwrap() else { continue };
+                        if socket.send_to(&bytes, peer).await.is_ok() {
+                            sent.sent.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                }
+            }
+        });
+
+        let link = Self {
+            impairment,
+            rng: StdRng::seed_from_u64(seed),
+            busy_until: Instant::now(),
+            seq: 0,
+            delay_line,
+            counters,
+        };
+        (link, task)
+    }
+
+    fn push(&mut self, bytes: Bytes) {
+        self.counters.received.fetch_add(1, Ordering::Relaxed);
+        if self.rng.random_bool(self.impairment.loss) {
+            self.counters.lost.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        let copies = if self.rng.random_bool(self.impairment.duplicate) {
+            self.counters.duplicated.fetch_add(1, Ordering::Relaxed);
+            2
+        } else {
+            1
+        };
+        for _ in 0..copies {
+            self.schedule(bytes.clone());
+        }
+    }
+
+    fn schedule(&mut self, bytes: Bytes) {
+        let now = Instant::now();
+        let mut departure = now;
+        if let Some(rate) = self.impairment.rate {
+            departure = self.busy_until.max(now);
+            if departure - now > self.impairment.queue {
+                self.counters.overflowed.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            let airtime = Duration::from_secs_f64(bytes.len() as f64 * 8.0 / rate.max(1) as f64);
+            self.busy_until = departure + airtime;
+        }
+
+        let mut delay = self.impairment.delay;
+        let jitter = self.impairment.jitter.as_micros() as i64;
+        if jitter > 0 {
+            let offset = self.rng.random_range(-jitter..=jitter);
+            delay = Duration::from_micros((delay.as_micros() as i64 + offset).max(0) as u64);
+        }
+
+        self.seq += 1;
+        let _ = self
+            .delay_line
+            .send((Reverse((departure + delay, self.seq)), bytes));
+    }
+}
+
+/// A running proxy; dropping it stops forwarding.
+pub struct ImpairProxy {
+    local_addr: SocketAddr,
+    forward: Arc<LinkCounters>,
+    backward: Arc<LinkCounters>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl ImpairProxy {
+    /// Binds `config.listen` (port 0 picks a free one) and starts forwarding to `config.upstream`.
+    ///
+    /// Replies go to the caller address seen last, so one caller at a time is supported.
+    pub async fn start(config: ProxyConfig) -> anyhow::Result<Self> {
+        let downstream = Arc::new(UdpSocket::bind(config.listen).await?);
+        let upstream_bind: SocketAddr = if config.upstream.is_ipv4() {
+            "0.0.0.0:0".parse()?
+        } else {
+            "[::]:0".parse()?
+        };
+        let upstream = Arc::new(UdpSocket::bind(upstream_bind).await?);
+        let local_addr = downstream.local_addr()?;
+
+        let caller = Arc::new(Mutex::new(None));
+        let listener = Arc::new(Mutex::new(Some(config.upstream)));
+        let forward_counters = Arc::new(LinkCounters::default());
+        let backward_counters = Arc::new(LinkCounters::default());
+
+        let (mut forward, forward_task) = Link::new(
+            config.forward,
+            config.seed,
+            upstream.clone(),
+            listener,
+            forward_counters.clone(),
+        );
+        let (mut backward, backward_task) = Link::new(
+            config.backward,
+            config.seed.wrapping_add(1),
+            downstream.clone(),
+            caller.clone(),
+            backward_counters.clone(),
+        );
+
+        let from_caller = {
+            let downstream = downstream.clone();
+            tokio::spawn(async move {
+                let mut buf = vec![0; MAX_DATAGRAM];
+                while let Ok((n, from)) = downstream.recv_from(&mut buf).await {
+                    *caller.lock().unwrap() = Some(from);
+                    forward.push(Bytes::copy_from_slice(&buf[..n]));
+                }
+            })
+        };
+        let from_listener = tokio::spawn(async move {
+            let mut buf = vec![0; MAX_DATAGRAM];
+            while let Ok((n, from)) = upstream.recv_from(&mut buf).await {
+                if from == config.upstream {
+                    backward.push(Bytes::copy_from_slice(&buf[..n]));
+                }
+            }
+        });
+
+        Ok(Self {
+            local_addr,
+            forward: forward_counters,
+            backward: backward_counters,
+            tasks: vec![forward_task, backward_task, from_caller, from_listener],
+        })
+    }
+
+    /// Address callers should connect to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn forward_stats(&self) -> LinkStats {
+        self.forward.snapshot()
+    }
+
+    pub fn backward_stats(&self) -> LinkStats {
+        self.backward.snapshot()
+    }
+}
+
+impl Drop for ImpairProxy {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
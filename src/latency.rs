@@ -0,0 +1,193 @@
+//! Clock offset estimation and latency distribution for glass-to-glass measurements.
+
+use std::{
+    collections::VecDeque,
+    fmt, io,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use tokio::time::timeout;
+
+use crate::protocol::{Message, now_us};
+
+/// Estimates the remote clock offset from probe round trips (NTP-style).
+///
+/// SRT adds its configured latency in both directions, which is symmetric and cancels out;
+/// the sample with the shortest round trip is trusted most.
+#[derive(Clone, Debug, Default)]
+pub struct ClockSync {
+    best: Option<(i64, u64)>,
+    samples: usize,
+}
+
+impl ClockSync {
+    /// Records a probe sent at `sent_at_us`, stamped `remote_at_us` by the peer and answered at `received_at_us`.
+    pub fn add_sample(&mut self, sent_at_us: u64, remote_at_us: u64, received_at_us: u64) {
+        let round_trip = received_at_us.saturating_sub(sent_at_us);
+        let midpoint = (sent_at_us + received_at_us) / 2;
+        let offset = remote_at_us as i64 - midpoint as i64;
+
+        self.samples += 1;
+        if self.best.is_none_or(|(_, best_rtt)| round_trip < best_rtt) {
+            self.best = Some((offset, round_trip));
+        }
+    }
+
+    /// Remote clock minus local clock in microseconds, 0 until a sample was recorded.
+    pub fn offset_us(&self) -> i64 {
+        self.best.map(|(offset, _)| offset).unwrap_or(0)
+    }
+
+    pub fn round_trip(&self) -> Option<Duration> {
+        self.best.map(|(_, rtt)| Duration::from_micros(rtt))
+    }
+
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// Converts a remote timestamp into the local clock.
+    pub fn to_local_us(&self, remote_us: u64) -> u64 {
+        (remote_us as i64 - self.offset_us()).max(0) as u64
+    }
+}
+
+/// Latency samples over a sliding window, summarized as min/p50/p99/max.
+pub struct LatencyStats {
+    window: VecDeque<Duration>,
+    capacity: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LatencySummary {
+    pub count: usize,
+    pub min: Duration,
+    pub p50: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl LatencyStats {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            window: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn record(&mut self, latency: Duration) {
+        if self.window.len() == self.capacity {
+            self.window.pop_front();
+        }
+        self.window.push_back(latency);
+    }
+
+    pub fn summary(&self) -> Option<LatencySummary> {
+        if self.window.is_empty() {
+            return None;
+        }
+
+        let mut sorted = self.window.iter().copied().collect::<Vec<_>>();
+        sorted.sort_unstable();
+        let percentile = |p: f64| sorted[((sorted.len() - 1) as f64 * p).round() as usize];
+
+        Some(LatencySummary {
+            count: sorted.len(),
+            min: sorted[0],
+            p50: percentile(0.5),
+            p99: percentile(0.99),
+            max: sorted[sorted.len() - 1],
+        })
+    }
+}
+
+impl fmt::Display for LatencySummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        write!(
+            f,
+            "min {:.1} ms / p50 {:.1} ms / p99 {:.1} ms / max {:.1} ms ({} frames)",
+            ms(self.min),
+            ms(self.p50),
+            ms(self.p99),
+            ms(self.max),
+            self.count
+        )
+    }
+}
+
+/// Viewer side of the clock handshake: sends `probes` probes, one at a time, and collects the replies.
+///
+/// Gives up on a probe after `wait`; other messages arriving meanwhile are discarded.
+pub async fn probe_clock<S>(socket: &mut S, probes: usize, wait: Duration) -> io::Result<ClockSync>
+where
+    S: Stream<Item = io::Result<(Instant, Bytes)>>
+        + Sink<(Instant, Bytes), Error = io::Error>
+        + Unpin,
+{
+    let mut sync = ClockSync::default();
+    for _ in 0..probes {
+        let sent_at_us = now_us();
+        socket
+            .send((Instant::now(), Message::ClockProbe { sent_at_us }.encode()))
+            .await?;
+
+        let deadline = tokio::time::Instant::now() + wait;
+        loop {
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            let Ok(next) = timeout(remaining, socket.next()).await else {
+                break;
+            };
+            let Some((_instant, bytes)) = next.transpose()? else {
+                return Ok(sync);
+            };
+            if let Ok(Message::ClockReply {
+                probe_sent_at_us,
+                remote_at_us,
+            }) = Message::decode(bytes)
+                && probe_sent_at_us == sent_at_us
+            {
+                sync.add_sample(sent_at_us, remote_at_us, now_us());
+                break;
+            }
+        }
+    }
+    Ok(sync)
+}
+
+/// Sender side of the clock handshake: answers up to `probes` probes, waiting at most `wait` overall.
+///
+/// Returns the number of probes answered; 0 means the viewer doesn't probe and latency stays uncorrected.
+pub async fn answer_clock_probes<S>(
+    socket: &mut S,
+    probes: usize,
+    wait: Duration,
+) -> io::Result<usize>
+where
+    S: Stream<Item = io::Result<(Instant, Bytes)>>
+        + Sink<(Instant, Bytes), Error = io::Error>
+        + Unpin,
+{
+    let deadline = tokio::time::Instant::now() + wait;
+    let mut answered = 0;
+    while answered < probes {
+        let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+        let Ok(next) = timeout(remaining, socket.next()).await else {
+            break;
+        };
+        let Some((_instant, bytes)) = next.transpose()? else {
+            break;
+        };
+        if let Ok(Message::ClockProbe { sent_at_us }) = Message::decode(bytes) {
+            let reply = Message::ClockReply {
+                probe_sent_at_us: sent_at_us,
+                remote_at_us: now_us(),
+            };
+            socket.send((Instant::now(), reply.encode())).await?;
+            answered += 1;
+        }
+    }
+    Ok(answered)
+}
@@ -0,0 +1,25 @@
+pub mod bridge;
+pub mod control;
+pub mod daemon;
+pub mod endpoint;
+pub mod error;
+pub mod gateway;
+pub mod hls;
+pub mod http;
+pub mod impair;
+pub mod latency;
+pub mod logging;
+pub mod metrics;
+pub mod mosaic;
+pub mod motion;
+pub mod pipeline;
+pub mod playout;
+pub mod probe;
+pub mod protocol;
+pub mod sequence;
+pub mod shutdown;
+pub mod stats;
+pub mod stdio;
+pub mod testsrc;
+pub mod transfer;
+pub mod ts;
@@ -0,0 +1,66 @@
+//! Levelled, structured logging to stderr with `tracing`.
+//!
+//! `RUST_LOG` picks the levels per target, e.g. `RUST_LOG=rust_srt=debug,srt_protocol=info`.
+//! srt-tokio logs through the `log` crate; those records are forwarded, so they share the
+//! format, the filter and the span of the session they happened in.
+
+use std::{
+    fmt,
+    io::{self, IsTerminal},
+    str::FromStr,
+};
+
+use tracing_subscriber::{EnvFilter, fmt::format::FmtSpan};
+
+/// How log lines are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// One human-readable line per event, coloured on a terminal.
+    #[default]
+    Text,
+    /// One JSON object per event, with the fields of its spans, for log pipelines.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => anyhow::bail!("unknown log format {s:?}, expected text or json"),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Text => "text",
+            Self::Json => "json",
+        })
+    }
+}
+
+/// Installs the global subscriber. Without `RUST_LOG` this crate logs at `info` (`debug` when
+/// `verbose`, for every packet and frame) and everything else, srt-tokio included, at `warn`.
+pub fn init(format: LogFormat, verbose: bool) -> anyhow::Result<()> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+        EnvFilter::new(if verbose {
+            "warn,rust_srt=debug"
+        } else {
+            "warn,rust_srt=info"
+        })
+    });
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        // Sessions and routes log when they close, with how long they were open
+        .with_span_events(FmtSpan::CLOSE);
+    let result = match format {
+        LogFormat::Text => builder.with_ansi(io::stderr().is_terminal()).try_init(),
+        LogFormat::Json => builder.json().try_init(),
+    };
+    result.map_err(|e| anyhow::anyhow!("can't install the logger: {e}"))
+}
@@ -0,0 +1,111 @@
+mod cli;
+
+use std::process::ExitCode;
+
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use rust_srt::{
+    error,
+    logging::{self, LogFormat},
+    metrics, shutdown,
+};
+use tracing::{Instrument, error, field, info_span, warn};
+
+/// SRT streaming tools: files, mpegts, cameras and relays.
+#[derive(Parser)]
+#[command(name = "rust-srt", version)]
+struct Cli {
+    /// Log every packet and frame, not just progress and totals
+    #[arg(short, long, global = true)]
+    verbose: bool,
+    /// `text`, or `json` for one object per event; levels are set with `RUST_LOG`
+    #[arg(long, global = true, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Send a file as blocks, mpegts paced in real time, or a verified file transfer
+    Send(cli::send::SendArgs),
+    /// Receive what `send` or `play` sends, checking it arrived complete
+    Recv(cli::recv::RecvArgs),
+    /// Demux a media file and stream it as mpegts paced by its timestamps
+    Play(cli::play::PlayArgs),
+    /// Forward mpegts between srt://, udp:// and tcp:// endpoints
+    ///
+    /// e.g. `relay udp://0.0.0.0:12345 srt://127.0.0.1:1234`, `relay tcp://127.0.0.1:12345?listen
+    /// srt://:1234` or `relay srt://127.0.0.1:1234 udp://239.0.0.1:5000?ttl=4`
+    Relay(cli::relay::RelayArgs),
+    /// Segment an incoming mpegts stream into HLS
+    Hls(cli::hls::HlsArgs),
+    /// Stream a camera as JPEG frames
+    Camera(cli::camera::CameraArgs),
+    /// Show the frames `camera` streams, with glass-to-glass latency
+    View(cli::view::ViewArgs),
+    /// Print an ffprobe-style JSON report of a file or live input
+    Probe(cli::probe::ProbeArgs),
+    /// Relay UDP between an SRT caller and listener with loss, delay and jitter
+    ///
+    /// e.g. `impair 127.0.0.1:4000 127.0.0.1:2223 loss=0.05 delay=40 jitter=15 up.rate=8000000
+    /// seed=42`; the randomness is seeded, so a run can be replayed exactly
+    Impair(cli::impair::ImpairArgs),
+    /// Send text messages, or print the ones received
+    Text(cli::text::TextArgs),
+    /// Run the routes listed in a config file, restarting failed ones and reloading on SIGHUP,
+    /// optionally controlled over a local HTTP API
+    Daemon(cli::daemon::DaemonArgs),
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if let Err(e) = logging::init(cli.log_format, cli.verbose) {
+        eprintln!("{e:#}");
+    }
+    if let Err(e) = shutdown::install() {
+        warn!("Can't catch signals, they will kill the process: {e}");
+    }
+    metrics::serve_from_env();
+
+    // Everything the command logs carries its name, and the SRT peer once `connect` made one
+    let span = info_span!(
+        "session",
+        command = matches.subcommand_name(),
+        peer = field::Empty,
+        stream_id = field::Empty,
+    );
+    let result = async {
+        match cli.command {
+            Command::Send(args) => cli::send::run(args).await,
+            Command::Recv(args) => cli::recv::run(args).await,
+            Command::Play(args) => cli::play::run(args).await,
+            Command::Relay(args) => cli::relay::run(args).await,
+            Command::Hls(args) => cli::hls::run(args).await,
+            Command::Camera(args) => cli::camera::run(args).await,
+            Command::View(args) => cli::view::run(args).await,
+            Command::Probe(args) => cli::probe::run(args).await,
+            Command::Impair(args) => cli::impair::run(args).await,
+            Command::Text(args) => cli::text::run(args).await,
+            Command::Daemon(args) => cli::daemon::run(args).await,
+        }
+    }
+    .instrument(span.clone())
+    .await;
+
+    let _session = span.enter();
+    match result {
+        Ok(code) => code,
+        // Whatever was cut short by the shutdown, e.g. waiting for a peer
+        Err(e) if shutdown::requested() => {
+            warn!("Interrupted: {e:#}");
+            ExitCode::from(shutdown::EXIT_INTERRUPTED)
+        }
+        Err(e) => {
+            error!("{e:#}");
+            error::exit_code(&e)
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! Process-wide counters and gauges exposed in the Prometheus text format.
+//!
+//! Set `SRT_METRICS_ADDR=127.0.0.1:9100` to serve them on `/metrics`.
+
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt::Write,
+    io,
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use tokio::net::{TcpListener, ToSocketAddrs};
+use tracing::{error, info};
+
+use crate::http::{self, Response};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Counter,
+    Gauge,
+}
+
+struct Family {
+    help: &'static str,
+    kind: Kind,
+    /// Rendered label set (`{socket="sender"}` or empty) to value; gauges store `f64` bits.
+    series: BTreeMap<String, Arc<AtomicU64>>,
+}
+
+fn registry() -> &'static Mutex<BTreeMap<&'static str, Family>> {
+    static REGISTRY: OnceLock<Mutex<BTreeMap<&'static str, Family>>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+fn register(
+    name: &'static str,
+    help: &'static str,
+    kind: Kind,
+    labels: &[(&str, &str)],
+) -> Arc<AtomicU64> {
+    let labels = if labels.is_empty() {
+        String::new()
+    } else {
+        let pairs = labels
+            .iter()
+            .map(|(key, value)| format!("{key}=\"{}\"", escape(value)))
+            .collect::<Vec<_>>();
+        format!("{{{}}}", pairs.join(","))
+    };
+
+    let mut registry = registry().lock().unwrap();
+    let family = registry.entry(name).or_insert_with(|| Family {
+        help,
+        kind,
+        series: BTreeMap::new(),
+    });
+    debug_assert!(
+        family.kind == kind,
+        "metric {name} registered twice with different kinds"
+    );
+    family.series.entry(labels).or_default().clone()
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[derive(Clone)]
+pub struct Counter(Arc<AtomicU64>);
+
+impl Counter {
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, value: u64) {
+        self.0.fetch_add(value, Ordering::Relaxed);
+    }
+
+    /// Mirrors a cumulative total kept elsewhere (e.g. SRT socket statistics).
+    pub fn set_total(&self, total: u64) {
+        self.0.fetch_max(total, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Clone)]
+pub struct Gauge(Arc<AtomicU64>);
+
+impl Gauge {
+    pub fn set(&self, value: f64) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+/// Returns the counter `name`, registering it on first use. Handles to the same name share a value.
+pub fn counter(name: &'static str, help: &'static str) -> Counter {
+    counter_with(name, help, &[])
+}
+
+pub fn counter_with(name: &'static str, help: &'static str, labels: &[(&str, &str)]) -> Counter {
+    Counter(register(name, help, Kind::Counter, labels))
+}
+
+pub fn gauge_with(name: &'static str, help: &'static str, labels: &[(&str, &str)]) -> Gauge {
+    Gauge(register(name, help, Kind::Gauge, labels))
+}
+
+/// Renders every registered metric in the Prometheus text exposition format.
+pub fn render() -> String {
+    let registry = registry().lock().unwrap();
+    let mut out = String::new();
+    for (name, family) in registry.iter() {
+        let kind = match family.kind {
+            Kind::Counter => "counter",
+            Kind::Gauge => "gauge",
+        };
+        let _ = writeln!(out, "# HELP {name} {}", family.help);
+        let _ = writeln!(out, "# TYPE {name} {kind}");
+        for (labels, value) in &family.series {
+            let value = value.load(Ordering::Relaxed);
+            match family.kind {
+                Kind::Counter => {
+                    let _ = writeln!(out, "{name}{labels} {value}");
+                }
+                Kind::Gauge => {
+                    let _ = writeln!(out, "{name}{labels} {}", f64::from_bits(value));
+                }
+            }
+        }
+    }
+    out
+}
+
+async fn handle(request: http::Request) -> Response {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/metrics") => {
+            Response::new(200, "text/plain; version=0.0.4; charset=utf-8", render())
+        }
+        _ => Response::not_found(),
+    }
+}
+
+/// Serves `/metrics` on `addr` until the task is dropped.
+pub async fn serve<A: ToSocketAddrs>(addr: A) -> io::Result<()> {
+    http::serve(addr, handle).await
+}
+
+pub async fn serve_listener(listener: TcpListener) -> io::Result<()> {
+    http::serve_listener(listener, handle).await
+}
+
+/// Starts the `/metrics` endpoint in the background when `SRT_METRICS_ADDR` is set.
+pub fn serve_from_env() {
+    let Some(addr) = env::var("SRT_METRICS_ADDR")
+        .ok()
+        .filter(|addr| !addr.is_empty())
+    else {
+        return;
+    };
+
+    info!("Serving metrics on http://{addr}/metrics");
+    tokio::spawn(async move {
+        if let Err(e) = serve(addr.as_str()).await {
+            error!("Metrics endpoint failed: {e}");
+        }
+    });
+}
+
+/// Whether SRT statistics should be sampled for metrics even without `SRT_STATS`.
+pub fn enabled() -> bool {
+    env::var("SRT_METRICS_ADDR").is_ok_and(|addr| !addr.is_empty())
+}
@@ -0,0 +1,276 @@
+//! Composes the frames of many cameras into one grid image, one labelled tile per stream ID.
+
+use std::{
+    collections::{BTreeMap, HashSet, VecDeque},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use opencv::{
+    core::{CV_8UC3, Mat, Point, Rect, Scalar, Size, Vector},
+    imgcodecs, imgproc,
+    prelude::*,
+};
+
+/// Size every frame is scaled to in the grid.
+pub const TILE_SIZE: (i32, i32) = (480, 270);
+/// A camera without a new frame for this long is marked stale.
+pub const STALE_AFTER: Duration = Duration::from_secs(2);
+
+const FPS_WINDOW: Duration = Duration::from_secs(1);
+
+struct Tile {
+    frame: Mat,
+    last_frame: Instant,
+    /// When the frames of the last [`FPS_WINDOW`] arrived.
+    arrivals: VecDeque<Instant>,
+    connected: bool,
+}
+
+/// How a tile's camera is doing, shown as a coloured dot in its corner.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TileState {
+    Live,
+    /// Connected, but no frame for [`STALE_AFTER`].
+    Stale,
+    Disconnected,
+}
+
+pub struct Mosaic {
+    tiles: BTreeMap<String, Tile>,
+    tile_size: Size,
+    stale_after: Duration,
+}
+
+impl Default for Mosaic {
+    fn default() -> Self {
+        Self::new(Size::new(TILE_SIZE.0, TILE_SIZE.1), STALE_AFTER)
+    }
+}
+
+impl Mosaic {
+    pub fn new(tile_size: Size, stale_after: Duration) -> Self {
+        Self {
+            tiles: BTreeMap::new(),
+            tile_size,
+            stale_after,
+        }
+    }
+
+    /// Shows `frame` on the tile of `id`, adding the tile for a new camera. `frame` must be BGR.
+    pub fn update(&mut self, id: &str, frame: &Mat, now: Instant) -> opencv::Result<()> {
+        let mut scaled = Mat::default();
+        imgproc::resize(
+            frame,
+            &mut scaled,
+            self.tile_size,
+            0.0,
+            0.0,
+            imgproc::INTER_LINEAR,
+        )?;
+
+        let tile = self.tiles.entry(id.to_string()).or_insert_with(|| Tile {
+            frame: Mat::default(),
+            last_frame: now,
+            arrivals: VecDeque::new(),
+            connected: true,
+        });
+        tile.frame = scaled;
+        tile.last_frame = now;
+        tile.connected = true;
+        tile.arrivals.push_back(now);
+        while tile
+            .arrivals
+            .front()
+            .is_some_and(|&arrival| now.duration_since(arrival) > FPS_WINDOW)
+        {
+            tile.arrivals.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Keeps the last frame of `id` up, marked as disconnected until the camera is back.
+    pub fn disconnected(&mut self, id: &str) {
+        if let Some(tile) = self.tiles.get_mut(id) {
+            tile.connected = false;
+        }
+    }
+
+    /// Stream IDs of the tiles, in grid order.
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.tiles.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    /// Frames per second `id` showed over the last second.
+    pub fn fps(&self, id: &str, now: Instant) -> Option<f64> {
+        let tile = self.tiles.get(id)?;
+        let recent = tile
+            .arrivals
+            .iter()
+            .filter(|&&arrival| now.duration_since(arrival) <= FPS_WINDOW)
+            .count();
+        Some(recent as f64 / FPS_WINDOW.as_secs_f64())
+    }
+
+    pub fn state(&self, id: &str, now: Instant) -> Option<TileState> {
+        let tile = self.tiles.get(id)?;
+        Some(if !tile.connected {
+            TileState::Disconnected
+        } else if now.duration_since(tile.last_frame) > self.stale_after {
+            TileState::Stale
+        } else {
+            TileState::Live
+        })
+    }
+
+    /// The tile of `id` with its overlays: stream ID, FPS and state.
+    pub fn render_tile(&self, id: &str, now: Instant) -> opencv::Result<Option<Mat>> {
+        let (Some(tile), Some(state), Some(fps)) =
+            (self.tiles.get(id), self.state(id, now), self.fps(id, now))
+        else {
+            return Ok(None);
+        };
+        let mut mat = tile.frame.try_clone()?;
+        let white = Scalar::new(255.0, 255.0, 255.0, 0.0);
+
+        label(
+            &mut mat,
+            id,
+            Point::new(10, self.tile_size.height - 12),
+            white,
+        )?;
+        label(
+            &mut mat,
+            &format!("{fps:.0} fps"),
+            Point::new(10, 24),
+            white,
+        )?;
+
+        let (colour, text) = match state {
+            TileState::Live => (Scalar::new(0.0, 200.0, 0.0, 0.0), "LIVE"),
+            TileState::Stale => (Scalar::new(0.0, 200.0, 255.0, 0.0), "STALE"),
+            TileState::Disconnected => (Scalar::new(0.0, 0.0, 255.0, 0.0), "OFFLINE"),
+        };
+        let right = self.tile_size.width;
+        imgproc::circle(
+            &mut mat,
+            Point::new(right - 16, 18),
+            7,
+            colour,
+            imgproc::FILLED,
+            imgproc::LINE_AA,
+            0,
+        )?;
+        label(&mut mat, text, Point::new(right - 110, 24), colour)?;
+        Ok(Some(mat))
+    }
+
+    /// All tiles in stream ID order, in a grid as square as it gets. Cells past the last tile
+    /// stay black; without any tile it's a single black cell saying so.
+    pub fn compose(&self, now: Instant) -> opencv::Result<Mat> {
+        let Size { width, height } = self.tile_size;
+        let count = self.tiles.len().max(1) as i32;
+        let columns = (count as f64).sqrt().ceil() as i32;
+        let rows = (count + columns - 1) / columns;
+
+        let mut mosaic = Mat::new_rows_cols_with_default(
+            rows * height,
+            columns * width,
+            CV_8UC3,
+            Scalar::all(0.0),
+        )?;
+        if self.tiles.is_empty() {
+            let grey = Scalar::all(160.0);
+            label(&mut mosaic, "Waiting for cameras", Point::new(10, 24), grey)?;
+        }
+        for (index, id) in self.ids().enumerate() {
+            let Some(tile) = self.render_tile(id, now)? else {
+                continue;
+            };
+            let index = index as i32;
+            let cell = Rect::new(
+                (index % columns) * width,
+                (index / columns) * height,
+                width,
+                height,
+            );
+            tile.copy_to(&mut mosaic.roi_mut(cell)?)?;
+        }
+        Ok(mosaic)
+    }
+
+    /// Writes the mosaic to `dir` as `mosaic.png`, and every tile as `tiles/<file name>.png`
+    /// with the names of [`Mosaic::tile_file_names`].
+    pub fn save(&self, dir: &Path, now: Instant) -> anyhow::Result<()> {
+        let tiles = dir.join("tiles");
+        std::fs::create_dir_all(&tiles)?;
+        write_png(&dir.join("mosaic.png"), &self.compose(now)?)?;
+        for (id, name) in self.tile_file_names() {
+            if let Some(tile) = self.render_tile(id, now)? {
+                write_png(&tiles.join(format!("{name}.png")), &tile)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Every stream ID with a distinct [`file_name`], in grid order. IDs that only differ in
+    /// replaced characters (`cam/1` and `cam_1`) get `-2`, `-3` … appended after the first.
+    pub fn tile_file_names(&self) -> Vec<(&str, String)> {
+        let mut taken = HashSet::new();
+        self.ids()
+            .map(|id| {
+                let base = file_name(id);
+                let mut name = base.clone();
+                let mut n = 1;
+                while !taken.insert(name.clone()) {
+                    n += 1;
+                    name = format!("{base}-{n}");
+                }
+                (id, name)
+            })
+            .collect()
+    }
+}
+
+fn label(mat: &mut Mat, text: &str, origin: Point, colour: Scalar) -> opencv::Result<()> {
+    imgproc::put_text(
+        mat,
+        text,
+        origin,
+        imgproc::FONT_HERSHEY_SIMPLEX,
+        0.6,
+        colour,
+        2,
+        imgproc::LINE_AA,
+        false,
+    )
+}
+
+fn write_png(path: &Path, mat: &Mat) -> anyhow::Result<()> {
+    let written = imgcodecs::imwrite(&path.to_string_lossy(), mat, &Vector::new())
+        .with_context(|| format!("can't write {}", path.display()))?;
+    anyhow::ensure!(written, "can't write {}", path.display());
+    Ok(())
+}
+
+/// `id` with anything but letters, digits, `-` and `_` replaced, safe as a file name.
+pub fn file_name(id: &str) -> String {
+    id.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,379 @@
+//! Motion detection by background subtraction, and event clips that start a few seconds before
+//! the motion did.
+
+use std::{
+    collections::VecDeque, fmt, fs::OpenOptions, io::Write, path::PathBuf, str::FromStr,
+    time::Duration,
+};
+
+use anyhow::{Context, anyhow};
+use opencv::{
+    core::{self, CV_8UC1, Mat, Ptr, Rect, Scalar, Size, Vector},
+    imgcodecs, imgproc,
+    prelude::*,
+    video::{self, BackgroundSubtractorMOG2},
+    videoio::VideoWriter,
+};
+use serde::Serialize;
+use tracing::info;
+
+use crate::{
+    metrics::{self, Counter},
+    mosaic,
+};
+
+/// Video recorded before the motion started.
+pub const PRE_EVENT: Duration = Duration::from_secs(5);
+/// Video recorded after the motion stopped; motion within it extends the event.
+pub const POST_EVENT: Duration = Duration::from_secs(5);
+/// Appended with one JSON line per event, next to the clips.
+pub const EVENT_LOG: &str = "events.jsonl";
+
+/// Frames the background model is learned over.
+const HISTORY: i32 = 500;
+/// Squared distance from the background model a pixel must be at to count as foreground.
+const VAR_THRESHOLD: f64 = 16.0;
+/// Foreground mask level separating foreground (255) from shadows (127).
+const SHADOW_LEVEL: f64 = 200.0;
+/// Frame rate of a clip when the pre-event buffer holds too few frames to tell.
+const DEFAULT_CLIP_FPS: f64 = 25.0;
+
+/// A rectangle of the frame given as `WxH+X+Y`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub width: i32,
+    pub height: i32,
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Region {
+    pub fn rect(&self) -> Rect {
+        Rect::new(self.x, self.y, self.width, self.height)
+    }
+}
+
+impl FromStr for Region {
+    type Err = anyhow::Error;
+
+    fn from_str(region: &str) -> anyhow::Result<Self> {
+        let invalid = || anyhow!("invalid region {region:?}, expected WxH+X+Y");
+        let (size, offset) = region.split_once('+').ok_or_else(invalid)?;
+        let (width, height) = size
+            .split_once('x')
+            .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+            .filter(|&(width, height): &(i32, i32)| width > 0 && height > 0)
+            .ok_or_else(invalid)?;
+        let (x, y) = offset
+            .split_once('+')
+            .and_then(|(x, y)| Some((x.parse().ok()?, y.parse().ok()?)))
+            .ok_or_else(invalid)?;
+        Ok(Self {
+            width,
+            height,
+            x,
+            y,
+        })
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}+{}+{}", self.width, self.height, self.x, self.y)
+    }
+}
+
+/// Tells how much of the watched area is moving, with a MOG2 background model that adapts to
+/// slow changes such as daylight. Shadows are not counted.
+pub struct MotionDetector {
+    subtractor: Ptr<BackgroundSubtractorMOG2>,
+    /// Watched rectangles, the whole frame if there are none.
+    regions: Vec<Region>,
+    /// White over `regions`, for the frame size it was drawn for.
+    mask: Option<(Size, Mat)>,
+    learning: bool,
+}
+
+impl MotionDetector {
+    pub fn new(regions: Vec<Region>) -> opencv::Result<Self> {
+        Ok(Self {
+            subtractor: video::create_background_subtractor_mog2(HISTORY, VAR_THRESHOLD, true)?,
+            regions,
+            mask: None,
+            learning: true,
+        })
+    }
+
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    /// Share of the watched area in the foreground of `frame`, from 0 to 1. The first frame only
+    /// starts the background model and always gives 0.
+    pub fn changed(&mut self, frame: &Mat) -> opencv::Result<f64> {
+        let mut blurred = Mat::default();
+        imgproc::gaussian_blur_def(frame, &mut blurred, Size::new(21, 21), 0.0)?;
+        let mut foreground = Mat::default();
+        self.subtractor.apply(&blurred, &mut foreground, -1.0)?;
+        if std::mem::replace(&mut self.learning, false) {
+            return Ok(0.0);
+        }
+
+        let mut moving = Mat::default();
+        imgproc::threshold(
+            &foreground,
+            &mut moving,
+            SHADOW_LEVEL,
+            255.0,
+            imgproc::THRESH_BINARY,
+        )?;
+        let (changed, area) = match self.mask(frame.size()?)? {
+            Some(mask) => {
+                let mut watched = Mat::default();
+                core::bitwise_and_def(&moving, mask, &mut watched)?;
+                (core::count_non_zero(&watched)?, core::count_non_zero(mask)?)
+            }
+            None => (core::count_non_zero(&moving)?, frame.rows() * frame.cols()),
+        };
+        Ok(f64::from(changed) / f64::from(area.max(1)))
+    }
+
+    fn mask(&mut self, size: Size) -> opencv::Result<Option<&Mat>> {
+        if self.regions.is_empty() {
+            return Ok(None);
+        }
+        if self
+            .mask
+            .as_ref()
+            .is_none_or(|(drawn_for, _)| *drawn_for != size)
+        {
+            let mut mask = Mat::new_size_with_default(size, CV_8UC1, Scalar::all(0.0))?;
+            for region in &self.regions {
+                imgproc::rectangle(
+                    &mut mask,
+                    region.rect(),
+                    Scalar::all(255.0),
+                    imgproc::FILLED,
+                    imgproc::LINE_8,
+                    0,
+                )?;
+            }
+            self.mask = Some((size, mask));
+        }
+        Ok(self.mask.as_ref().map(|(_, mask)| mask))
+    }
+}
+
+/// An entry of the event log.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MotionEvent {
+    pub stream: String,
+    /// Capture time of the first frame with motion, in microseconds since the Unix epoch.
+    pub started_at_us: u64,
+    /// Capture time of the last frame with motion.
+    pub ended_at_us: u64,
+    /// Largest share of the watched area that moved.
+    pub peak: f64,
+    /// File name of the clip, in the directory of the event log.
+    pub clip: String,
+    /// Frames in the clip, pre-event ones included.
+    pub frames: u64,
+    /// Frames from before the motion started.
+    pub pre_event_frames: u64,
+}
+
+struct Recording {
+    writer: VideoWriter,
+    size: Size,
+    event: MotionEvent,
+}
+
+impl Recording {
+    fn write(&mut self, frame: &Mat) -> opencv::Result<()> {
+        // The writer silently skips frames of another size
+        if frame.size()? == self.size {
+            self.writer.write(frame)?;
+        } else {
+            let mut resized = Mat::default();
+            imgproc::resize(
+                frame,
+                &mut resized,
+                self.size,
+                0.0,
+                0.0,
+                imgproc::INTER_LINEAR,
+            )?;
+            self.writer.write(&resized)?;
+        }
+        self.event.frames += 1;
+        Ok(())
+    }
+}
+
+/// Records a camera's motion events into `dir`: every event as an MJPEG clip
+/// `<stream ID>-<start in ms>.avi` and a line in [`EVENT_LOG`].
+///
+/// Frames are kept JPEG-compressed for the length of the pre-event buffer, so a clip starts that
+/// long before the motion. It ends once no motion was seen for the post-event time. Times are the
+/// capture times of the frames. Events are counted in `motion_events_total`, labelled by `stream`.
+pub struct EventRecorder {
+    dir: PathBuf,
+    stream: String,
+    pre_event: Duration,
+    post_event: Duration,
+    /// Capture times and JPEGs of the frames of the last `pre_event`.
+    buffer: VecDeque<(u64, Vector<u8>)>,
+    recording: Option<Recording>,
+    events_metric: Counter,
+}
+
+impl EventRecorder {
+    pub fn new(dir: PathBuf, stream: &str, pre_event: Duration, post_event: Duration) -> Self {
+        Self {
+            dir,
+            stream: stream.to_string(),
+            pre_event,
+            post_event,
+            buffer: VecDeque::new(),
+            recording: None,
+            events_metric: metrics::counter_with(
+                "motion_events_total",
+                "Motion events recorded",
+                &[("stream", stream)],
+            ),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Feeds the frame captured at `captured_at_us`, with the share of the watched area that
+    /// moved if it showed motion. Returns the event this frame ended.
+    pub fn push(
+        &mut self,
+        frame: &Mat,
+        captured_at_us: u64,
+        motion: Option<f64>,
+    ) -> anyhow::Result<Option<MotionEvent>> {
+        if let Some(recording) = &mut self.recording {
+            recording.write(frame)?;
+            let event = &mut recording.event;
+            match motion {
+                Some(changed) => {
+                    event.ended_at_us = event.ended_at_us.max(captured_at_us);
+                    event.peak = event.peak.max(changed);
+                }
+                None => {
+                    let quiet = captured_at_us.saturating_sub(event.ended_at_us);
+                    if quiet >= self.post_event.as_micros() as u64 {
+                        return self.close();
+                    }
+                }
+            }
+            return Ok(None);
+        }
+
+        match motion {
+            Some(changed) => self.start(frame, captured_at_us, changed)?,
+            None => {
+                let mut jpeg = Vector::new();
+                imgcodecs::imencode(".jpg", frame, &mut jpeg, &Vector::new())?;
+                self.buffer.push_back((captured_at_us, jpeg));
+                let oldest = captured_at_us.saturating_sub(self.pre_event.as_micros() as u64);
+                while self.buffer.front().is_some_and(|(at, _)| *at < oldest) {
+                    self.buffer.pop_front();
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Ends the event being recorded, e.g. when the camera leaves.
+    pub fn finish(&mut self) -> anyhow::Result<Option<MotionEvent>> {
+        self.buffer.clear();
+        self.close()
+    }
+
+    fn start(&mut self, frame: &Mat, captured_at_us: u64, changed: f64) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("can't create {}", self.dir.display()))?;
+        let clip = format!(
+            "{}-{}.avi",
+            mosaic::file_name(&self.stream),
+            captured_at_us / 1000
+        );
+        let path = self.dir.join(&clip);
+        let size = frame.size()?;
+        let writer = VideoWriter::new(
+            &path.to_string_lossy(),
+            VideoWriter::fourcc('M', 'J', 'P', 'G')?,
+            self.clip_fps(captured_at_us),
+            size,
+            true,
+        )?;
+        anyhow::ensure!(writer.is_opened()?, "can't write {}", path.display());
+
+        let pre_event_frames = self.buffer.len() as u64;
+        let mut recording = Recording {
+            writer,
+            size,
+            event: MotionEvent {
+                stream: self.stream.clone(),
+                started_at_us: captured_at_us,
+                ended_at_us: captured_at_us,
+                peak: changed,
+                clip,
+                frames: 0,
+                pre_event_frames,
+            },
+        };
+        for (_, jpeg) in self.buffer.drain(..) {
+            recording.write(&imgcodecs::imdecode(&jpeg, imgcodecs::IMREAD_COLOR)?)?;
+        }
+        recording.write(frame)?;
+
+        info!(clip = %recording.event.clip, pre_event_frames, "Motion event started");
+        self.events_metric.inc();
+        self.recording = Some(recording);
+        Ok(())
+    }
+
+    fn close(&mut self) -> anyhow::Result<Option<MotionEvent>> {
+        let Some(mut recording) = self.recording.take() else {
+            return Ok(None);
+        };
+        recording.writer.release()?;
+        let event = recording.event;
+
+        let path = self.dir.join(EVENT_LOG);
+        let mut line = serde_json::to_vec(&event)?;
+        line.push(b'\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut log| log.write_all(&line))
+            .with_context(|| format!("can't append to {}", path.display()))?;
+
+        info!(
+            clip = %event.clip,
+            frames = event.frames,
+            "Motion event ended after {:.1} s, peak {:.0}%",
+            event.ended_at_us.saturating_sub(event.started_at_us) as f64 / 1e6,
+            event.peak * 100.0
+        );
+        Ok(Some(event))
+    }
+
+    /// The camera's frame rate over the pre-event buffer and the frame at `now_us`.
+    fn clip_fps(&self, now_us: u64) -> f64 {
+        match self.buffer.front() {
+            Some(&(first_us, _)) if now_us > first_us => {
+                let span = (now_us - first_us) as f64 / 1e6;
+                (self.buffer.len() as f64 / span).clamp(1.0, 60.0)
+            }
+            _ => DEFAULT_CLIP_FPS,
+        }
+    }
+}
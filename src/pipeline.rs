@@ -0,0 +1,520 @@
+//! Frame processing between decode and display: a chain of [`FrameProcessor`]s, built per camera
+//! from `--process` specs such as `resize=640x360` or `rotate=90`.
+
+use std::{
+    fmt,
+    path::PathBuf,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, anyhow, bail};
+use opencv::{
+    core::{self, Mat, Point, Rect, Scalar, Size, Vector},
+    imgcodecs, imgproc,
+    prelude::*,
+};
+use tracing::info;
+
+use crate::{
+    mosaic,
+    motion::{self, EventRecorder, MotionDetector, Region},
+};
+
+/// What is known about the frame being processed.
+#[derive(Clone, Debug)]
+pub struct FrameContext {
+    /// Stream ID of the camera.
+    pub stream: String,
+    pub seq: u64,
+    /// Capture time on the local clock, in microseconds since the Unix epoch.
+    pub captured_at_us: u64,
+    /// Set by a motion detector earlier in the chain.
+    pub motion: bool,
+    /// Share of the watched area the motion detector found moving, from 0 to 1.
+    pub changed: f64,
+}
+
+/// One step of a [`Pipeline`]. Implementations keep whatever state they need across frames of
+/// their camera, each camera has its own instance.
+pub trait FrameProcessor: Send {
+    fn name(&self) -> &'static str;
+
+    /// Processes `frame` in place, or replaces it, e.g. with a smaller copy.
+    fn process(&mut self, frame: &mut Mat, context: &mut FrameContext) -> anyhow::Result<()>;
+
+    /// Called once after the last frame, e.g. to close files.
+    fn finish(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Processors run in order on every frame.
+#[derive(Default)]
+pub struct Pipeline {
+    processors: Vec<Box<dyn FrameProcessor>>,
+}
+
+impl Pipeline {
+    /// Builds a fresh processor of every spec, in order.
+    pub fn from_specs(specs: &[ProcessorSpec]) -> Self {
+        Self {
+            processors: specs.iter().map(ProcessorSpec::build).collect(),
+        }
+    }
+
+    pub fn push(&mut self, processor: Box<dyn FrameProcessor>) {
+        self.processors.push(processor);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.processors.is_empty()
+    }
+
+    pub fn process(&mut self, frame: &mut Mat, context: &mut FrameContext) -> anyhow::Result<()> {
+        for processor in &mut self.processors {
+            processor
+                .process(frame, context)
+                .with_context(|| format!("{} failed on frame {}", processor.name(), context.seq))?;
+        }
+        Ok(())
+    }
+
+    /// Finishes every processor, even if one fails, and returns the first error.
+    pub fn finish(&mut self) -> anyhow::Result<()> {
+        let mut result = Ok(());
+        for processor in &mut self.processors {
+            let finished = processor
+                .finish()
+                .with_context(|| format!("{} failed to finish", processor.name()));
+            result = result.and(finished);
+        }
+        result
+    }
+}
+
+/// A built-in processor and its settings, as given on the command line.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProcessorSpec {
+    /// `resize=WxH`
+    Resize { width: i32, height: i32 },
+    /// `crop=WxH+X+Y`, clipped to the frame.
+    Crop {
+        width: i32,
+        height: i32,
+        x: i32,
+        y: i32,
+    },
+    /// `rotate=90|180|270`, clockwise.
+    Rotate { degrees: u16 },
+    /// `timestamp`: capture time (UTC) and sequence number in the bottom left corner.
+    Timestamp,
+    /// `motion[=FRACTION[,WxH+X+Y]...]`: flags frames where at least this share of the watched
+    /// regions (default the whole frame) moves against the background (default 0.01).
+    Motion {
+        threshold: f64,
+        regions: Vec<Region>,
+    },
+    /// `snapshot=DIR`: saves a JPEG into `DIR` at most once a second.
+    Snapshot { dir: PathBuf },
+    /// `record=DIR`: saves a clip of every motion event into `DIR`, starting
+    /// [`motion::PRE_EVENT`] before it, and logs it in [`motion::EVENT_LOG`].
+    Record { dir: PathBuf },
+}
+
+impl FromStr for ProcessorSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> anyhow::Result<Self> {
+        let (name, arg) = match spec.split_once('=') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (spec, None),
+        };
+        let required =
+            || arg.ok_or_else(|| anyhow!("{name} needs a value, e.g. {}", example(name)));
+
+        Ok(match name {
+            "resize" => {
+                let (width, height) = parse_size(required()?)?;
+                Self::Resize { width, height }
+            }
+            "crop" => {
+                let Region {
+                    width,
+                    height,
+                    x,
+                    y,
+                } = required()?.parse()?;
+                Self::Crop {
+                    width,
+                    height,
+                    x,
+                    y,
+                }
+            }
+            "rotate" => match required()?.parse() {
+                Ok(degrees @ (90 | 180 | 270)) => Self::Rotate { degrees },
+                _ => bail!("rotate takes 90, 180 or 270"),
+            },
+            "timestamp" => Self::Timestamp,
+            "motion" => {
+                let mut args = arg.into_iter().flat_map(|arg| arg.split(','));
+                let threshold = args
+                    .next()
+                    .map(str::parse)
+                    .transpose()
+                    .context("invalid motion threshold")?
+                    .unwrap_or(0.01);
+                if !(0.0..=1.0).contains(&threshold) {
+                    bail!("motion threshold must be between 0 and 1");
+                }
+                let regions = args.map(str::parse).collect::<anyhow::Result<_>>()?;
+                Self::Motion { threshold, regions }
+            }
+            "snapshot" => Self::Snapshot {
+                dir: required()?.into(),
+            },
+            "record" => Self::Record {
+                dir: required()?.into(),
+            },
+            _ => bail!(
+                "unknown processor {name:?}, expected resize, crop, rotate, timestamp, motion, snapshot or record"
+            ),
+        })
+    }
+}
+
+impl fmt::Display for ProcessorSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Resize { width, height } => write!(f, "resize={width}x{height}"),
+            Self::Crop {
+                width,
+                height,
+                x,
+                y,
+            } => write!(f, "crop={width}x{height}+{x}+{y}"),
+            Self::Rotate { degrees } => write!(f, "rotate={degrees}"),
+            Self::Timestamp => f.write_str("timestamp"),
+            Self::Motion { threshold, regions } => {
+                write!(f, "motion={threshold}")?;
+                for region in regions {
+                    write!(f, ",{region}")?;
+                }
+                Ok(())
+            }
+            Self::Snapshot { dir } => write!(f, "snapshot={}", dir.display()),
+            Self::Record { dir } => write!(f, "record={}", dir.display()),
+        }
+    }
+}
+
+impl ProcessorSpec {
+    /// Whether it draws text or outlines onto the frame.
+    fn draws_overlay(&self) -> bool {
+        matches!(self, Self::Timestamp | Self::Motion { .. })
+    }
+
+    pub fn build(&self) -> Box<dyn FrameProcessor> {
+        match self {
+            Self::Resize { width, height } => Box::new(Resize(Size::new(*width, *height))),
+            Self::Crop {
+                width,
+                height,
+                x,
+                y,
+            } => Box::new(Crop(Rect::new(*x, *y, *width, *height))),
+            Self::Rotate { degrees } => Box::new(Rotate(match degrees {
+                90 => core::ROTATE_90_CLOCKWISE,
+                180 => core::ROTATE_180,
+                _ => core::ROTATE_90_COUNTERCLOCKWISE,
+            })),
+            Self::Timestamp => Box::new(Timestamp),
+            Self::Motion { threshold, regions } => Box::new(Motion {
+                threshold: *threshold,
+                regions: regions.clone(),
+                detector: None,
+                moving: false,
+            }),
+            Self::Snapshot { dir } => Box::new(Snapshot {
+                dir: dir.clone(),
+                saved_at: None,
+            }),
+            Self::Record { dir } => Box::new(Record {
+                dir: dir.clone(),
+                recorder: None,
+            }),
+        }
+    }
+}
+
+/// Checks that every processor has what it needs earlier in the chain, and that nothing draws on
+/// the frame before `motion`, which would take the overlay for movement.
+pub fn check_order(specs: &[ProcessorSpec]) -> anyhow::Result<()> {
+    for (index, spec) in specs.iter().enumerate() {
+        let before = &specs[..index];
+        match spec {
+            ProcessorSpec::Record { .. }
+                if !before
+                    .iter()
+                    .any(|spec| matches!(spec, ProcessorSpec::Motion { .. })) =>
+            {
+                bail!("{spec} needs a motion processor before it");
+            }
+            ProcessorSpec::Motion { .. } => {
+                if let Some(overlay) = before.iter().find(|spec| spec.draws_overlay()) {
+                    bail!("{overlay} draws on the frame, put it after {spec}");
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn example(name: &str) -> &'static str {
+    match name {
+        "resize" => "resize=640x360",
+        "crop" => "crop=640x360+0+0",
+        "rotate" => "rotate=90",
+        "snapshot" => "snapshot=snapshots/",
+        "record" => "record=events/",
+        _ => "name=value",
+    }
+}
+
+fn parse_size(size: &str) -> anyhow::Result<(i32, i32)> {
+    size.split_once('x')
+        .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+        .filter(|&(width, height): &(i32, i32)| width > 0 && height > 0)
+        .ok_or_else(|| anyhow!("invalid size {size:?}, expected WxH"))
+}
+
+struct Resize(Size);
+
+impl FrameProcessor for Resize {
+    fn name(&self) -> &'static str {
+        "resize"
+    }
+
+    fn process(&mut self, frame: &mut Mat, _: &mut FrameContext) -> anyhow::Result<()> {
+        let mut resized = Mat::default();
+        imgproc::resize(
+            &*frame,
+            &mut resized,
+            self.0,
+            0.0,
+            0.0,
+            imgproc::INTER_LINEAR,
+        )?;
+        *frame = resized;
+        Ok(())
+    }
+}
+
+struct Crop(Rect);
+
+impl FrameProcessor for Crop {
+    fn name(&self) -> &'static str {
+        "crop"
+    }
+
+    fn process(&mut self, frame: &mut Mat, _: &mut FrameContext) -> anyhow::Result<()> {
+        let x = self.0.x.clamp(0, frame.cols());
+        let y = self.0.y.clamp(0, frame.rows());
+        let width = self.0.width.min(frame.cols() - x);
+        let height = self.0.height.min(frame.rows() - y);
+        if width == 0 || height == 0 {
+            bail!("crop {:?} is outside the frame", self.0);
+        }
+        let cropped = frame.roi(Rect::new(x, y, width, height))?.try_clone()?;
+        *frame = cropped;
+        Ok(())
+    }
+}
+
+struct Rotate(i32);
+
+impl FrameProcessor for Rotate {
+    fn name(&self) -> &'static str {
+        "rotate"
+    }
+
+    fn process(&mut self, frame: &mut Mat, _: &mut FrameContext) -> anyhow::Result<()> {
+        let mut rotated = Mat::default();
+        core::rotate(&*frame, &mut rotated, self.0)?;
+        *frame = rotated;
+        Ok(())
+    }
+}
+
+struct Timestamp;
+
+impl FrameProcessor for Timestamp {
+    fn name(&self) -> &'static str {
+        "timestamp"
+    }
+
+    fn process(&mut self, frame: &mut Mat, context: &mut FrameContext) -> anyhow::Result<()> {
+        let text = format!(
+            "{} #{}",
+            format_utc_time(context.captured_at_us),
+            context.seq
+        );
+        let origin = Point::new(10, frame.rows() - 40);
+        imgproc::put_text(
+            frame,
+            &text,
+            origin,
+            imgproc::FONT_HERSHEY_SIMPLEX,
+            0.6,
+            Scalar::new(255.0, 255.0, 255.0, 0.0),
+            2,
+            imgproc::LINE_AA,
+            false,
+        )?;
+        Ok(())
+    }
+}
+
+/// `HH:MM:SS.mmm` UTC of a Unix time in microseconds.
+pub fn format_utc_time(unix_us: u64) -> String {
+    let millis = unix_us / 1000;
+    let seconds = millis / 1000;
+    format!(
+        "{:02}:{:02}:{:02}.{:03} UTC",
+        seconds / 3600 % 24,
+        seconds / 60 % 60,
+        seconds % 60,
+        millis % 1000
+    )
+}
+
+/// Background subtraction over the watched regions, see [`MotionDetector`].
+struct Motion {
+    threshold: f64,
+    regions: Vec<Region>,
+    /// Made on the first frame, and again whenever the frame size changes.
+    detector: Option<(Size, MotionDetector)>,
+    moving: bool,
+}
+
+impl FrameProcessor for Motion {
+    fn name(&self) -> &'static str {
+        "motion"
+    }
+
+    fn process(&mut self, frame: &mut Mat, context: &mut FrameContext) -> anyhow::Result<()> {
+        let size = frame.size()?;
+        let detector = match &mut self.detector {
+            Some((made_for, detector)) if *made_for == size => detector,
+            detector => {
+                let made = MotionDetector::new(self.regions.clone())?;
+                &mut detector.insert((size, made)).1
+            }
+        };
+        let changed = detector.changed(frame)?;
+
+        let moving = changed >= self.threshold;
+        if moving != self.moving {
+            info!(
+                changed,
+                "Motion {}",
+                if moving { "started" } else { "stopped" }
+            );
+            self.moving = moving;
+        }
+        context.motion |= moving;
+        context.changed = context.changed.max(changed);
+
+        let yellow = Scalar::new(0.0, 255.0, 255.0, 0.0);
+        for region in &self.regions {
+            imgproc::rectangle(frame, region.rect(), yellow, 1, imgproc::LINE_8, 0)?;
+        }
+        if moving {
+            let red = Scalar::new(0.0, 0.0, 255.0, 0.0);
+            let border = Rect::new(0, 0, frame.cols(), frame.rows());
+            imgproc::rectangle(frame, border, red, 6, imgproc::LINE_8, 0)?;
+            imgproc::put_text(
+                frame,
+                &format!("MOTION {:.0}%", changed * 100.0),
+                Point::new(10, 90),
+                imgproc::FONT_HERSHEY_SIMPLEX,
+                0.7,
+                red,
+                2,
+                imgproc::LINE_AA,
+                false,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Saves `<stream>-<seq>.jpg`, at most once per [`SNAPSHOT_INTERVAL`].
+struct Snapshot {
+    dir: PathBuf,
+    saved_at: Option<Instant>,
+}
+
+impl FrameProcessor for Snapshot {
+    fn name(&self) -> &'static str {
+        "snapshot"
+    }
+
+    fn process(&mut self, frame: &mut Mat, context: &mut FrameContext) -> anyhow::Result<()> {
+        let now = Instant::now();
+        if self
+            .saved_at
+            .is_some_and(|at| now.duration_since(at) < SNAPSHOT_INTERVAL)
+        {
+            return Ok(());
+        }
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(format!(
+            "{}-{}.jpg",
+            mosaic::file_name(&context.stream),
+            context.seq
+        ));
+        let written = imgcodecs::imwrite(&path.to_string_lossy(), &*frame, &Vector::new())?;
+        anyhow::ensure!(written, "can't write {}", path.display());
+        self.saved_at = Some(now);
+        Ok(())
+    }
+}
+
+/// Clips and logs motion events, see [`EventRecorder`].
+struct Record {
+    dir: PathBuf,
+    /// Made on the first frame, which tells the stream ID.
+    recorder: Option<EventRecorder>,
+}
+
+impl FrameProcessor for Record {
+    fn name(&self) -> &'static str {
+        "record"
+    }
+
+    fn process(&mut self, frame: &mut Mat, context: &mut FrameContext) -> anyhow::Result<()> {
+        let recorder = self.recorder.get_or_insert_with(|| {
+            EventRecorder::new(
+                self.dir.clone(),
+                &context.stream,
+                motion::PRE_EVENT,
+                motion::POST_EVENT,
+            )
+        });
+        let motion = context.motion.then_some(context.changed);
+        recorder.push(frame, context.captured_at_us, motion)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.finish()?;
+        }
+        Ok(())
+    }
+}
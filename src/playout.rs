@@ -0,0 +1,165 @@
+//! Playout buffer: shows received frames on the schedule of their SRT timestamps, not on arrival.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::metrics::{self, Counter};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PlayoutTotals {
+    pub played: u64,
+    /// Frames that arrived after their slot, or were overtaken before they could be shown.
+    pub late: u64,
+    /// Times the buffer ran dry: a frame's slot came while nothing was queued to show in it.
+    pub underruns: u64,
+    /// Frames dropped, oldest first, because the buffer was full.
+    pub overruns: u64,
+}
+
+impl fmt::Display for PlayoutTotals {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} played, {} late, {} underruns, {} overruns",
+            self.played, self.late, self.underruns, self.overruns
+        )
+    }
+}
+
+/// Holds frames back until their delivery timestamp plus a target delay, smoothing out the
+/// jitter of the path they took after SRT handed them over.
+///
+/// Due times are anchored on the first frame: it is due `delay` after it arrived, and every
+/// later one `delay` after the transit of the first frame added to its own timestamp. A frame
+/// arriving past its due time is dropped as late, so is one a newer frame is already due
+/// behind. The buffer has run dry when the next frame lands more than a frame interval (the
+/// shortest timestamp step seen) after the due time of the last one shown, counted once per gap.
+/// The totals are mirrored into `playout_*_total` metrics labelled by `stream`.
+pub struct PlayoutBuffer<T> {
+    delay: Duration,
+    capacity: usize,
+    /// Arrival minus timestamp of the first frame.
+    transit: Option<Duration>,
+    /// Timestamp of the last frame pushed.
+    last_timestamp: Option<Instant>,
+    /// Shortest step between the timestamps of consecutive frames.
+    interval: Option<Duration>,
+    /// Due time of the last frame shown, until the gap after it is counted as an underrun.
+    shown_due: Option<Instant>,
+    queue: VecDeque<(Instant, T)>,
+    totals: PlayoutTotals,
+    late_metric: Counter,
+    underruns_metric: Counter,
+    overruns_metric: Counter,
+}
+
+impl<T> PlayoutBuffer<T> {
+    /// Buffers `delay` worth of frames, but never more than `capacity` of them.
+    pub fn new(stream: &str, delay: Duration, capacity: usize) -> Self {
+        let labels = [("stream", stream)];
+        Self {
+            delay,
+            capacity: capacity.max(1),
+            transit: None,
+            last_timestamp: None,
+            interval: None,
+            shown_due: None,
+            queue: VecDeque::new(),
+            totals: PlayoutTotals::default(),
+            late_metric: metrics::counter_with(
+                "playout_late_total",
+                "Frames dropped for missing their playout time",
+                &labels,
+            ),
+            underruns_metric: metrics::counter_with(
+                "playout_underruns_total",
+                "Times the playout buffer ran dry",
+                &labels,
+            ),
+            overruns_metric: metrics::counter_with(
+                "playout_overruns_total",
+                "Frames dropped because the playout buffer was full",
+                &labels,
+            ),
+        }
+    }
+
+    /// Queues `frame`, delivered with `timestamp` and arriving `now`. Returns `false` if it came
+    /// too late to be shown.
+    pub fn push(&mut self, timestamp: Instant, now: Instant, frame: T) -> bool {
+        if self.queue.is_empty()
+            && let (Some(shown), Some(interval)) = (self.shown_due, self.interval)
+            && now > shown + interval
+        {
+            self.totals.underruns += 1;
+            self.underruns_metric.inc();
+            self.shown_due = None;
+        }
+        if let Some(step) = self
+            .last_timestamp
+            .and_then(|last| timestamp.checked_duration_since(last))
+            .filter(|step| !step.is_zero())
+        {
+            self.interval = Some(self.interval.map_or(step, |interval| interval.min(step)));
+        }
+        self.last_timestamp = Some(
+            self.last_timestamp
+                .map_or(timestamp, |last| last.max(timestamp)),
+        );
+
+        let transit = *self
+            .transit
+            .get_or_insert_with(|| now.saturating_duration_since(timestamp));
+        let due = timestamp + transit + self.delay;
+        if due < now {
+            self.late(1);
+            return false;
+        }
+
+        let index = self.queue.partition_point(|(queued, _)| *queued <= due);
+        self.queue.insert(index, (due, frame));
+        if self.queue.len() > self.capacity {
+            self.queue.pop_front();
+            self.totals.overruns += 1;
+            self.overruns_metric.inc();
+        }
+        true
+    }
+
+    /// When the next frame is due, `None` while the buffer is empty.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.queue.front().map(|(due, _)| *due)
+    }
+
+    /// The frame to show at `now`: the newest one due by then, the others due are skipped as late.
+    pub fn pop(&mut self, now: Instant) -> Option<T> {
+        let due = self.queue.partition_point(|(due, _)| *due <= now);
+        let (shown, frame) = self.queue.drain(..due).last()?;
+        self.late(due as u64 - 1);
+
+        self.totals.played += 1;
+        self.shown_due = Some(shown);
+        Some(frame)
+    }
+
+    /// Frames waiting to be shown.
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn totals(&self) -> PlayoutTotals {
+        self.totals
+    }
+
+    fn late(&mut self, frames: u64) {
+        self.totals.late += frames;
+        self.late_metric.add(frames);
+    }
+}
@@ -0,0 +1,329 @@
+//! ffprobe-style inspection of a media input: container totals, streams and mpegts programs.
+//!
+//! Files, stdin and test sources are probed in place; live endpoints (`srt://`, `udp://`,
+//! `tcp://`) are captured for a while first. Bitrates and frame rates the codec parameters
+//! don't declare are measured from the packets read during the probe.
+
+use std::{
+    io::{self, Cursor, Read, Seek, SeekFrom},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use ac_ffmpeg::{
+    codec::CodecParameters,
+    format::{demuxer::Demuxer, io::IO, stream::Stream},
+    packet::Packet,
+};
+use anyhow::{Context, anyhow};
+use futures::TryStreamExt;
+use serde::Serialize;
+use tokio::time::{Instant, timeout_at};
+
+use crate::{
+    endpoint::Endpoint,
+    error::Error,
+    gateway, shutdown,
+    stdio::Input,
+    ts::{Program, ProgramScanner},
+};
+
+/// How much media to read when measuring rates, unless a shorter input ends first.
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ProbeReport {
+    pub input: String,
+    pub format: FormatInfo,
+    /// Empty unless the input is mpegts.
+    pub programs: Vec<Program>,
+    pub streams: Vec<StreamInfo>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FormatInfo {
+    /// Longest stream duration, in seconds.
+    pub duration: Option<f64>,
+    /// Sum of the stream bitrates, in bits/s.
+    pub bit_rate: Option<u64>,
+    /// TS rate measured between PCRs, including muxing overhead, in bits/s.
+    pub mux_rate: Option<u64>,
+    pub probed_bytes: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CodecType {
+    Video,
+    Audio,
+    Other,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct StreamInfo {
+    pub index: usize,
+    pub codec_type: CodecType,
+    pub codec_name: Option<String>,
+    /// As `num/den`.
+    pub time_base: String,
+    /// In seconds.
+    pub start_time: Option<f64>,
+    /// In seconds.
+    pub duration: Option<f64>,
+    /// Declared by the codec parameters, otherwise measured; bits/s.
+    pub bit_rate: Option<u64>,
+    /// Measured from packet timestamps.
+    pub frame_rate: Option<f64>,
+    /// Packets read while probing.
+    pub packets: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pixel_format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channels: Option<u32>,
+    /// mpegts only.
+    pub pid: Option<u16>,
+    /// From the ISO 639 descriptor of the stream's PMT entry, mpegts only.
+    pub language: Option<String>,
+}
+
+impl StreamInfo {
+    /// Describes what the demuxer knows about a stream, before any packets are counted.
+    pub fn new(index: usize, stream: &Stream) -> Self {
+        let params = stream.codec_parameters();
+        let time_base = stream.time_base();
+        let seconds = |timestamp: ac_ffmpeg::time::Timestamp| {
+            (!timestamp.is_null()).then(|| timestamp.as_f64()).flatten()
+        };
+
+        let mut info = Self {
+            index,
+            codec_type: CodecType::Other,
+            codec_name: None,
+            time_base: format!("{}/{}", time_base.num(), time_base.den()),
+            start_time: seconds(stream.start_time()),
+            duration: seconds(stream.duration()).filter(|duration| *duration > 0.0),
+            bit_rate: None,
+            frame_rate: None,
+            packets: 0,
+            width: None,
+            height: None,
+            pixel_format: None,
+            sample_format: None,
+            sample_rate: None,
+            channels: None,
+            pid: None,
+            language: None,
+        };
+        info.describe_codec(&params);
+        info
+    }
+
+    fn describe_codec(&mut self, params: &CodecParameters) {
+        if let Some(video) = params.as_video_codec_parameters() {
+            self.codec_type = CodecType::Video;
+            self.codec_name = video.decoder_name().map(str::to_string);
+            self.bit_rate = Some(video.bit_rate()).filter(|rate| *rate > 0);
+            self.width = Some(video.width());
+            self.height = Some(video.height());
+            self.pixel_format = Some(video.pixel_format().name().to_string());
+        } else if let Some(audio) = params.as_audio_codec_parameters() {
+            self.codec_type = CodecType::Audio;
+            self.codec_name = audio.decoder_name().map(str::to_string);
+            self.bit_rate = Some(audio.bit_rate()).filter(|rate| *rate > 0);
+            self.sample_format = Some(audio.sample_format().name().to_string());
+            self.sample_rate = Some(audio.sample_rate());
+            self.channels = Some(audio.channel_layout().channels());
+        } else {
+            self.codec_name = params.decoder_name().map(str::to_string);
+        }
+    }
+}
+
+/// Packet totals of one stream during the probe.
+#[derive(Clone, Copy, Default)]
+struct PacketCount {
+    packets: u64,
+    bytes: u64,
+    first_pts: Option<f64>,
+    last_pts: Option<f64>,
+}
+
+impl PacketCount {
+    fn add(&mut self, packet: &Packet) {
+        self.packets += 1;
+        self.bytes += packet.data().len() as u64;
+        if let Some(pts) = packet.pts().as_f64() {
+            self.first_pts = Some(self.first_pts.map_or(pts, |first| first.min(pts)));
+            self.last_pts = Some(self.last_pts.map_or(pts, |last| last.max(pts)));
+        }
+    }
+
+    fn span(&self) -> Option<f64> {
+        let span = self.last_pts? - self.first_pts?;
+        (span > 0.0).then_some(span)
+    }
+
+    fn bit_rate(&self) -> Option<u64> {
+        Some((self.bytes as f64 * 8.0 / self.span()?) as u64)
+    }
+
+    fn frame_rate(&self) -> Option<f64> {
+        let rate = (self.packets - 1) as f64 / self.span()?;
+        Some((rate * 1000.0).round() / 1000.0)
+    }
+}
+
+/// Feeds everything the demuxer reads to the program scanner as well.
+struct Tap<R> {
+    inner: R,
+    scanner: Arc<Mutex<ProgramScanner>>,
+}
+
+impl<R: Read> Read for Tap<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.scanner.lock().unwrap().push(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for Tap<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let offset = self.inner.seek(pos)?;
+        self.scanner.lock().unwrap().discontinuity();
+        Ok(offset)
+    }
+}
+
+/// Probes `reader`, counting packets for up to `window` of media.
+pub fn probe<R: Read + Seek>(
+    name: &str,
+    reader: R,
+    seekable: bool,
+    window: Duration,
+) -> anyhow::Result<ProbeReport> {
+    let scanner = Arc::new(Mutex::new(ProgramScanner::default()));
+    let tap = Tap {
+        inner: reader,
+        scanner: scanner.clone(),
+    };
+    let io = if seekable {
+        IO::from_seekable_read_stream(tap)
+    } else {
+        IO::from_read_stream(tap)
+    };
+    let demux_error = || Error::Demux {
+        input: name.to_string(),
+    };
+    let mut demuxer = Demuxer::builder()
+        .build(io)
+        .with_context(demux_error)?
+        .find_stream_info(None)
+        .map_err(|(_, e)| e)
+        .with_context(demux_error)?;
+
+    let mut streams = demuxer
+        .streams()
+        .iter()
+        .enumerate()
+        .map(|(index, stream)| StreamInfo::new(index, stream))
+        .collect::<Vec<_>>();
+
+    let mut counts = vec![PacketCount::default(); streams.len()];
+    let window = window.as_secs_f64();
+    while let Some(packet) = demuxer.take().with_context(demux_error)? {
+        let Some(count) = counts.get_mut(packet.stream_index()) else {
+            continue;
+        };
+        count.add(&packet);
+        if count.span().is_some_and(|span| span >= window) {
+            break;
+        }
+    }
+
+    for (info, count) in streams.iter_mut().zip(&counts) {
+        info.packets = count.packets;
+        info.bit_rate = info.bit_rate.or_else(|| count.bit_rate());
+        if info.codec_type == CodecType::Video {
+            info.frame_rate = count.frame_rate();
+        }
+    }
+
+    let scanner = scanner.lock().unwrap();
+    let programs = scanner.programs();
+    // FFmpeg's mpegts demuxer adds streams in PMT order, so a single program lines up by index
+    if let [program] = programs.as_slice()
+        && program.streams.len() == streams.len()
+    {
+        for (info, pmt) in streams.iter_mut().zip(&program.streams) {
+            info.pid = Some(pmt.pid);
+            info.language = pmt.language.clone();
+        }
+    }
+
+    let format = FormatInfo {
+        duration: streams
+            .iter()
+            .filter_map(|info| info.duration)
+            .max_by(f64::total_cmp),
+        bit_rate: streams
+            .iter()
+            .map(|info| info.bit_rate)
+            .sum::<Option<u64>>()
+            .filter(|_| !streams.is_empty()),
+        mux_rate: scanner.mux_rate(),
+        probed_bytes: scanner.bytes(),
+    };
+
+    Ok(ProbeReport {
+        input: name.to_string(),
+        format,
+        programs,
+        streams,
+    })
+}
+
+/// Probes a file, `-` for stdin or a test source spec.
+pub fn probe_path(path: &str, window: Duration) -> anyhow::Result<ProbeReport> {
+    let input = Input::open(path)?;
+    let seekable = input.is_seekable();
+    probe(path, input, seekable, window)
+}
+
+/// Records `duration` of a live endpoint (or less if it ends), then probes the recording. The
+/// duration includes waiting for the peer: fails with [`Error::Connection`] if none connected.
+pub async fn probe_endpoint(
+    endpoint: &Endpoint,
+    duration: Duration,
+) -> anyhow::Result<ProbeReport> {
+    let deadline = Instant::now() + duration;
+    let input = shutdown::until(timeout_at(deadline, endpoint.open_input()))
+        .await
+        .ok_or_else(|| anyhow!("no connection on {endpoint} yet"))?
+        .with_context(|| format!("no peer within {duration:?}"))
+        .with_context(|| Error::Connection {
+            endpoint: endpoint.to_string(),
+        })??;
+    let mut input = gateway::align_ts(input, endpoint.scheme);
+    let mut captured = Vec::new();
+    // Shutdown cuts the recording short, what was captured so far is still probed
+    while let Some(Ok(next)) = shutdown::until(timeout_at(deadline, input.try_next())).await {
+        match next? {
+            Some(bytes) => captured.extend_from_slice(&bytes),
+            None => break,
+        }
+    }
+    drop(input);
+
+    let name = endpoint.to_string();
+    tokio::task::spawn_blocking(move || probe(&name, Cursor::new(captured), true, Duration::MAX))
+        .await?
+}
@@ -0,0 +1,269 @@
+//! Message framing shared by the senders and receivers.
+//!
+//! Every SRT message starts with a one-byte type followed by big-endian fields.
+//! Frames and TS data carry a sender-assigned sequence number so receivers can detect
+//! loss, duplicates and reordering (see [`crate::sequence`]).
+//!
+//! TS goes out as raw 1316-byte payloads by default, so any SRT mpegts receiver can play it.
+//! Numbered [`Message::Data`] framing is opt-in (`--sequence` or `?seq=1`), and then carries
+//! [`SEQUENCED_TS_PAYLOAD_SIZE`] bytes of TS so the message still fits 1316 bytes.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, anyhow, bail, ensure};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::{
+    error::Error,
+    ts::{SRT_PAYLOAD_SIZE, SYNC_BYTE, TS_PACKET_SIZE},
+};
+
+/// Clock probes the viewer sends after connecting, before frames start flowing.
+pub const CLOCK_PROBES: usize = 5;
+/// Type and sequence number in front of a [`Message::Data`] payload.
+pub const DATA_HEADER_SIZE: usize = 9;
+/// Whole TS packets that fit a 1316-byte message behind the data header: six of them.
+pub const SEQUENCED_TS_PAYLOAD_SIZE: usize =
+    (SRT_PAYLOAD_SIZE - DATA_HEADER_SIZE) / TS_PACKET_SIZE * TS_PACKET_SIZE;
+
+/// TS bytes per message, with or without the numbered framing.
+pub fn ts_payload_size(sequenced: bool) -> usize {
+    if sequenced {
+        SEQUENCED_TS_PAYLOAD_SIZE
+    } else {
+        SRT_PAYLOAD_SIZE
+    }
+}
+
+const FRAME: u8 = 0x01;
+// Anything but 0x47, so raw TS payloads stay distinguishable from wrapped ones
+const DATA: u8 = 0x02;
+const CLOCK_PROBE: u8 = 0x10;
+const CLOCK_REPLY: u8 = 0x11;
+const END_OF_STREAM: u8 = 0x20;
+const MANIFEST: u8 = 0x30;
+const RESUME: u8 = 0x31;
+const CHUNK: u8 = 0x32;
+const VERIFIED: u8 = 0x33;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    /// Encoded camera frame, stamped with the sender's wall clock at capture.
+    Frame {
+        seq: u64,
+        captured_at_us: u64,
+        payload: Bytes,
+    },
+    /// Opaque media data (TS payloads or file blocks) from the senders.
+    Data { seq: u64, payload: Bytes },
+    /// Last message of a stream: how many data messages and payload bytes were sent in total.
+    EndOfStream { frames: u64, bytes: u64 },
+    /// Sent by the viewer to measure the clock offset, carrying its own send time.
+    ClockProbe { sent_at_us: u64 },
+    /// Answer to a probe: the probe's send time echoed back plus the sender's clock on receipt.
+    ClockReply {
+        probe_sent_at_us: u64,
+        remote_at_us: u64,
+    },
+    /// Announces a file transfer (see [`crate::transfer`]).
+    Manifest {
+        name: String,
+        size: u64,
+        sha256: [u8; 32],
+    },
+    /// Receiver asks for the file from `offset` on, after the manifest or when it spots a gap.
+    Resume { offset: u64 },
+    /// File data starting at `offset`.
+    Chunk { offset: u64, payload: Bytes },
+    /// Receiver's verdict once the whole file arrived and its SHA-256 was checked.
+    Verified { ok: bool },
+}
+
+impl Message {
+    pub fn encode(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+        match self {
+            Self::Frame {
+                seq,
+                captured_at_us,
+                payload,
+            } => {
+                buf.reserve(17 + payload.len());
+                buf.put_u8(FRAME);
+                buf.put_u64(*seq);
+                buf.put_u64(*captured_at_us);
+                buf.put_slice(payload);
+            }
+            Self::Data { seq, payload } => {
+                buf.reserve(DATA_HEADER_SIZE + payload.len());
+                buf.put_u8(DATA);
+                buf.put_u64(*seq);
+                buf.put_slice(payload);
+            }
+            Self::ClockProbe { sent_at_us } => {
+                buf.put_u8(CLOCK_PROBE);
+                buf.put_u64(*sent_at_us);
+            }
+            Self::ClockReply {
+                probe_sent_at_us,
+                remote_at_us,
+            } => {
+                buf.put_u8(CLOCK_REPLY);
+                buf.put_u64(*probe_sent_at_us);
+                buf.put_u64(*remote_at_us);
+            }
+            Self::EndOfStream { frames, bytes } => {
+                buf.put_u8(END_OF_STREAM);
+                buf.put_u64(*frames);
+                buf.put_u64(*bytes);
+            }
+            Self::Manifest { name, size, sha256 } => {
+                buf.put_u8(MANIFEST);
+                buf.put_u16(name.len() as u16);
+                buf.put_slice(name.as_bytes());
+                buf.put_u64(*size);
+                buf.put_slice(sha256);
+            }
+            Self::Resume { offset } => {
+                buf.put_u8(RESUME);
+                buf.put_u64(*offset);
+            }
+            Self::Chunk { offset, payload } => {
+                buf.reserve(9 + payload.len());
+                buf.put_u8(CHUNK);
+                buf.put_u64(*offset);
+                buf.put_slice(payload);
+            }
+            Self::Verified { ok } => {
+                buf.put_u8(VERIFIED);
+                buf.put_u8(*ok as u8);
+            }
+        }
+        buf.freeze()
+    }
+
+    /// Fails with [`Error::Protocol`] for anything that isn't a message of this protocol.
+    pub fn decode(bytes: Bytes) -> anyhow::Result<Self> {
+        Self::parse(bytes).context(Error::Protocol)
+    }
+
+    fn parse(mut bytes: Bytes) -> anyhow::Result<Self> {
+        ensure!(!bytes.is_empty(), "empty message");
+        let kind = bytes.get_u8();
+
+        let message = match kind {
+            FRAME => {
+                ensure!(bytes.len() >= 16, "truncated frame header");
+                let seq = bytes.get_u64();
+                let captured_at_us = bytes.get_u64();
+                Self::Frame {
+                    seq,
+                    captured_at_us,
+                    payload: bytes,
+                }
+            }
+            DATA => {
+                ensure!(bytes.len() >= 8, "truncated data header");
+                let seq = bytes.get_u64();
+                Self::Data {
+                    seq,
+                    payload: bytes,
+                }
+            }
+            CLOCK_PROBE => {
+                ensure!(bytes.len() >= 8, "truncated clock probe");
+                Self::ClockProbe {
+                    sent_at_us: bytes.get_u64(),
+                }
+            }
+            CLOCK_REPLY => {
+                ensure!(bytes.len() >= 16, "truncated clock reply");
+                Self::ClockReply {
+                    probe_sent_at_us: bytes.get_u64(),
+                    remote_at_us: bytes.get_u64(),
+                }
+            }
+            END_OF_STREAM => {
+                ensure!(bytes.len() >= 16, "truncated end of stream");
+                Self::EndOfStream {
+                    frames: bytes.get_u64(),
+                    bytes: bytes.get_u64(),
+                }
+            }
+            MANIFEST => {
+                ensure!(bytes.len() >= 2, "truncated manifest");
+                let name_len = bytes.get_u16() as usize;
+                ensure!(bytes.len() >= name_len + 40, "truncated manifest");
+                let name = String::from_utf8(bytes.split_to(name_len).to_vec())?;
+                let size = bytes.get_u64();
+                let mut sha256 = [0; 32];
+                bytes.copy_to_slice(&mut sha256);
+                Self::Manifest { name, size, sha256 }
+            }
+            RESUME => {
+                ensure!(bytes.len() >= 8, "truncated resume");
+                Self::Resume {
+                    offset: bytes.get_u64(),
+                }
+            }
+            CHUNK => {
+                ensure!(bytes.len() >= 8, "truncated chunk header");
+                let offset = bytes.get_u64();
+                Self::Chunk {
+                    offset,
+                    payload: bytes,
+                }
+            }
+            VERIFIED => {
+                ensure!(!bytes.is_empty(), "truncated verification");
+                Self::Verified {
+                    ok: bytes.get_u8() != 0,
+                }
+            }
+            other => bail!("unknown message type {other:#04x}"),
+        };
+        Ok(message)
+    }
+}
+
+/// A message on a TS data stream, see [`unwrap_data`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Data {
+    /// Media payload, numbered unless it arrived as raw TS.
+    Payload {
+        seq: Option<u64>,
+        payload: Bytes,
+    },
+    EndOfStream {
+        frames: u64,
+        bytes: u64,
+    },
+}
+
+/// Splits a TS sender message into its sequence number and payload.
+///
+/// Raw TS (starting with the sync byte, e.g. from ffmpeg or the gateway) is passed through without a sequence number.
+pub fn unwrap_data(bytes: Bytes) -> anyhow::Result<Data> {
+    if bytes.first() == Some(&SYNC_BYTE) {
+        return Ok(Data::Payload {
+            seq: None,
+            payload: bytes,
+        });
+    }
+    match Message::decode(bytes)? {
+        Message::Data { seq, payload } => Ok(Data::Payload {
+            seq: Some(seq),
+            payload,
+        }),
+        Message::EndOfStream { frames, bytes } => Ok(Data::EndOfStream { frames, bytes }),
+        other => Err(anyhow!("unexpected {other:?} on a data stream").context(Error::Protocol)),
+    }
+}
+
+/// Wall clock in microseconds since the Unix epoch, comparable across hosts once offset-corrected.
+pub fn now_us() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64
+}
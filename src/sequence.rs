@@ -0,0 +1,408 @@
+//! Loss, duplicate and reorder detection from sender-assigned sequence numbers, or from the
+//! per-PID continuity counters of raw TS.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use tracing::{info, warn};
+
+use crate::{
+    error::EXIT_TRUNCATED,
+    metrics::{self, Counter},
+    protocol::Data,
+    ts::{self, TS_PACKET_SIZE},
+};
+
+/// Period of the loss summaries printed by [`SequenceTracker::track`].
+pub const REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Missing sequence numbers remembered for late arrivals; older gaps count as lost for good.
+const MAX_TRACKED_GAPS: usize = 4096;
+
+/// How a sequence number fits into what was received so far.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arrival {
+    InOrder,
+    /// Arrived after skipping `missing` sequence numbers.
+    Gap {
+        missing: u64,
+    },
+    /// Arrived after a later number, filling an earlier gap.
+    Reordered,
+    Duplicate,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SequenceTotals {
+    pub received: u64,
+    /// Sequence numbers skipped and not (yet) filled by a late arrival.
+    pub lost: u64,
+    pub duplicates: u64,
+    pub reordered: u64,
+}
+
+impl SequenceTotals {
+    /// Lost over expected (received unique plus lost), 0 before anything arrived.
+    pub fn loss_rate(&self) -> f64 {
+        let unique = self.received - self.duplicates;
+        let expected = unique + self.lost;
+        if expected == 0 {
+            0.0
+        } else {
+            self.lost as f64 / expected as f64
+        }
+    }
+
+    fn since(&self, earlier: &Self) -> Self {
+        Self {
+            received: self.received - earlier.received,
+            lost: self.lost.saturating_sub(earlier.lost),
+            duplicates: self.duplicates - earlier.duplicates,
+            reordered: self.reordered - earlier.reordered,
+        }
+    }
+}
+
+impl fmt::Display for SequenceTotals {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} received, {} lost ({:.2}%), {} duplicates, {} reordered",
+            self.received,
+            self.lost,
+            self.loss_rate() * 100.0,
+            self.duplicates,
+            self.reordered
+        )
+    }
+}
+
+/// Tracks one sender's sequence numbers and mirrors the totals into metrics labelled by `stream`.
+pub struct SequenceTracker {
+    stream: String,
+    expected: Option<u64>,
+    gaps: BTreeSet<u64>,
+    totals: SequenceTotals,
+    interval_start: (Instant, SequenceTotals),
+    lost_metric: Counter,
+    duplicates_metric: Counter,
+    reordered_metric: Counter,
+}
+
+impl SequenceTracker {
+    pub fn new(stream: &str) -> Self {
+        let labels = [("stream", stream)];
+        Self {
+            stream: stream.to_string(),
+            expected: None,
+            gaps: BTreeSet::new(),
+            totals: SequenceTotals::default(),
+            interval_start: (Instant::now(), SequenceTotals::default()),
+            lost_metric: metrics::counter_with(
+                "sequence_lost_total",
+                "Sequence numbers skipped on arrival, including ones that arrived late",
+                &labels,
+            ),
+            duplicates_metric: metrics::counter_with(
+                "sequence_duplicates_total",
+                "Sequence numbers received more than once",
+                &labels,
+            ),
+            reordered_metric: metrics::counter_with(
+                "sequence_reordered_total",
+                "Sequence numbers received after a later one",
+                &labels,
+            ),
+        }
+    }
+
+    pub fn observe(&mut self, seq: u64) -> Arrival {
+        self.totals.received += 1;
+
+        let arrival = match self.expected {
+            None => Arrival::InOrder,
+            Some(expected) if seq == expected => Arrival::InOrder,
+            Some(expected) if seq > expected => {
+                let missing = seq - expected;
+                self.totals.lost += missing;
+                self.lost_metric.add(missing);
+                // Only the most recent gaps can still be filled by a late arrival
+                let first = expected.max(seq.saturating_sub(MAX_TRACKED_GAPS as u64));
+                self.gaps.extend(first..seq);
+                while self.gaps.len() > MAX_TRACKED_GAPS {
+                    self.gaps.pop_first();
+                }
+                Arrival::Gap { missing }
+            }
+            Some(_) if self.gaps.remove(&seq) => {
+                self.totals.lost -= 1;
+                self.totals.reordered += 1;
+                self.reordered_metric.inc();
+                Arrival::Reordered
+            }
+            Some(_) => {
+                self.totals.duplicates += 1;
+                self.duplicates_metric.inc();
+                Arrival::Duplicate
+            }
+        };
+
+        if matches!(arrival, Arrival::InOrder | Arrival::Gap { .. }) {
+            self.expected = Some(seq + 1);
+        }
+        arrival
+    }
+
+    /// Observes `seq`, logging anomalies and a loss summary every [`REPORT_INTERVAL`].
+    pub fn track(&mut self, seq: u64) -> Arrival {
+        let arrival = self.observe(seq);
+        match arrival {
+            Arrival::InOrder => {}
+            Arrival::Gap { missing } => {
+                warn!(stream = %self.stream, seq, missing, "{missing} missing before seq {seq}")
+            }
+            Arrival::Reordered => {
+                warn!(stream = %self.stream, seq, "seq {seq} arrived out of order")
+            }
+            Arrival::Duplicate => warn!(stream = %self.stream, seq, "duplicate seq {seq}"),
+        }
+        self.report();
+        arrival
+    }
+
+    /// Counts a raw TS packet on `pid` that arrived as [`ContinuityTracker`] found it, logging
+    /// anomalies like [`track`](Self::track). Reordering can't be told apart from loss here.
+    pub fn track_packet(&mut self, pid: u16, arrival: Arrival) {
+        self.totals.received += 1;
+        match arrival {
+            Arrival::InOrder | Arrival::Reordered => {}
+            Arrival::Gap { missing } => {
+                self.totals.lost += missing;
+                self.lost_metric.add(missing);
+                warn!(stream = %self.stream, pid, missing, "{missing} TS packets missing on PID {pid}");
+            }
+            Arrival::Duplicate => {
+                self.totals.duplicates += 1;
+                self.duplicates_metric.inc();
+                warn!(stream = %self.stream, pid, "duplicate TS packet on PID {pid}");
+            }
+        }
+        self.report();
+    }
+
+    fn report(&mut self) {
+        if let Some(interval) = self.interval(REPORT_INTERVAL) {
+            info!(
+                stream = %self.stream,
+                "last {}s: {interval}",
+                REPORT_INTERVAL.as_secs()
+            );
+        }
+    }
+
+    pub fn totals(&self) -> SequenceTotals {
+        self.totals
+    }
+
+    /// Returns the counts since the previous interval once `every` has elapsed, starting a new interval.
+    pub fn interval(&mut self, every: Duration) -> Option<SequenceTotals> {
+        let (started, at_start) = self.interval_start;
+        if started.elapsed() < every {
+            return None;
+        }
+        self.interval_start = (Instant::now(), self.totals);
+        Some(self.totals.since(&at_start))
+    }
+}
+
+/// Follows the 4-bit continuity counter of every PID in raw TS, the only loss signal it carries
+/// without a sequence header.
+#[derive(Default)]
+pub struct ContinuityTracker {
+    last: HashMap<u16, u8>,
+}
+
+impl ContinuityTracker {
+    /// How `packet` follows the previous one on its PID; `None` for packets that carry no count
+    /// (null packets, adaptation field only). Only up to 15 missing packets in a row are seen.
+    pub fn check(&mut self, packet: &[u8]) -> Option<Arrival> {
+        let pid = ts::pid(packet);
+        if pid == ts::NULL_PID {
+            return None;
+        }
+        let (counter, discontinuity) = ts::continuity(packet)?;
+        let last = self.last.insert(pid, counter);
+        Some(match last {
+            None => Arrival::InOrder,
+            Some(_) if discontinuity => Arrival::InOrder,
+            // A packet may be sent twice in a row, with the same count
+            Some(last) if last == counter => Arrival::Duplicate,
+            Some(last) => match counter.wrapping_sub(last).wrapping_sub(1) & 0x0f {
+                0 => Arrival::InOrder,
+                missing => Arrival::Gap {
+                    missing: missing.into(),
+                },
+            },
+        })
+    }
+}
+
+/// Whether a received stream matched the totals announced by the sender.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Completion {
+    Complete {
+        frames: u64,
+        bytes: u64,
+    },
+    /// Raw TS ended by the sender closing cleanly: there are no totals to check it against.
+    Unchecked {
+        frames: u64,
+        bytes: u64,
+    },
+    Truncated {
+        reason: String,
+    },
+}
+
+impl Completion {
+    /// `0` for a complete or unchecked transfer, [`EXIT_TRUNCATED`] otherwise.
+    pub fn exit_code(&self) -> ExitCode {
+        match self {
+            Self::Complete { .. } | Self::Unchecked { .. } => ExitCode::SUCCESS,
+            Self::Truncated { .. } => ExitCode::from(EXIT_TRUNCATED),
+        }
+    }
+}
+
+impl fmt::Display for Completion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Complete { frames, bytes } => {
+                write!(f, "complete, {frames} frames ({bytes} bytes)")
+            }
+            Self::Unchecked { frames, bytes } => {
+                write!(f, "unchecked raw TS, {frames} frames ({bytes} bytes)")
+            }
+            Self::Truncated { reason } => write!(f, "truncated, {reason}"),
+        }
+    }
+}
+
+/// Follows a data stream from one of the senders up to its end-of-stream message. Raw TS is
+/// tracked by its continuity counters instead, so its totals count TS packets.
+pub struct StreamCheck {
+    tracker: SequenceTracker,
+    continuity: ContinuityTracker,
+    frames: u64,
+    bytes: u64,
+    /// Whether any payload carried a sequence number.
+    numbered: bool,
+    end: Option<(u64, u64)>,
+    /// Why the link broke before the stream ended, if it did.
+    failure: Option<String>,
+}
+
+impl StreamCheck {
+    pub fn new(stream: &str) -> Self {
+        Self {
+            tracker: SequenceTracker::new(stream),
+            continuity: ContinuityTracker::default(),
+            frames: 0,
+            bytes: 0,
+            numbered: false,
+            end: None,
+            failure: None,
+        }
+    }
+
+    /// Returns the payload to pass on; `None` for the end-of-stream message and duplicates.
+    pub fn accept(&mut self, data: Data) -> Option<Bytes> {
+        match data {
+            Data::Payload { seq, payload } => {
+                match seq {
+                    Some(seq) => {
+                        self.numbered = true;
+                        if self.tracker.track(seq) == Arrival::Duplicate {
+                            return None;
+                        }
+                    }
+                    None => {
+                        for packet in payload.chunks_exact(TS_PACKET_SIZE) {
+                            if let Some(arrival) = self.continuity.check(packet) {
+                                self.tracker.track_packet(ts::pid(packet), arrival);
+                            }
+                        }
+                    }
+                }
+                self.frames += 1;
+                self.bytes += payload.len() as u64;
+                Some(payload)
+            }
+            Data::EndOfStream { frames, bytes } => {
+                self.end = Some((frames, bytes));
+                None
+            }
+        }
+    }
+
+    /// Whether the sender announced the end of the stream.
+    pub fn ended(&self) -> bool {
+        self.end.is_some()
+    }
+
+    /// Records that the link failed before the stream ended, so even raw TS counts as truncated.
+    pub fn fail(&mut self, error: impl fmt::Display) {
+        self.failure = Some(error.to_string());
+    }
+
+    /// Whether the stream is numbered, and so can be checked for completeness.
+    pub fn numbered(&self) -> bool {
+        self.numbered || self.end.is_some()
+    }
+
+    pub fn totals(&self) -> SequenceTotals {
+        self.tracker.totals()
+    }
+
+    /// Compares what arrived with the sender's totals.
+    pub fn finish(&self) -> Completion {
+        if let Some(failure) = &self.failure
+            && self.end.is_none()
+        {
+            return Completion::Truncated {
+                reason: format!(
+                    "{failure} after {} frames ({} bytes)",
+                    self.frames, self.bytes
+                ),
+            };
+        }
+        if !self.numbered() {
+            return Completion::Unchecked {
+                frames: self.frames,
+                bytes: self.bytes,
+            };
+        }
+        let Some((frames, bytes)) = self.end else {
+            return Completion::Truncated {
+                reason: format!(
+                    "no end of stream after {} frames ({} bytes)",
+                    self.frames, self.bytes
+                ),
+            };
+        };
+        if frames != self.frames || bytes != self.bytes {
+            return Completion::Truncated {
+                reason: format!(
+                    "got {}/{frames} frames ({}/{bytes} bytes)",
+                    self.frames, self.bytes
+                ),
+            };
+        }
+        Completion::Complete { frames, bytes }
+    }
+}
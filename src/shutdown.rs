@@ -0,0 +1,63 @@
+//! Graceful shutdown on SIGINT/SIGTERM.
+//!
+//! The first signal asks everything to wind down: stop capturing or demuxing, finish the muxer,
+//! announce the end of stream, drain and close SRT sockets and flush outputs. A second signal
+//! exits right away.
+
+use std::{future::Future, io, process, sync::LazyLock};
+
+use tokio::{
+    signal::unix::{SignalKind, signal},
+    sync::watch,
+};
+use tracing::warn;
+
+/// Exit code for an interrupted run, as a shell reports a process killed by SIGINT.
+pub const EXIT_INTERRUPTED: u8 = 130;
+
+static REQUESTED: LazyLock<watch::Sender<bool>> = LazyLock::new(|| watch::Sender::new(false));
+
+/// Starts listening for SIGINT and SIGTERM. Call once from within the runtime.
+pub fn install() -> io::Result<()> {
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = interrupt.recv() => {}
+                _ = terminate.recv() => {}
+            }
+            if requested() {
+                warn!("Second signal, exiting now");
+                process::exit(EXIT_INTERRUPTED.into());
+            }
+            warn!("Shutting down cleanly, signal again to exit now …");
+            request();
+        }
+    });
+    Ok(())
+}
+
+/// Asks everything to wind down, as the first signal does.
+pub fn request() {
+    REQUESTED.send_replace(true);
+}
+
+pub fn requested() -> bool {
+    *REQUESTED.borrow()
+}
+
+/// Resolves once shutdown was requested.
+pub async fn wait() {
+    let mut requested = REQUESTED.subscribe();
+    // The sender lives in a static, so this only returns once the flag is set
+    let _ = requested.wait_for(|requested| *requested).await;
+}
+
+/// Runs `future` to completion, or returns `None` if shutdown is requested first.
+pub async fn until<F: Future>(future: F) -> Option<F::Output> {
+    tokio::select! {
+        output = future => Some(output),
+        _ = wait() => None,
+    }
+}
@@ -0,0 +1,593 @@
+//! Periodic SRT link statistics, logged and optionally written as CSV or JSON lines.
+//!
+//! Enabled per process through the environment:
+//! - `SRT_STATS=1` logs a sample every interval,
+//! - `SRT_STATS=<path>` also appends samples to `<path>` (CSV for `.csv`, JSON lines otherwise),
+//! - `SRT_STATS_INTERVAL=<ms>` changes the interval (default 1000).
+//!
+//! Every open socket is also listed by [`sessions`], for the control API.
+//!
+//! srt-tokio 0.4's [`SocketStatistics`] only carries packet, byte and buffer counters, and keeps
+//! its RTT and link capacity estimates internal to the protocol state. Bandwidth isn't sampled at
+//! all; the round trip is only known where this crate measures it itself, from the clock probes
+//! `view` sends a camera ([`StatsSocket::record_round_trip`]).
+
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    net::SocketAddr,
+    path::PathBuf,
+    pin::Pin,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+use futures::{Sink, Stream, StreamExt};
+use serde::Serialize;
+use srt_tokio::{SocketStatistics, SrtSocket};
+use tracing::{debug, info, warn};
+
+use crate::metrics::{self, Counter, Gauge};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatsFormat {
+    Csv,
+    JsonLines,
+}
+
+#[derive(Clone, Debug)]
+pub struct StatsConfig {
+    pub interval: Duration,
+    /// Log every sample.
+    pub print: bool,
+    pub output: Option<(PathBuf, StatsFormat)>,
+}
+
+impl StatsConfig {
+    pub fn from_env() -> Option<Self> {
+        let value = env::var("SRT_STATS")
+            .ok()
+            .filter(|value| !value.is_empty() && value != "0");
+        let Some(value) = value else {
+            let sample = metrics::enabled() || SAMPLE_ALWAYS.load(Ordering::Relaxed);
+            return sample.then(|| Self {
+                interval: Duration::from_secs(1),
+                print: false,
+                output: None,
+            });
+        };
+        let interval = env::var("SRT_STATS_INTERVAL")
+            .ok()
+            .and_then(|ms| ms.parse().ok())
+            .map(Duration::from_millis)
+            .unwrap_or(Duration::from_secs(1));
+
+        let output = match value.as_str() {
+            "1" | "true" => None,
+            path => {
+                let format = if path.ends_with(".csv") {
+                    StatsFormat::Csv
+                } else {
+                    StatsFormat::JsonLines
+                };
+                Some((PathBuf::from(path), format))
+            }
+        };
+
+        Some(Self {
+            interval,
+            print: true,
+            output,
+        })
+    }
+}
+
+static SAMPLE_ALWAYS: AtomicBool = AtomicBool::new(false);
+
+/// Samples every socket opened from now on, even without `SRT_STATS` or metrics, so
+/// [`sessions`] can report statistics.
+pub fn sample_always() {
+    SAMPLE_ALWAYS.store(true, Ordering::Relaxed);
+}
+
+/// One statistics sample, with rates computed over the interval since the previous one.
+#[derive(Clone, Debug, Serialize)]
+pub struct StatsSample {
+    pub label: String,
+    pub unix_ms: u128,
+    pub elapsed_ms: u128,
+    pub tx_bytes: u64,
+    pub rx_bytes: u64,
+    pub tx_mbps: f64,
+    pub rx_mbps: f64,
+    pub tx_packets: u64,
+    pub rx_packets: u64,
+    pub tx_lost: u64,
+    pub rx_lost: u64,
+    pub tx_retransmitted: u64,
+    pub rx_retransmitted: u64,
+    pub tx_dropped: u64,
+    pub rx_dropped: u64,
+    pub tx_buffered_packets: u64,
+    pub rx_buffered_packets: u64,
+    pub tx_buffered_ms: u128,
+    pub rx_buffered_ms: u128,
+    /// Best round trip of the application's clock probes, SRT latency in both directions
+    /// included; `None` unless measured.
+    pub probe_rtt_ms: Option<f64>,
+}
+
+const CSV_HEADER: &str = "label,unix_ms,elapsed_ms,tx_bytes,rx_bytes,tx_mbps,rx_mbps,tx_packets,rx_packets,tx_lost,rx_lost,tx_retransmitted,rx_retransmitted,tx_dropped,rx_dropped,tx_buffered_packets,rx_buffered_packets,tx_buffered_ms,rx_buffered_ms,probe_rtt_ms";
+
+impl StatsSample {
+    fn new(label: &str, stats: &SocketStatistics, previous: Option<&SocketStatistics>) -> Self {
+        let mbps = |bytes: u64, previous_bytes: u64| {
+            let elapsed = stats
+                .elapsed_time
+                .saturating_sub(previous.map(|p| p.elapsed_time).unwrap_or_default());
+            if elapsed.is_zero() {
+                0.0
+            } else {
+                bytes.saturating_sub(previous_bytes) as f64 * 8.0 / elapsed.as_secs_f64() / 1e6
+            }
+        };
+
+        Self {
+            label: label.to_string(),
+            unix_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis(),
+            elapsed_ms: stats.elapsed_time.as_millis(),
+            tx_bytes: stats.tx_all_bytes,
+            rx_bytes: stats.rx_all_bytes,
+            tx_mbps: mbps(
+                stats.tx_all_bytes,
+                previous.map(|p| p.tx_all_bytes).unwrap_or(0),
+            ),
+            rx_mbps: mbps(
+                stats.rx_all_bytes,
+                previous.map(|p| p.rx_all_bytes).unwrap_or(0),
+            ),
+            tx_packets: stats.tx_data,
+            rx_packets: stats.rx_data,
+            tx_lost: stats.tx_loss_data,
+            rx_lost: stats.rx_loss_data,
+            tx_retransmitted: stats.tx_retransmit_data,
+            rx_retransmitted: stats.rx_retransmit_data,
+            tx_dropped: stats.tx_dropped_data,
+            rx_dropped: stats.rx_dropped_data,
+            tx_buffered_packets: stats.tx_buffered_data,
+            rx_buffered_packets: stats.rx_buffered_data,
+            tx_buffered_ms: stats.tx_buffered_time.as_millis(),
+            rx_buffered_ms: stats.rx_buffered_time.as_millis(),
+            probe_rtt_ms: None,
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{:.3},{:.3},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            self.label,
+            self.unix_ms,
+            self.elapsed_ms,
+            self.tx_bytes,
+            self.rx_bytes,
+            self.tx_mbps,
+            self.rx_mbps,
+            self.tx_packets,
+            self.rx_packets,
+            self.tx_lost,
+            self.rx_lost,
+            self.tx_retransmitted,
+            self.rx_retransmitted,
+            self.tx_dropped,
+            self.rx_dropped,
+            self.tx_buffered_packets,
+            self.rx_buffered_packets,
+            self.tx_buffered_ms,
+            self.rx_buffered_ms,
+            self.probe_rtt_ms
+                .map(|rtt| format!("{rtt:.3}"))
+                .unwrap_or_default(),
+        )
+    }
+}
+
+/// Per-socket series on the `/metrics` endpoint, labelled with the socket's session as well as its
+/// label: every `view` camera or `play` receiver shares a label, but each is a socket of its own.
+struct StatsMetrics {
+    label: String,
+    session: String,
+    /// Registered once a round trip was measured, so unmeasured sockets don't report zero.
+    probe_rtt_ms: Option<Gauge>,
+    tx_mbps: Gauge,
+    rx_mbps: Gauge,
+    tx_buffered_ms: Gauge,
+    rx_buffered_ms: Gauge,
+    tx_lost: Counter,
+    rx_lost: Counter,
+    tx_retransmitted: Counter,
+    rx_retransmitted: Counter,
+    tx_dropped: Counter,
+    rx_dropped: Counter,
+}
+
+impl StatsMetrics {
+    fn new(label: &str, session: u64) -> Self {
+        let session = session.to_string();
+        let labels = &[("socket", label), ("session", session.as_str())];
+        Self {
+            label: label.to_string(),
+            session: session.clone(),
+            probe_rtt_ms: None,
+            tx_mbps: metrics::gauge_with("srt_tx_mbps", "SRT send rate in Mb/s", labels),
+            rx_mbps: metrics::gauge_with("srt_rx_mbps", "SRT receive rate in Mb/s", labels),
+            tx_buffered_ms: metrics::gauge_with(
+                "srt_tx_buffered_ms",
+                "Time span of data in the SRT send buffer",
+                labels,
+            ),
+            rx_buffered_ms: metrics::gauge_with(
+                "srt_rx_buffered_ms",
+                "Time span of data in the SRT receive buffer",
+                labels,
+            ),
+            tx_lost: metrics::counter_with(
+                "srt_tx_lost_packets_total",
+                "Packets reported lost by the peer",
+                labels,
+            ),
+            rx_lost: metrics::counter_with(
+                "srt_rx_lost_packets_total",
+                "Packets detected lost on receive",
+                labels,
+            ),
+            tx_retransmitted: metrics::counter_with(
+                "srt_tx_retransmitted_packets_total",
+                "Packets retransmitted",
+                labels,
+            ),
+            rx_retransmitted: metrics::counter_with(
+                "srt_rx_retransmitted_packets_total",
+                "Retransmitted packets received",
+                labels,
+            ),
+            tx_dropped: metrics::counter_with(
+                "srt_tx_dropped_packets_total",
+                "Packets dropped by the sender as too late",
+                labels,
+            ),
+            rx_dropped: metrics::counter_with(
+                "srt_rx_dropped_packets_total",
+                "Packets dropped by the receiver as too late",
+                labels,
+            ),
+        }
+    }
+
+    fn update(&self, sample: &StatsSample) {
+        self.tx_mbps.set(sample.tx_mbps);
+        self.rx_mbps.set(sample.rx_mbps);
+        self.tx_buffered_ms.set(sample.tx_buffered_ms as f64);
+        self.rx_buffered_ms.set(sample.rx_buffered_ms as f64);
+        self.tx_lost.set_total(sample.tx_lost);
+        self.rx_lost.set_total(sample.rx_lost);
+        self.tx_retransmitted.set_total(sample.tx_retransmitted);
+        self.rx_retransmitted.set_total(sample.rx_retransmitted);
+        self.tx_dropped.set_total(sample.tx_dropped);
+        self.rx_dropped.set_total(sample.rx_dropped);
+    }
+
+    fn round_trip(&mut self, rtt: Duration) {
+        let gauge = self.probe_rtt_ms.get_or_insert_with(|| {
+            metrics::gauge_with(
+                "srt_probe_rtt_ms",
+                "Round trip of the clock probes, SRT latency in both directions included",
+                &[("socket", &self.label), ("session", &self.session)],
+            )
+        });
+        gauge.set(rtt.as_secs_f64() * 1000.0);
+    }
+
+    /// Zeroes the gauges of a closed socket; its counters keep their final totals.
+    fn close(&self) {
+        for gauge in [
+            &self.tx_mbps,
+            &self.rx_mbps,
+            &self.tx_buffered_ms,
+            &self.rx_buffered_ms,
+        ] {
+            gauge.set(0.0);
+        }
+    }
+}
+
+pub struct StatsReporter {
+    label: String,
+    config: StatsConfig,
+    writer: Option<(BufWriter<File>, StatsFormat)>,
+    /// Registered once a [`StatsSocket`] assigns the session.
+    metrics: Option<StatsMetrics>,
+    last: Option<(Instant, SocketStatistics)>,
+    latest: Option<StatsSample>,
+    round_trip: Option<Duration>,
+}
+
+impl StatsReporter {
+    pub fn new(label: impl Into<String>, config: StatsConfig) -> io::Result<Self> {
+        let writer = match &config.output {
+            Some((path, format)) => {
+                let is_new = !path.exists();
+                let mut writer =
+                    BufWriter::new(OpenOptions::new().create(true).append(true).open(path)?);
+                if is_new && *format == StatsFormat::Csv {
+                    writeln!(writer, "{CSV_HEADER}")?;
+                }
+                Some((writer, *format))
+            }
+            None => None,
+        };
+
+        let label = label.into();
+        Ok(Self {
+            metrics: None,
+            label,
+            config,
+            writer,
+            last: None,
+            latest: None,
+            round_trip: None,
+        })
+    }
+
+    /// Adds a round trip measured by the application to this and every later sample.
+    pub fn record_round_trip(&mut self, rtt: Duration) {
+        self.round_trip = Some(rtt);
+        if let Some(latest) = &mut self.latest {
+            latest.probe_rtt_ms = Some(rtt.as_secs_f64() * 1000.0);
+        }
+        if let Some(metrics) = &mut self.metrics {
+            metrics.round_trip(rtt);
+        }
+    }
+
+    /// The most recent sample reported, if any.
+    pub fn latest(&self) -> Option<&StatsSample> {
+        self.latest.as_ref()
+    }
+
+    /// Reports `stats` if at least one interval passed since the previous report.
+    pub fn record(&mut self, stats: &SocketStatistics) -> io::Result<()> {
+        let now = Instant::now();
+        if let Some((reported_at, _)) = &self.last
+            && now.duration_since(*reported_at) < self.config.interval
+        {
+            return Ok(());
+        }
+
+        let mut sample = StatsSample::new(&self.label, stats, self.last.as_ref().map(|(_, s)| s));
+        sample.probe_rtt_ms = self.round_trip.map(|rtt| rtt.as_secs_f64() * 1000.0);
+        if self.config.print {
+            info!(
+                socket = %sample.label,
+                tx_mbps = sample.tx_mbps,
+                rx_mbps = sample.rx_mbps,
+                tx_lost = sample.tx_lost,
+                rx_lost = sample.rx_lost,
+                tx_retransmitted = sample.tx_retransmitted,
+                rx_retransmitted = sample.rx_retransmitted,
+                tx_dropped = sample.tx_dropped,
+                rx_dropped = sample.rx_dropped,
+                tx_buffered_ms = sample.tx_buffered_ms,
+                rx_buffered_ms = sample.rx_buffered_ms,
+                probe_rtt_ms = sample.probe_rtt_ms,
+                "SRT statistics"
+            );
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.update(&sample);
+        }
+
+        if let Some((writer, format)) = self.writer.as_mut() {
+            match format {
+                StatsFormat::Csv => writeln!(writer, "{}", sample.to_csv())?,
+                StatsFormat::JsonLines => {
+                    serde_json::to_writer(&mut *writer, &sample)?;
+                    writeln!(writer)?;
+                }
+            }
+            writer.flush()?;
+        }
+
+        self.last = Some((now, stats.clone()));
+        self.latest = Some(sample);
+        Ok(())
+    }
+}
+
+/// An SRT connection open in this process.
+#[derive(Clone, Debug, Serialize)]
+pub struct Session {
+    pub id: u64,
+    pub label: String,
+    pub peer: SocketAddr,
+    pub stream_id: Option<String>,
+    pub uptime_ms: u128,
+    /// The latest sample, when statistics are sampled.
+    pub stats: Option<StatsSample>,
+}
+
+struct OpenSession {
+    label: String,
+    peer: SocketAddr,
+    stream_id: Option<String>,
+    opened: Instant,
+    stats: Option<StatsSample>,
+}
+
+static SESSIONS: Mutex<BTreeMap<u64, OpenSession>> = Mutex::new(BTreeMap::new());
+static NEXT_SESSION: AtomicU64 = AtomicU64::new(1);
+
+/// Every [`StatsSocket`] still open, oldest first.
+pub fn sessions() -> Vec<Session> {
+    let sessions = SESSIONS.lock().unwrap();
+    sessions
+        .iter()
+        .map(|(id, session)| Session {
+            id: *id,
+            label: session.label.clone(),
+            peer: session.peer,
+            stream_id: session.stream_id.clone(),
+            uptime_ms: session.opened.elapsed().as_millis(),
+            stats: session.stats.clone(),
+        })
+        .collect()
+}
+
+/// An [`SrtSocket`] that samples its statistics whenever it's polled for sending or receiving.
+///
+/// Drop-in for the socket itself: it forwards `Stream` and `Sink`, so `send_all`, `try_next`
+/// and `close` keep working unchanged.
+pub struct StatsSocket {
+    socket: SrtSocket,
+    reporter: Option<StatsReporter>,
+    session: u64,
+}
+
+impl StatsSocket {
+    /// Wraps `socket`, listed in [`sessions`] under `label` until dropped. The reporter's metrics
+    /// carry the session ID next to the label.
+    pub fn new(socket: SrtSocket, label: &str, mut reporter: Option<StatsReporter>) -> Self {
+        let session = NEXT_SESSION.fetch_add(1, Ordering::Relaxed);
+        let settings = socket.settings();
+        debug!(
+            session,
+            label,
+            peer = %settings.remote,
+            stream_id = settings.stream_id.as_deref(),
+            "SRT session opened"
+        );
+        SESSIONS.lock().unwrap().insert(
+            session,
+            OpenSession {
+                label: label.to_string(),
+                peer: settings.remote,
+                stream_id: settings.stream_id.clone(),
+                opened: Instant::now(),
+                stats: None,
+            },
+        );
+        if let Some(reporter) = &mut reporter {
+            reporter.metrics = Some(StatsMetrics::new(label, session));
+        }
+        Self {
+            socket,
+            reporter,
+            session,
+        }
+    }
+
+    /// Wraps `socket`, reporting under `label` when `SRT_STATS` or `SRT_METRICS_ADDR` is set.
+    pub fn from_env(socket: SrtSocket, label: &str) -> Self {
+        let reporter = StatsConfig::from_env().and_then(|config| {
+            StatsReporter::new(label, config)
+                .inspect_err(|e| warn!("Can't open the stats output: {e}"))
+                .ok()
+        });
+        Self::new(socket, label, reporter)
+    }
+
+    /// The ID this socket is listed under in [`sessions`] and labelled with in metrics.
+    pub fn session(&self) -> u64 {
+        self.session
+    }
+
+    pub fn get_ref(&self) -> &SrtSocket {
+        &self.socket
+    }
+
+    pub fn get_mut(&mut self) -> &mut SrtSocket {
+        &mut self.socket
+    }
+
+    pub fn reporter(&self) -> Option<&StatsReporter> {
+        self.reporter.as_ref()
+    }
+
+    /// Records a round trip the application measured over this socket, e.g. with
+    /// [`probe_clock`](crate::latency::probe_clock), in its samples and metrics.
+    pub fn record_round_trip(&mut self, rtt: Duration) {
+        let Some(reporter) = self.reporter.as_mut() else {
+            return;
+        };
+        reporter.record_round_trip(rtt);
+        if let Some(session) = SESSIONS.lock().unwrap().get_mut(&self.session) {
+            session.stats = reporter.latest().cloned();
+        }
+    }
+
+    fn poll_stats(&mut self, cx: &mut Context<'_>) {
+        let Some(reporter) = self.reporter.as_mut() else {
+            return;
+        };
+        let mut sampled = false;
+        while let Poll::Ready(Some(stats)) = self.socket.statistics().poll_next_unpin(cx) {
+            if let Err(e) = reporter.record(&stats) {
+                warn!("Failed to write a stats sample: {e}");
+            }
+            sampled = true;
+        }
+        if sampled && let Some(session) = SESSIONS.lock().unwrap().get_mut(&self.session) {
+            session.stats = reporter.latest().cloned();
+        }
+    }
+}
+
+impl Drop for StatsSocket {
+    fn drop(&mut self) {
+        SESSIONS.lock().unwrap().remove(&self.session);
+        if let Some(metrics) = self.reporter.as_ref().and_then(|r| r.metrics.as_ref()) {
+            metrics.close();
+        }
+        debug!(session = self.session, "SRT session closed");
+    }
+}
+
+impl Stream for StatsSocket {
+    type Item = io::Result<(Instant, Bytes)>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_stats(cx);
+        self.socket.poll_next_unpin(cx)
+    }
+}
+
+impl Sink<(Instant, Bytes)> for StatsSocket {
+    type Error = io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_stats(cx);
+        Pin::new(&mut self.socket).poll_ready(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: (Instant, Bytes)) -> io::Result<()> {
+        Pin::new(&mut self.socket).start_send(item)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_stats(cx);
+        Pin::new(&mut self.socket).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.socket).poll_close(cx)
+    }
+}
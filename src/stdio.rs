@@ -0,0 +1,129 @@
+//! `-` as a path means stdin for inputs and stdout for outputs.
+//! A `testsrc[:...]` input path generates test media instead, see [`crate::testsrc`].
+
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Stdin},
+    path::Path,
+};
+
+use ac_ffmpeg::format::io::IO;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tracing::error;
+
+use crate::testsrc::{self, Generator, TestSource};
+
+pub const STDIO_PATH: &str = "-";
+
+pub fn is_stdio(path: impl AsRef<Path>) -> bool {
+    path.as_ref() == Path::new(STDIO_PATH)
+}
+
+/// Parses `path` as a test source spec if it is one.
+pub fn test_source(path: impl AsRef<Path>) -> io::Result<Option<TestSource>> {
+    match path.as_ref().to_str() {
+        Some(spec) if testsrc::is_test_source(spec) => spec
+            .parse()
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e)),
+        _ => Ok(None),
+    }
+}
+
+/// Demuxer input: a regular file, or stdin and generated media which can only be read forward.
+pub enum Input {
+    File(File),
+    Stdin(Stdin),
+    Generated(Generator),
+}
+
+impl Input {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        if is_stdio(&path) {
+            Ok(Self::Stdin(io::stdin()))
+        } else if let Some(source) = test_source(&path)? {
+            Ok(Self::Generated(source.spawn()?))
+        } else {
+            Ok(Self::File(File::open(path)?))
+        }
+    }
+
+    pub fn is_seekable(&self) -> bool {
+        matches!(self, Self::File(_))
+    }
+
+    /// Wraps the input for FFmpeg, telling it whether seeking is possible.
+    pub fn into_io(self) -> IO<Self> {
+        if self.is_seekable() {
+            IO::from_seekable_read_stream(self)
+        } else {
+            IO::from_read_stream(self)
+        }
+    }
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::File(file) => file.read(buf),
+            Self::Stdin(stdin) => stdin.read(buf),
+            Self::Generated(pipe) => pipe.read(buf),
+        }
+    }
+}
+
+impl Seek for Input {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Self::File(file) => file.seek(pos),
+            Self::Stdin(_) | Self::Generated(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "stdin and generated input are not seekable",
+            )),
+        }
+    }
+}
+
+pub async fn open_async_input(
+    path: impl AsRef<Path>,
+) -> io::Result<Box<dyn AsyncRead + Unpin + Send>> {
+    if is_stdio(&path) {
+        Ok(Box::new(tokio::io::stdin()))
+    } else if let Some(source) = test_source(&path)? {
+        // The generator writes a blocking pipe, so copy it over on a blocking thread
+        let mut generator = source.spawn()?;
+        let (mut writer, reader) = tokio::io::duplex(64 * 1024);
+        let runtime = tokio::runtime::Handle::current();
+        tokio::task::spawn_blocking(move || {
+            let mut buf = vec![0; 64 * 1024];
+            loop {
+                match generator.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        if runtime.block_on(writer.write_all(&buf[..n])).is_err() {
+                            break;
+                        }
+                    }
+                    // The reader only sees the end, so say why
+                    Err(e) => {
+                        error!("{e}");
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(Box::new(reader))
+    } else {
+        Ok(Box::new(tokio::fs::File::open(path).await?))
+    }
+}
+
+pub async fn create_async_output(
+    path: impl AsRef<Path>,
+) -> io::Result<Box<dyn AsyncWrite + Unpin + Send>> {
+    if is_stdio(&path) {
+        Ok(Box::new(tokio::io::stdout()))
+    } else {
+        Ok(Box::new(tokio::fs::File::create(path).await?))
+    }
+}
@@ -0,0 +1,364 @@
+//! Built-in test source: colour bars with a burned-in timecode and a 1 kHz tone, muxed to mpegts.
+//!
+//! Selected with an input path of the form `testsrc[:WxH][@FPS][:SECONDS]`, e.g.
+//! `testsrc:640x360@25:30`. Defaults are 1280x720 at 30 fps for 10 s; `0` seconds runs forever.
+
+use std::{
+    f64::consts::TAU,
+    io::{self, PipeReader, Read, Write},
+    str::FromStr,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use ac_ffmpeg::{
+    codec::{
+        Encoder,
+        audio::{self, AudioEncoder, AudioFrameMut, ChannelLayout},
+        video::{self, VideoEncoder, VideoFrameMut},
+    },
+    format::{
+        io::IO,
+        muxer::{Muxer, OutputFormat},
+    },
+    time::{TimeBase, Timestamp},
+};
+use anyhow::{Context, bail};
+
+use crate::error::Error;
+
+pub const PREFIX: &str = "testsrc";
+
+const SAMPLE_RATE: u32 = 48_000;
+const TONE_HZ: f64 = 1000.0;
+/// -20 dBFS, the usual line-up level.
+const TONE_AMPLITUDE: f64 = 0.1 * i16::MAX as f64;
+
+/// 75% bars in BT.601 YUV: white, yellow, cyan, green, magenta, red, blue.
+const BARS: [(u8, u8, u8); 7] = [
+    (180, 128, 128),
+    (162, 44, 142),
+    (131, 156, 44),
+    (112, 72, 58),
+    (84, 184, 198),
+    (65, 100, 212),
+    (35, 212, 114),
+];
+
+/// 5x7 glyphs for `0`-`9` and `:`, one byte per row, bit 4 is the leftmost pixel.
+const GLYPHS: [[u8; 7]; 11] = [
+    [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e],
+    [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e],
+    [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f],
+    [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e],
+    [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02],
+    [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e],
+    [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e],
+    [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+    [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e],
+    [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c],
+    [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00],
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TestSource {
+    pub width: usize,
+    pub height: usize,
+    pub fps: u32,
+    /// `None` generates until the reader goes away.
+    pub duration: Option<Duration>,
+}
+
+impl Default for TestSource {
+    fn default() -> Self {
+        Self {
+            width: 1280,
+            height: 720,
+            fps: 30,
+            duration: Some(Duration::from_secs(10)),
+        }
+    }
+}
+
+pub fn is_test_source(path: &str) -> bool {
+    path == PREFIX || path.starts_with("testsrc:")
+}
+
+impl FromStr for TestSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        if !is_test_source(s) {
+            bail!("not a test source: {s:?}");
+        }
+        let mut source = Self::default();
+        let mut parts = s[PREFIX.len()..].split(':').skip(1);
+
+        if let Some(format) = parts.next().filter(|format| !format.is_empty()) {
+            let (size, fps) = match format.split_once('@') {
+                Some((size, fps)) => (size, Some(fps)),
+                None => (format, None),
+            };
+            if !size.is_empty() {
+                let (width, height) = size
+                    .split_once('x')
+                    .with_context(|| format!("expected WxH, got {size:?}"))?;
+                source.width = width.parse()?;
+                source.height = height.parse()?;
+            }
+            if let Some(fps) = fps {
+                source.fps = fps.parse()?;
+            }
+        }
+        if let Some(seconds) = parts.next() {
+            let seconds: u64 = seconds.parse()?;
+            source.duration = (seconds > 0).then(|| Duration::from_secs(seconds));
+        }
+
+        if source.width < 64
+            || source.height < 32
+            || !source.width.is_multiple_of(2)
+            || !source.height.is_multiple_of(2)
+        {
+            bail!("test source needs an even size of at least 64x32");
+        }
+        if source.fps == 0 {
+            bail!("test source needs a frame rate above 0");
+        }
+        Ok(source)
+    }
+}
+
+impl TestSource {
+    /// Generates on a background thread, handing out the mpegts through a pipe.
+    ///
+    /// Generation only runs as fast as the reader consumes, so a paced reader paces it too.
+    pub fn spawn(self) -> io::Result<Generator> {
+        let (pipe, writer) = io::pipe()?;
+        let thread = thread::Builder::new()
+            .name("testsrc".to_string())
+            .spawn(move || self.write_to(writer))?;
+        Ok(Generator {
+            pipe,
+            thread: Some(thread),
+        })
+    }
+
+    /// Encodes MPEG-2 video and MP2 audio into mpegts on `output`.
+    ///
+    /// Fails with [`Error::Codec`] or [`Error::Mux`], or when `output` can't be written.
+    pub fn write_to<W: Write>(&self, output: W) -> anyhow::Result<()> {
+        let video_error = || Error::Codec {
+            codec: "mpeg2video".to_string(),
+        };
+        let audio_error = || Error::Codec {
+            codec: "mp2".to_string(),
+        };
+        let mux_error = || Error::Mux {
+            output: PREFIX.to_string(),
+        };
+
+        let fps = self.fps as i32;
+        let video_time_base = TimeBase::new(1, fps);
+        let audio_time_base = TimeBase::new(1, SAMPLE_RATE as i32);
+
+        let pixel_format = video::frame::get_pixel_format("yuv420p");
+        let mut video_encoder = VideoEncoder::builder("mpeg2video")
+            .with_context(video_error)?
+            .pixel_format(pixel_format)
+            .width(self.width)
+            .height(self.height)
+            .time_base(video_time_base)
+            .bit_rate((self.width * self.height) as u64 * self.fps as u64 / 4)
+            // A keyframe every second, so segmenters and late joiners can start quickly
+            .set_option("g", self.fps)
+            .build()
+            .with_context(video_error)?;
+
+        let sample_format = audio::frame::get_sample_format("s16");
+        let channel_layout = ChannelLayout::from_channels(2).context("no stereo layout")?;
+        let mut audio_encoder = AudioEncoder::builder("mp2")
+            .with_context(audio_error)?
+            .sample_format(sample_format)
+            .sample_rate(SAMPLE_RATE)
+            .channel_layout(channel_layout.clone())
+            .bit_rate(192_000)
+            .build()
+            .with_context(audio_error)?;
+        let samples_per_frame = audio_encoder.samples_per_frame().unwrap_or(1152);
+
+        let mut muxer_builder = Muxer::builder();
+        muxer_builder
+            .add_stream(&video_encoder.codec_parameters().into())
+            .with_context(mux_error)?;
+        muxer_builder
+            .add_stream(&audio_encoder.codec_parameters().into())
+            .with_context(mux_error)?;
+        let mut muxer = muxer_builder
+            .build(
+                IO::from_write_stream(output),
+                OutputFormat::find_by_name("mpegts").context("mpegts muxer not available")?,
+            )
+            .with_context(mux_error)?;
+
+        let total_frames = self
+            .duration
+            .map(|duration| (duration.as_secs_f64() * self.fps as f64).round() as u64);
+        let mut samples_written = 0u64;
+        let mut frame_index = 0u64;
+
+        while total_frames.is_none_or(|total| frame_index < total) {
+            let frame = self
+                .video_frame(pixel_format, frame_index)
+                .with_time_base(video_time_base)
+                .with_pts(Timestamp::new(frame_index as i64, video_time_base))
+                .freeze();
+            video_encoder.push(frame).with_context(video_error)?;
+            while let Some(packet) = video_encoder.take().with_context(video_error)? {
+                muxer
+                    .push(packet.with_stream_index(0))
+                    .with_context(mux_error)?;
+            }
+            frame_index += 1;
+
+            // Keep the audio up to the end of this video frame
+            let video_end = frame_index * SAMPLE_RATE as u64 / self.fps as u64;
+            while samples_written < video_end {
+                let mut frame = AudioFrameMut::silence(
+                    &channel_layout,
+                    sample_format,
+                    SAMPLE_RATE,
+                    samples_per_frame,
+                );
+                fill_tone(frame.planes_mut()[0].data_mut(), samples_written);
+                let frame = frame
+                    .with_time_base(audio_time_base)
+                    .with_pts(Timestamp::new(samples_written as i64, audio_time_base))
+                    .freeze();
+                audio_encoder.push(frame).with_context(audio_error)?;
+                while let Some(packet) = audio_encoder.take().with_context(audio_error)? {
+                    muxer
+                        .push(packet.with_stream_index(1))
+                        .with_context(mux_error)?;
+                }
+                samples_written += samples_per_frame as u64;
+            }
+        }
+
+        video_encoder.flush().with_context(video_error)?;
+        while let Some(packet) = video_encoder.take().with_context(video_error)? {
+            muxer
+                .push(packet.with_stream_index(0))
+                .with_context(mux_error)?;
+        }
+        audio_encoder.flush().with_context(audio_error)?;
+        while let Some(packet) = audio_encoder.take().with_context(audio_error)? {
+            muxer
+                .push(packet.with_stream_index(1))
+                .with_context(mux_error)?;
+        }
+        muxer.flush().with_context(mux_error)?;
+        muxer.close().with_context(mux_error)?;
+        Ok(())
+    }
+
+    fn video_frame(&self, pixel_format: video::PixelFormat, index: u64) -> VideoFrameMut {
+        let (width, height) = (self.width, self.height);
+        let mut frame = VideoFrameMut::black(pixel_format, width, height);
+        let mut planes = frame.planes_mut();
+
+        // Bars over the full picture, chroma planes are subsampled 2x2
+        for (plane, (scale, pick)) in [(1, 0), (2, 1), (2, 2)].into_iter().enumerate() {
+            let stride = planes[plane].line_size();
+            let data = planes[plane].data_mut();
+            for y in 0..height / scale {
+                for x in 0..width / scale {
+                    let (luma, u, v) = BARS[x * scale * BARS.len() / width];
+                    data[y * stride + x] = [luma, u, v][pick];
+                }
+            }
+        }
+
+        // Timecode HH:MM:SS:FF, white on a black box in the lower third
+        let fps = self.fps as u64;
+        let seconds = index / fps;
+        let timecode = format!(
+            "{:02}:{:02}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60,
+            index % fps
+        );
+        let scale = (height / 90).max(1);
+        let text_width = timecode.len() * 6 * scale;
+        let left = width.saturating_sub(text_width) / 2;
+        let top = height * 2 / 3;
+        let stride = planes[0].line_size();
+        let luma = planes[0].data_mut();
+
+        let box_bottom = (top + 9 * scale).min(height);
+        let box_right = (left + text_width + scale).min(width);
+        for y in top.saturating_sub(scale)..box_bottom {
+            luma[y * stride + left.saturating_sub(scale)..y * stride + box_right].fill(16);
+        }
+        for (position, character) in timecode.chars().enumerate() {
+            let glyph = match character {
+                ':' => &GLYPHS[10],
+                digit => &GLYPHS[digit.to_digit(10).unwrap_or(0) as usize],
+            };
+            for (row, bits) in glyph.iter().enumerate() {
+                for column in 0..5 {
+                    if bits & (0x10 >> column) == 0 {
+                        continue;
+                    }
+                    let x0 = left + (position * 6 + column) * scale;
+                    let y0 = top + row * scale;
+                    for y in y0..(y0 + scale).min(height) {
+                        let start = y * stride + x0.min(width);
+                        let end = y * stride + (x0 + scale).min(width);
+                        luma[start..end].fill(235);
+                    }
+                }
+            }
+        }
+
+        drop(planes);
+        frame
+    }
+}
+
+/// The read end of a [`TestSource::spawn`]ed generator.
+///
+/// Ends like a file once everything was generated, or fails with the generator's error, so a
+/// broken source isn't mistaken for a complete one.
+pub struct Generator {
+    pipe: PipeReader,
+    thread: Option<JoinHandle<anyhow::Result<()>>>,
+}
+
+impl Read for Generator {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.pipe.read(buf)?;
+        if n == 0
+            && let Some(thread) = self.thread.take()
+        {
+            match thread.join() {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => return Err(io::Error::other(format!("test source failed: {e:#}"))),
+                Err(_) => return Err(io::Error::other("test source panicked")),
+            }
+        }
+        Ok(n)
+    }
+}
+
+/// Writes interleaved stereo s16 samples of the tone, continuing the phase at `first_sample`.
+fn fill_tone(data: &mut [u8], first_sample: u64) {
+    for (index, sample) in data.chunks_exact_mut(4).enumerate() {
+        let t = (first_sample + index as u64) as f64 / SAMPLE_RATE as f64;
+        let value = ((TAU * TONE_HZ * t).sin() * TONE_AMPLITUDE) as i16;
+        let bytes = value.to_le_bytes();
+        sample[..2].copy_from_slice(&bytes);
+        sample[2..].copy_from_slice(&bytes);
+    }
+}
@@ -0,0 +1,373 @@
+//! File transfer over SRT with a manifest, resume from an offset and SHA-256 verification.
+//!
+//! 1. The sender announces `Manifest { name, size, sha256 }`.
+//! 2. The receiver answers `Resume { offset }` with what it already has in `<name>.part`.
+//! 3. The sender streams `Chunk`s from there, paced to a bitrate, then repeats `EndOfStream`
+//!    until it gets an answer. A receiver that spots a gap asks again with `Resume`.
+//! 4. Once the file is complete the receiver checks the hash, renames it and answers `Verified`.
+//!
+//! A dropped connection leaves `<name>.part` behind and the next session picks up from its length.
+
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::{self, Read, SeekFrom},
+    path::Path,
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, bail};
+use bytes::Bytes;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use sha2::{Digest, Sha256};
+use tokio::{
+    fs::{self, OpenOptions},
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+    time::{sleep_until, timeout},
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    error::{EXIT_CHECKSUM_MISMATCH, EXIT_TRUNCATED},
+    protocol::Message,
+};
+
+pub const CHUNK_SIZE: usize = 64 * 1024;
+/// Default pacing in bits/s, well below what a loopback or LAN link sustains.
+pub const DEFAULT_BITRATE: u64 = 20_000_000;
+
+/// How long either side waits for the peer before treating the link as dropped.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often end-of-stream and gap requests are repeated while unanswered.
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Verified,
+    ChecksumMismatch,
+    /// The link dropped or went silent; the transfer can resume on the next connection.
+    Interrupted,
+}
+
+impl Outcome {
+    pub fn exit_code(&self) -> ExitCode {
+        match self {
+            Self::Verified => ExitCode::SUCCESS,
+            Self::ChecksumMismatch => ExitCode::from(EXIT_CHECKSUM_MISMATCH),
+            Self::Interrupted => ExitCode::from(EXIT_TRUNCATED),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Manifest {
+    pub name: String,
+    pub size: u64,
+    pub sha256: [u8; 32],
+}
+
+impl Manifest {
+    /// Hashes `path` on a blocking thread.
+    pub async fn for_file(path: &Path) -> anyhow::Result<Self> {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .with_context(|| format!("{} has no usable file name", path.display()))?
+            .to_string();
+        let size = fs::metadata(path).await?.len();
+        let owned = path.to_path_buf();
+        let sha256 = tokio::task::spawn_blocking(move || sha256_file(&owned)).await??;
+        Ok(Self { name, size, sha256 })
+    }
+
+    fn message(&self) -> Message {
+        Message::Manifest {
+            name: self.name.clone(),
+            size: self.size,
+            sha256: self.sha256,
+        }
+    }
+}
+
+pub fn sha256_file(path: &Path) -> io::Result<[u8; 32]> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; CHUNK_SIZE];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            return Ok(hasher.finalize().into());
+        }
+        hasher.update(&buf[..n]);
+    }
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut out, byte| {
+        let _ = write!(out, "{byte:02x}");
+        out
+    })
+}
+
+/// Sends `path` described by `manifest`, paced to `bitrate` bits/s, until the receiver's verdict.
+pub async fn send_file<S>(
+    socket: &mut S,
+    path: &Path,
+    manifest: &Manifest,
+    bitrate: u64,
+) -> anyhow::Result<Outcome>
+where
+    S: Stream<Item = io::Result<(Instant, Bytes)>>
+        + Sink<(Instant, Bytes), Error = io::Error>
+        + Unpin,
+{
+    if !send_message(socket, manifest.message()).await {
+        return Ok(Outcome::Interrupted);
+    }
+    let resume = wait_for(socket, |message| match message {
+        Message::Resume { offset } => Some(offset),
+        _ => None,
+    })
+    .await;
+    let Some(mut offset) = resume else {
+        return Ok(Outcome::Interrupted);
+    };
+    offset = offset.min(manifest.size);
+    info!(
+        "Sending {} ({} bytes) from offset {offset}",
+        manifest.name, manifest.size
+    );
+
+    let mut file = fs::File::open(path).await?;
+    file.seek(SeekFrom::Start(offset)).await?;
+    let mut buf = vec![0; CHUNK_SIZE];
+    let byte_interval = Duration::from_secs_f64(8.0 / bitrate.max(1) as f64);
+    let mut next_send = tokio::time::Instant::now();
+    // (first, last) end-of-stream sent since the last progress
+    let mut ending: Option<(Instant, Instant)> = None;
+
+    loop {
+        let wake = match ending {
+            _ if offset < manifest.size => next_send,
+            None => tokio::time::Instant::now(),
+            Some((_, last)) => (last + RETRY_INTERVAL).into(),
+        };
+
+        tokio::select! {
+            biased;
+            incoming = socket.next() => {
+                let bytes = match incoming {
+                    Some(Ok((_instant, bytes))) => bytes,
+                    Some(Err(e)) => {
+                        warn!("Receive failed: {e}");
+                        return Ok(Outcome::Interrupted);
+                    }
+                    None => return Ok(Outcome::Interrupted),
+                };
+                match Message::decode(bytes) {
+                    Ok(Message::Resume { offset: from }) => {
+                        info!("Receiver asked to resume from {from}");
+                        offset = from.min(manifest.size);
+                        file.seek(SeekFrom::Start(offset)).await?;
+                        ending = None;
+                    }
+                    Ok(Message::Verified { ok: true }) => return Ok(Outcome::Verified),
+                    Ok(Message::Verified { ok: false }) => return Ok(Outcome::ChecksumMismatch),
+                    Ok(_) => {}
+                    Err(e) => warn!("Invalid message: {e}"),
+                }
+            }
+            _ = sleep_until(wake) => {
+                if offset < manifest.size {
+                    let n = file.read(&mut buf).await?;
+                    if n == 0 {
+                        bail!("{} shrank while sending", path.display());
+                    }
+                    let chunk = Message::Chunk {
+                        offset,
+                        payload: Bytes::copy_from_slice(&buf[..n]),
+                    };
+                    if !send_message(socket, chunk).await {
+                        return Ok(Outcome::Interrupted);
+                    }
+                    offset += n as u64;
+                    next_send = next_send.max(tokio::time::Instant::now()) + byte_interval * n as u32;
+                } else {
+                    let now = Instant::now();
+                    let first = ending.map_or(now, |(first, _)| first);
+                    if now.duration_since(first) > RESPONSE_TIMEOUT {
+                        warn!("No verdict from the receiver");
+                        return Ok(Outcome::Interrupted);
+                    }
+                    let end = Message::EndOfStream {
+                        frames: manifest.size.div_ceil(CHUNK_SIZE as u64),
+                        bytes: manifest.size,
+                    };
+                    if !send_message(socket, end).await {
+                        return Ok(Outcome::Interrupted);
+                    }
+                    ending = Some((first, now));
+                }
+            }
+        }
+    }
+}
+
+/// Receives one file into `dir`, resuming a matching `<name>.part` left by an earlier session.
+pub async fn receive_file<S>(socket: &mut S, dir: &Path) -> anyhow::Result<Outcome>
+where
+    S: Stream<Item = io::Result<(Instant, Bytes)>>
+        + Sink<(Instant, Bytes), Error = io::Error>
+        + Unpin,
+{
+    let manifest = wait_for(socket, |message| match message {
+        Message::Manifest { name, size, sha256 } => Some(Manifest { name, size, sha256 }),
+        _ => None,
+    })
+    .await;
+    let Some(manifest) = manifest else {
+        return Ok(Outcome::Interrupted);
+    };
+
+    // Never let the sender pick a path outside `dir`
+    let name = Path::new(&manifest.name)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("invalid file name {:?}", manifest.name))?;
+    let final_path = dir.join(name);
+    let part_path = dir.join(format!("{name}.part"));
+    let hash_path = dir.join(format!("{name}.part.sha256"));
+    let expected = hex(&manifest.sha256);
+
+    // Only resume a partial download of the very same file
+    let mut written = match fs::read_to_string(&hash_path).await {
+        Ok(hash) if hash.trim() == expected => fs::metadata(&part_path)
+            .await
+            .map_or(0, |meta| meta.len().min(manifest.size)),
+        _ => {
+            fs::write(&hash_path, &expected).await?;
+            0
+        }
+    };
+    let mut file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&part_path)
+        .await?;
+    file.set_len(written).await?;
+    file.seek(SeekFrom::Start(written)).await?;
+
+    info!(
+        "Receiving {name} ({} bytes, sha256 {expected}) from offset {written}",
+        manifest.size
+    );
+    if !send_message(socket, Message::Resume { offset: written }).await {
+        return Ok(Outcome::Interrupted);
+    }
+    let mut requested = (written, Instant::now());
+
+    while written < manifest.size {
+        let bytes = match timeout(RESPONSE_TIMEOUT, socket.next()).await {
+            Ok(Some(Ok((_instant, bytes)))) => bytes,
+            Ok(Some(Err(e))) => {
+                warn!("Receive failed at {written}: {e}");
+                return Ok(Outcome::Interrupted);
+            }
+            Ok(None) | Err(_) => {
+                warn!("Link lost at {written}/{} bytes", manifest.size);
+                return Ok(Outcome::Interrupted);
+            }
+        };
+
+        let gap = match Message::decode(bytes) {
+            Ok(Message::Chunk { offset, payload }) if offset == written => {
+                file.write_all(&payload).await?;
+                written += payload.len() as u64;
+                false
+            }
+            // Ahead of us: something in between was lost
+            Ok(Message::Chunk { offset, .. }) => offset > written,
+            // The sender thinks it's done but we aren't
+            Ok(Message::EndOfStream { .. }) => true,
+            Ok(_) => false,
+            Err(e) => {
+                warn!("Invalid message: {e}");
+                false
+            }
+        };
+
+        let (at, when) = requested;
+        if gap && (at != written || when.elapsed() > RETRY_INTERVAL) {
+            warn!("Gap at {written}, asking the sender to resume");
+            if !send_message(socket, Message::Resume { offset: written }).await {
+                return Ok(Outcome::Interrupted);
+            }
+            requested = (written, Instant::now());
+        }
+    }
+
+    file.flush().await?;
+    file.sync_all().await?;
+    drop(file);
+
+    let hashed_path = part_path.clone();
+    let sha256 = tokio::task::spawn_blocking(move || sha256_file(&hashed_path)).await??;
+    let ok = sha256 == manifest.sha256;
+    if ok {
+        fs::rename(&part_path, &final_path).await?;
+        info!("{} verified", final_path.display());
+    } else {
+        // Start over next time instead of resuming corrupt data
+        fs::remove_file(&part_path).await?;
+        error!("Checksum mismatch for {name}, got {}", hex(&sha256));
+    }
+    fs::remove_file(&hash_path).await.ok();
+
+    if !send_message(socket, Message::Verified { ok }).await {
+        warn!("Couldn't report the verdict to the sender");
+    }
+    Ok(if ok {
+        Outcome::Verified
+    } else {
+        Outcome::ChecksumMismatch
+    })
+}
+
+/// Sends `message`, logging a failed link and reporting it as `false`.
+async fn send_message<S>(socket: &mut S, message: Message) -> bool
+where
+    S: Sink<(Instant, Bytes), Error = io::Error> + Unpin,
+{
+    match socket.send((Instant::now(), message.encode())).await {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("Send failed: {e}");
+            false
+        }
+    }
+}
+
+/// Waits up to [`RESPONSE_TIMEOUT`] for a message `select` accepts, skipping others.
+async fn wait_for<S, T>(socket: &mut S, mut select: impl FnMut(Message) -> Option<T>) -> Option<T>
+where
+    S: Stream<Item = io::Result<(Instant, Bytes)>> + Unpin,
+{
+    let deadline = tokio::time::Instant::now() + RESPONSE_TIMEOUT;
+    loop {
+        let next = tokio::time::timeout_at(deadline, socket.next()).await;
+        match next {
+            Ok(Some(Ok((_instant, bytes)))) => {
+                if let Some(found) = Message::decode(bytes).ok().and_then(&mut select) {
+                    return Some(found);
+                }
+            }
+            Ok(Some(Err(e))) => {
+                warn!("Receive failed: {e}");
+                return None;
+            }
+            Ok(None) | Err(_) => return None,
+        }
+    }
+}
@@ -0,0 +1,624 @@
+//! MPEG-TS packet helpers.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
+};
+
+use bytes::{Buf, Bytes, BytesMut};
+use serde::Serialize;
+
+pub const TS_PACKET_SIZE: usize = 188;
+pub const SYNC_BYTE: u8 = 0x47;
+/// Seven TS packets, the customary SRT/UDP payload that stays below a 1500-byte MTU.
+pub const SRT_PAYLOAD_SIZE: usize = 7 * TS_PACKET_SIZE;
+
+/// Re-chunks an arbitrary byte stream into SRT payloads made of whole TS packets.
+///
+/// Bytes that don't line up with a sync byte are skipped until the stream locks again.
+pub struct TsChunker {
+    buffer: BytesMut,
+    current: BytesMut,
+    payload_size: usize,
+}
+
+impl Default for TsChunker {
+    fn default() -> Self {
+        Self::new(SRT_PAYLOAD_SIZE)
+    }
+}
+
+impl TsChunker {
+    /// `payload_size` is rounded down to a whole number of TS packets (at least one).
+    pub fn new(payload_size: usize) -> Self {
+        let payload_size = (payload_size / TS_PACKET_SIZE).max(1) * TS_PACKET_SIZE;
+        Self {
+            buffer: BytesMut::new(),
+            current: BytesMut::with_capacity(payload_size),
+            payload_size,
+        }
+    }
+
+    /// Feeds `data` in and returns every payload completed by it.
+    pub fn push(&mut self, data: &[u8]) -> Vec<Bytes> {
+        self.buffer.extend_from_slice(data);
+
+        let mut payloads = Vec::new();
+        while self.buffer.len() >= TS_PACKET_SIZE {
+            if !self.is_synced() {
+                self.resync();
+                continue;
+            }
+
+            self.current
+                .extend_from_slice(&self.buffer.split_to(TS_PACKET_SIZE));
+            if self.current.len() == self.payload_size {
+                payloads.push(self.current.split().freeze());
+            }
+        }
+        payloads
+    }
+
+    /// Returns the whole packets still buffered, e.g. at end of stream.
+    pub fn flush(&mut self) -> Option<Bytes> {
+        (!self.current.is_empty()).then(|| self.current.split().freeze())
+    }
+
+    fn is_synced(&self) -> bool {
+        // Check the following packet too when it's there, a lone 0x47 in a payload isn't a lock
+        self.buffer[0] == SYNC_BYTE
+            && self
+                .buffer
+                .get(TS_PACKET_SIZE)
+                .is_none_or(|&byte| byte == SYNC_BYTE)
+    }
+
+    fn resync(&mut self) {
+        match self.buffer[1..].iter().position(|&byte| byte == SYNC_BYTE) {
+            Some(offset) => self.buffer.advance(offset + 1),
+            None => self.buffer.clear(),
+        }
+    }
+}
+
+/// 27 MHz system clock ticks per second.
+pub const PCR_HZ: u64 = 27_000_000;
+/// PCR base is 33 bits of 90 kHz ticks, times 300 plus the 9-bit extension.
+const PCR_WRAP: u64 = (1 << 33) * 300;
+
+pub fn pid(packet: &[u8]) -> u16 {
+    (u16::from(packet[1] & 0x1f) << 8) | u16::from(packet[2])
+}
+
+/// Reads the program clock reference of a TS packet, in 27 MHz ticks.
+pub fn pcr(packet: &[u8]) -> Option<u64> {
+    if packet.len() < 12 || packet[0] != SYNC_BYTE {
+        return None;
+    }
+
+    let has_adaptation = packet[3] & 0x20 != 0;
+    let adaptation_len = packet[4];
+    let has_pcr = packet[5] & 0x10 != 0;
+    if !has_adaptation || adaptation_len < 7 || !has_pcr {
+        return None;
+    }
+
+    let base = (u64::from(packet[6]) << 25)
+        | (u64::from(packet[7]) << 17)
+        | (u64::from(packet[8]) << 9)
+        | (u64::from(packet[9]) << 1)
+        | (u64::from(packet[10]) >> 7);
+    let extension = (u64::from(packet[10] & 0x01) << 8) | u64::from(packet[11]);
+    Some(base * 300 + extension)
+}
+
+/// Whether the adaptation field flags the packet as a random access point, which muxers (ffmpeg
+/// among them) set on the first packet of every keyframe.
+pub fn random_access(packet: &[u8]) -> bool {
+    packet.len() >= 6 && packet[3] & 0x20 != 0 && packet[4] > 0 && packet[5] & 0x40 != 0
+}
+
+/// PID of the null packets that pad a constant-rate TS; they carry no continuity counter.
+pub const NULL_PID: u16 = 0x1fff;
+
+/// The continuity counter of a packet carrying payload, and whether its adaptation field flags a
+/// discontinuity after which the counter may jump. `None` for packets without payload, which
+/// repeat the previous count.
+pub fn continuity(packet: &[u8]) -> Option<(u8, bool)> {
+    if packet.len() < 4 || packet[0] != SYNC_BYTE || packet[3] & 0x10 == 0 {
+        return None;
+    }
+    let discontinuity =
+        packet.len() >= 6 && packet[3] & 0x20 != 0 && packet[4] > 0 && packet[5] & 0x80 != 0;
+    Some((packet[3] & 0x0f, discontinuity))
+}
+
+pub enum Pacing {
+    /// Follow the stream's own program clock references.
+    Pcr,
+    /// Fixed rate in bits per second, for streams without usable PCRs.
+    Bitrate(u64),
+}
+
+/// Computes when each TS payload is due so a file plays out at its real rate.
+pub struct TsPacer {
+    pacing: Pacing,
+    start: Option<Instant>,
+    bytes_sent: u64,
+    pcr_pid: Option<u16>,
+    /// Last PCR seen and the instant it was due.
+    last_pcr: Option<(u64, Instant)>,
+    bytes_since_pcr: u64,
+    /// Bits per second measured between the last two PCRs, used to spread the payloads in between.
+    estimated_rate: Option<f64>,
+}
+
+impl TsPacer {
+    pub fn new(pacing: Pacing) -> Self {
+        Self {
+            pacing,
+            start: None,
+            bytes_sent: 0,
+            pcr_pid: None,
+            last_pcr: None,
+            bytes_since_pcr: 0,
+            estimated_rate: None,
+        }
+    }
+
+    /// Returns the instant `payload` should be sent at. Call once per payload, in order.
+    pub fn deadline(&mut self, payload: &[u8]) -> Instant {
+        let now = Instant::now();
+        let start = *self.start.get_or_insert(now);
+
+        let deadline = match self.pacing {
+            Pacing::Bitrate(bitrate) => {
+                start + Duration::from_secs_f64(self.bytes_sent as f64 * 8.0 / bitrate as f64)
+            }
+            Pacing::Pcr => self.pcr_deadline(payload, now),
+        };
+
+        self.bytes_sent += payload.len() as u64;
+        deadline
+    }
+
+    /// Pushes every later deadline back by `by`, e.g. after playback was paused.
+    pub fn delay(&mut self, by: Duration) {
+        if let Some(start) = &mut self.start {
+            *start += by;
+        }
+        if let Some((_, due)) = &mut self.last_pcr {
+            *due += by;
+        }
+    }
+
+    fn pcr_deadline(&mut self, payload: &[u8], now: Instant) -> Instant {
+        let pcr = payload
+            .chunks_exact(TS_PACKET_SIZE)
+            .filter(|packet| self.pcr_pid.is_none_or(|expected| pid(packet) == expected))
+            .find_map(|packet| pcr(packet).map(|pcr| (pid(packet), pcr)));
+
+        let Some((pcr_pid, pcr)) = pcr else {
+            // Interpolate from the last PCR at the measured rate, or send right away if unknown
+            let deadline = match (self.last_pcr, self.estimated_rate) {
+                (Some((_, due)), Some(rate)) => {
+                    due + Duration::from_secs_f64(self.bytes_since_pcr as f64 * 8.0 / rate)
+                }
+                _ => now,
+            };
+            self.bytes_since_pcr += payload.len() as u64;
+            return deadline;
+        };
+        self.pcr_pid = Some(pcr_pid);
+
+        let deadline = match self.last_pcr {
+            Some((last, due)) => {
+                let elapsed = (pcr + PCR_WRAP - last) % PCR_WRAP;
+                let elapsed = Duration::from_nanos(elapsed * 1000 / (PCR_HZ / 1_000_000));
+                if elapsed > Duration::from_secs(1) {
+                    // Discontinuity (splice, loop, corrupt PCR): restart the clock from here
+                    self.estimated_rate = None;
+                    now
+                } else {
+                    if !elapsed.is_zero() {
+                        self.estimated_rate =
+                            Some(self.bytes_since_pcr as f64 * 8.0 / elapsed.as_secs_f64());
+                    }
+                    due + elapsed
+                }
+            }
+            None => now,
+        };
+
+        self.last_pcr = Some((pcr, deadline));
+        self.bytes_since_pcr = payload.len() as u64;
+        deadline
+    }
+}
+
+pub const PAT_PID: u16 = 0x0000;
+const PAT_TABLE_ID: u8 = 0x00;
+const PMT_TABLE_ID: u8 = 0x02;
+const ISO_639_LANGUAGE_DESCRIPTOR: u8 = 0x0a;
+/// Section header up to and including `last_section_number`.
+const SECTION_HEADER_SIZE: usize = 8;
+const CRC_SIZE: usize = 4;
+
+/// An elementary stream listed in a program map table.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PmtStream {
+    pub pid: u16,
+    pub stream_type: u8,
+    pub stream_type_name: &'static str,
+    pub language: Option<String>,
+}
+
+/// A program from the PAT, filled in once its PMT was seen.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Program {
+    pub number: u16,
+    pub pmt_pid: u16,
+    pub pcr_pid: Option<u16>,
+    pub streams: Vec<PmtStream>,
+}
+
+/// Names the common ISO 13818-1 stream types.
+pub fn stream_type_name(stream_type: u8) -> &'static str {
+    match stream_type {
+        0x01 => "MPEG-1 video",
+        0x02 => "MPEG-2 video",
+        0x03 => "MPEG-1 audio",
+        0x04 => "MPEG-2 audio",
+        0x06 => "private PES",
+        0x0f => "AAC (ADTS)",
+        0x10 => "MPEG-4 video",
+        0x11 => "AAC (LATM)",
+        0x15 => "metadata",
+        0x1b => "H.264",
+        0x24 => "HEVC",
+        0x81 => "AC-3",
+        0x86 => "SCTE-35",
+        0x87 => "E-AC-3",
+        _ => "unknown",
+    }
+}
+
+fn is_video_stream_type(stream_type: u8) -> bool {
+    matches!(stream_type, 0x01 | 0x02 | 0x10 | 0x1b | 0x24)
+}
+
+/// Reads the language code of the first ISO 639 language descriptor in a descriptor loop.
+pub fn iso639_language(descriptors: &[u8]) -> Option<String> {
+    let mut rest = descriptors;
+    while let [tag, length, body @ ..] = rest {
+        let length = usize::from(*length);
+        let body = body.get(..length)?;
+        if *tag == ISO_639_LANGUAGE_DESCRIPTOR && length >= 3 {
+            let code = &body[..3];
+            return code
+                .iter()
+                .all(u8::is_ascii_alphabetic)
+                .then(|| String::from_utf8_lossy(code).to_ascii_lowercase());
+        }
+        rest = &rest[2 + length..];
+    }
+    None
+}
+
+/// Collects the programs (PAT and PMTs) of a TS byte stream and measures its mux rate from PCRs.
+///
+/// Bytes can be pushed in any size; the CRCs of the tables are not checked.
+pub struct ProgramScanner {
+    chunker: TsChunker,
+    /// Partial sections of the PSI PIDs, waiting for the packets that complete them.
+    sections: HashMap<u16, BytesMut>,
+    pmt_pids: HashMap<u16, u16>,
+    programs: BTreeMap<u16, Program>,
+    bytes: u64,
+    pcr_pid: Option<u16>,
+    /// First and last PCR of `pcr_pid`, with the stream offset they were seen at.
+    first_pcr: Option<(u64, u64)>,
+    last_pcr: Option<(u64, u64)>,
+}
+
+impl Default for ProgramScanner {
+    fn default() -> Self {
+        Self {
+            chunker: TsChunker::new(TS_PACKET_SIZE),
+            sections: HashMap::new(),
+            pmt_pids: HashMap::new(),
+            programs: BTreeMap::new(),
+            bytes: 0,
+            pcr_pid: None,
+            first_pcr: None,
+            last_pcr: None,
+        }
+    }
+}
+
+impl ProgramScanner {
+    pub fn push(&mut self, data: &[u8]) {
+        for packet in self.chunker.push(data) {
+            self.packet(&packet);
+            self.bytes += TS_PACKET_SIZE as u64;
+        }
+    }
+
+    /// Forgets partial packets, sections and PCRs, e.g. after the reader seeked.
+    pub fn discontinuity(&mut self) {
+        self.chunker = TsChunker::new(TS_PACKET_SIZE);
+        self.sections.clear();
+        self.first_pcr = None;
+        self.last_pcr = None;
+    }
+
+    /// Programs in program number order.
+    pub fn programs(&self) -> Vec<Program> {
+        self.programs.values().cloned().collect()
+    }
+
+    /// Whole TS packets scanned.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Whether `pid` carries the PAT or one of the PMTs it lists.
+    pub fn is_psi(&self, pid: u16) -> bool {
+        pid == PAT_PID || self.pmt_pids.contains_key(&pid)
+    }
+
+    /// Whether a PMT seen so far lists `pid` as a video stream.
+    pub fn is_video(&self, pid: u16) -> bool {
+        self.programs
+            .values()
+            .flat_map(|program| &program.streams)
+            .any(|stream| stream.pid == pid && is_video_stream_type(stream.stream_type))
+    }
+
+    /// Bits per second between the first and last PCR seen, including all TS overhead.
+    pub fn mux_rate(&self) -> Option<u64> {
+        let ((first, first_offset), (last, last_offset)) = (self.first_pcr?, self.last_pcr?);
+        let ticks = (last + PCR_WRAP - first) % PCR_WRAP;
+        (ticks > 0).then(|| (last_offset - first_offset) * 8 * PCR_HZ / ticks)
+    }
+
+    fn packet(&mut self, packet: &[u8]) {
+        let pid = pid(packet);
+
+        if let Some(pcr) = pcr(packet)
+            && *self.pcr_pid.get_or_insert(pid) == pid
+        {
+            self.first_pcr.get_or_insert((pcr, self.bytes));
+            self.last_pcr = Some((pcr, self.bytes));
+        }
+
+        if pid != PAT_PID && !self.pmt_pids.contains_key(&pid) {
+            return;
+        }
+        let Some(payload) = payload(packet) else {
+            return;
+        };
+
+        let unit_start = packet[1] & 0x40 != 0;
+        if unit_start {
+            let Some((&pointer, rest)) = payload.split_first() else {
+                return;
+            };
+            let pointer = usize::from(pointer).min(rest.len());
+            // The bytes before the pointer finish the previous section
+            if let Some(buffer) = self.sections.get_mut(&pid) {
+                buffer.extend_from_slice(&rest[..pointer]);
+                self.drain_sections(pid);
+            }
+            let buffer = self.sections.entry(pid).or_default();
+            buffer.clear();
+            buffer.extend_from_slice(&rest[pointer..]);
+        } else if let Some(buffer) = self.sections.get_mut(&pid) {
+            // Sections only start being collected at a unit start
+            buffer.extend_from_slice(payload);
+        } else {
+            return;
+        }
+        self.drain_sections(pid);
+    }
+
+    /// Parses every complete section buffered for `pid`.
+    fn drain_sections(&mut self, pid: u16) {
+        let mut complete = Vec::new();
+        if let Some(buffer) = self.sections.get_mut(&pid) {
+            // 0xff is stuffing after the last section in the packet
+            while buffer.len() >= 3 && buffer[0] != 0xff {
+                let length = ((usize::from(buffer[1] & 0x0f) << 8) | usize::from(buffer[2])) + 3;
+                if buffer.len() < length {
+                    break;
+                }
+                complete.push(buffer.split_to(length).freeze());
+            }
+            if buffer.first() == Some(&0xff) {
+                buffer.clear();
+            }
+        }
+        for section in complete {
+            self.section(pid, &section);
+        }
+    }
+
+    fn section(&mut self, pid: u16, section: &[u8]) {
+        let current = section.get(5).is_some_and(|byte| byte & 0x01 != 0);
+        if section.len() < SECTION_HEADER_SIZE + CRC_SIZE || !current {
+            return;
+        }
+        let body = &section[SECTION_HEADER_SIZE..section.len() - CRC_SIZE];
+
+        match section[0] {
+            PAT_TABLE_ID if pid == PAT_PID => {
+                for entry in body.chunks_exact(4) {
+                    let number = u16::from_be_bytes([entry[0], entry[1]]);
+                    let pmt_pid = u16::from_be_bytes([entry[2], entry[3]]) & 0x1fff;
+                    // Program 0 points at the network information table
+                    if number == 0 {
+                        continue;
+                    }
+                    self.pmt_pids.insert(pmt_pid, number);
+                    self.programs.entry(number).or_insert_with(|| Program {
+                        number,
+                        pmt_pid,
+                        pcr_pid: None,
+                        streams: Vec::new(),
+                    });
+                }
+            }
+            PMT_TABLE_ID => {
+                let number = u16::from_be_bytes([section[3], section[4]]);
+                if self.pmt_pids.get(&pid) != Some(&number) || body.len() < 4 {
+                    return;
+                }
+                let pcr_pid = u16::from_be_bytes([body[0], body[1]]) & 0x1fff;
+                let info_length = usize::from(u16::from_be_bytes([body[2], body[3]]) & 0x0fff);
+
+                let mut streams = Vec::new();
+                let mut rest = body.get(4 + info_length..).unwrap_or_default();
+                while let [
+                    stream_type,
+                    pid_high,
+                    pid_low,
+                    length_high,
+                    length_low,
+                    tail @ ..,
+                ] = rest
+                {
+                    let es_length =
+                        usize::from(u16::from_be_bytes([*length_high, *length_low]) & 0x0fff);
+                    let Some(descriptors) = tail.get(..es_length) else {
+                        break;
+                    };
+                    streams.push(PmtStream {
+                        pid: u16::from_be_bytes([*pid_high, *pid_low]) & 0x1fff,
+                        stream_type: *stream_type,
+                        stream_type_name: stream_type_name(*stream_type),
+                        language: iso639_language(descriptors),
+                    });
+                    rest = &tail[es_length..];
+                }
+
+                if let Some(program) = self.programs.get_mut(&number) {
+                    // 0x1fff means no PCR
+                    program.pcr_pid = (pcr_pid != 0x1fff).then_some(pcr_pid);
+                    program.streams = streams;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Default bound of a [`GopCache`], several seconds of HD video.
+pub const GOP_CACHE_MAX_BYTES: usize = 16 * 1024 * 1024;
+
+/// Keeps what a decoder joining a live TS midway needs to start right away: the latest PAT and
+/// PMTs, and every payload since the last video keyframe.
+///
+/// Keyframes are the video packets flagged as [random access](random_access) points. Without
+/// video only the tables are kept. A GOP outgrowing `max_bytes` is dropped, and collecting
+/// starts over at the next keyframe.
+pub struct GopCache {
+    scanner: ProgramScanner,
+    /// Latest packets of each PSI PID, from the start of its last section.
+    tables: BTreeMap<u16, BytesMut>,
+    /// Payloads from the last keyframe on; empty while none is being collected.
+    gop: Vec<Bytes>,
+    gop_bytes: usize,
+    max_bytes: usize,
+}
+
+impl Default for GopCache {
+    fn default() -> Self {
+        Self::new(GOP_CACHE_MAX_BYTES)
+    }
+}
+
+impl GopCache {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            scanner: ProgramScanner::default(),
+            tables: BTreeMap::new(),
+            gop: Vec::new(),
+            gop_bytes: 0,
+            max_bytes,
+        }
+    }
+
+    /// Feeds one payload of whole TS packets, in stream order.
+    pub fn push(&mut self, payload: &Bytes) {
+        self.scanner.push(payload);
+
+        let mut keyframe = None;
+        for (index, packet) in payload.chunks_exact(TS_PACKET_SIZE).enumerate() {
+            let pid = pid(packet);
+            if self.scanner.is_psi(pid) {
+                let table = self.tables.entry(pid).or_default();
+                if packet[1] & 0x40 != 0 {
+                    table.clear();
+                } else if table.is_empty() {
+                    // The tail of a section whose start was missed
+                    continue;
+                }
+                table.extend_from_slice(packet);
+            } else if self.scanner.is_video(pid) && random_access(packet) {
+                keyframe = Some(index * TS_PACKET_SIZE);
+            }
+        }
+
+        match keyframe {
+            Some(offset) => {
+                self.gop.clear();
+                self.gop_bytes = 0;
+                self.append(payload.slice(offset..));
+            }
+            None if !self.gop.is_empty() => self.append(payload.clone()),
+            None => {}
+        }
+    }
+
+    fn append(&mut self, payload: Bytes) {
+        self.gop_bytes += payload.len();
+        if self.gop_bytes > self.max_bytes {
+            self.gop.clear();
+            self.gop_bytes = 0;
+            return;
+        }
+        self.gop.push(payload);
+    }
+
+    /// The payloads to send a new subscriber before the live stream: the tables, PAT first, in
+    /// payloads of up to `payload_size` bytes, then the GOP so far. Empty until a PAT was seen.
+    ///
+    /// The GOP keeps the payloads it was fed, so `payload_size` should match their chunking.
+    pub fn snapshot(&self, payload_size: usize) -> Vec<Bytes> {
+        let tables = self.tables.values().flatten().copied().collect::<Vec<_>>();
+        tables
+            .chunks(payload_size)
+            .map(Bytes::copy_from_slice)
+            .chain(self.gop.iter().cloned())
+            .collect()
+    }
+
+    /// Bytes of the GOP currently cached.
+    pub fn gop_bytes(&self) -> usize {
+        self.gop_bytes
+    }
+}
+
+/// The payload of a TS packet, after the header and adaptation field.
+fn payload(packet: &[u8]) -> Option<&[u8]> {
+    let control = (packet[3] >> 4) & 0x03;
+    if control & 0x01 == 0 {
+        return None;
+    }
+    let start = if control & 0x02 != 0 {
+        5 + usize::from(packet[4])
+    } else {
+        4
+    };
+    packet.get(start..)
+}
@@ -0,0 +1,196 @@
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures::SinkExt;
+use rust_srt::{control, daemon::Supervisor, stats};
+use serde_json::Value;
+use srt_tokio::SrtSocket;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream, UdpSocket},
+    time::{sleep, timeout},
+};
+
+/// Held by every test, since `/sessions` lists the SRT sockets of the whole process.
+static SESSIONS: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Sends one request and returns the status code and the JSON body (`null` when empty).
+async fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, Value) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!(
+        "{method} {path} HTTP/1.1\r\nHost: {addr}\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let status = response[9..12].parse().unwrap();
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    let body = if body.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_str(body).unwrap()
+    };
+    (status, body)
+}
+
+fn payload() -> Vec<u8> {
+    (0..7u8)
+        .flat_map(|n| {
+            let mut packet = vec![n; 188];
+            packet[..4].copy_from_slice(&[0x47, 0x01, 0x00, 0x10]);
+            packet
+        })
+        .collect()
+}
+
+async fn recv(socket: &UdpSocket, wait: Duration) -> Option<Vec<u8>> {
+    let mut buf = vec![0; 2048];
+    let len = timeout(wait, socket.recv(&mut buf)).await.ok()?.unwrap();
+    Some(buf[..len].to_vec())
+}
+
+#[tokio::test]
+async fn routes_are_created_paused_and_deleted_over_http() {
+    let _sessions = SESSIONS.lock().await;
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let api = listener.local_addr().unwrap();
+    let supervisor = Arc::new(Mutex::new(Supervisor::default()));
+    tokio::spawn(control::serve_listener(listener, supervisor));
+
+    let (status, body) = request(api, "GET", "/sessions", "").await;
+    assert_eq!(status, 200);
+    assert_eq!(body, Value::Array(Vec::new()));
+
+    let input = UdpSocket::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let output = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let route = format!(
+        r#"{{"name": "feed", "input": "udp://{input}", "outputs": ["udp://{}"]}}"#,
+        output.local_addr().unwrap()
+    );
+    let (status, body) = request(api, "POST", "/routes", &route).await;
+    assert_eq!(status, 201, "{body}");
+    assert_eq!(body["name"], "feed");
+    assert_eq!(body["origin"], "api");
+
+    let (status, _) = request(api, "POST", "/routes", &route).await;
+    assert_eq!(status, 409);
+    let (status, body) = request(api, "POST", "/routes", r#"{"name": "broken"}"#).await;
+    assert_eq!(status, 400);
+    assert!(body["error"].as_str().unwrap().contains("input"), "{body}");
+
+    // Resend until the route has bound its input
+    let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    loop {
+        sender.send_to(&payload(), input).await.unwrap();
+        if recv(&output, Duration::from_millis(100)).await.is_some() {
+            break;
+        }
+    }
+    while recv(&output, Duration::from_millis(100)).await.is_some() {}
+    let (status, body) = request(api, "GET", "/routes/feed", "").await;
+    assert_eq!(status, 200);
+    assert_eq!(body["state"], "running");
+    assert!(body["messages"].as_u64().unwrap() >= 1, "{body}");
+
+    // A live input is dropped while paused
+    let (status, body) = request(api, "POST", "/routes/feed/pause", "").await;
+    assert_eq!(status, 200);
+    assert_eq!(body["state"], "paused");
+    sender.send_to(&payload(), input).await.unwrap();
+    assert_eq!(recv(&output, Duration::from_millis(300)).await, None);
+
+    let (status, body) = request(api, "POST", "/routes/feed/resume", "").await;
+    assert_eq!(status, 200);
+    assert_eq!(body["state"], "running");
+    sender.send_to(&payload(), input).await.unwrap();
+    assert_eq!(
+        recv(&output, Duration::from_secs(1)).await.unwrap(),
+        payload()
+    );
+
+    let (status, _) = request(api, "DELETE", "/routes/feed", "").await;
+    assert_eq!(status, 204);
+    let (status, body) = request(api, "GET", "/routes", "").await;
+    assert_eq!(status, 200);
+    assert_eq!(body, Value::Array(Vec::new()));
+    let (status, _) = request(api, "DELETE", "/routes/feed", "").await;
+    assert_eq!(status, 404);
+}
+
+#[tokio::test]
+async fn sessions_list_a_live_srt_route() {
+    let _sessions = SESSIONS.lock().await;
+    // As `daemon --api` does before opening any route
+    stats::sample_always();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let api = listener.local_addr().unwrap();
+    let supervisor = Arc::new(Mutex::new(Supervisor::default()));
+    tokio::spawn(control::serve_listener(listener, supervisor));
+
+    let output = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let route = format!(
+        r#"{{"name": "door", "input": "srt://:24394", "outputs": ["udp://{}"]}}"#,
+        output.local_addr().unwrap()
+    );
+    let (status, body) = request(api, "POST", "/routes", &route).await;
+    assert_eq!(status, 201, "{body}");
+
+    let mut camera = loop {
+        let call = SrtSocket::builder()
+            .latency(Duration::from_millis(120))
+            .call("127.0.0.1:24394", Some("door"))
+            .await;
+        match call {
+            Ok(socket) => break socket,
+            Err(_) => sleep(Duration::from_millis(100)).await,
+        }
+    };
+
+    // Keep the link busy until the route's socket has been sampled
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(10);
+    let session = loop {
+        camera
+            .send((Instant::now(), payload().into()))
+            .await
+            .unwrap();
+        let (status, body) = request(api, "GET", "/sessions", "").await;
+        assert_eq!(status, 200);
+        let session = body
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|session| session["stream_id"] == "door")
+            .cloned();
+        if let Some(session) = session.filter(|session| !session["stats"].is_null()) {
+            break session;
+        }
+        assert!(tokio::time::Instant::now() < deadline, "{body}");
+        sleep(Duration::from_millis(100)).await;
+    };
+
+    let peer: SocketAddr = session["peer"].as_str().unwrap().parse().unwrap();
+    assert!(peer.ip().is_loopback(), "{session}");
+    assert!(session["uptime_ms"].as_u64().unwrap() > 0, "{session}");
+    assert!(
+        session["stats"]["rx_bytes"].as_u64().unwrap() > 0,
+        "{session}"
+    );
+    assert_eq!(session["stats"]["label"], session["label"], "{session}");
+    assert_eq!(
+        recv(&output, Duration::from_secs(1)).await.unwrap(),
+        payload()
+    );
+
+    camera.close().await.unwrap();
+    let (status, _) = request(api, "DELETE", "/routes/door", "").await;
+    assert_eq!(status, 204);
+}
@@ -0,0 +1,113 @@
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use rust_srt::daemon::{Config, Supervisor};
+use tokio::{net::UdpSocket, time::timeout};
+
+const PACKETS_PER_PAYLOAD: usize = 7;
+
+fn config_file(test: &str, toml: &str) -> PathBuf {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{test}.toml"));
+    std::fs::write(&path, toml).unwrap();
+    path
+}
+
+fn route(name: &str, input: SocketAddr, outputs: &[SocketAddr]) -> String {
+    let outputs = outputs
+        .iter()
+        .map(|addr| format!("\"udp://{addr}\""))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("[[route]]\nname = \"{name}\"\ninput = \"udp://{input}\"\noutputs = [{outputs}]\n")
+}
+
+fn ts_packet(n: u8) -> Vec<u8> {
+    let mut packet = vec![n; 188];
+    packet[..4].copy_from_slice(&[0x47, 0x01, 0x00, 0x10]);
+    packet
+}
+
+async fn free_udp_port() -> SocketAddr {
+    UdpSocket::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+async fn recv(socket: &UdpSocket, wait: Duration) -> Option<Vec<u8>> {
+    let mut buf = vec![0; 2048];
+    let len = timeout(wait, socket.recv(&mut buf)).await.ok()?.unwrap();
+    Some(buf[..len].to_vec())
+}
+
+#[test]
+fn config_rejects_duplicate_and_empty_routes() {
+    let addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+    let twice = format!("{}{}", route("a", addr, &[addr]), route("a", addr, &[addr]));
+    let error = Config::load(&config_file("daemon_twice", &twice)).unwrap_err();
+    assert!(error.to_string().contains("defined twice"), "{error:#}");
+
+    let empty = route("a", addr, &[]);
+    let error = Config::load(&config_file("daemon_empty", &empty)).unwrap_err();
+    assert!(error.to_string().contains("no outputs"), "{error:#}");
+
+    let unknown = format!("{}restart = \"sometimes\"\n", route("a", addr, &[addr]));
+    assert!(Config::load(&config_file("daemon_unknown", &unknown)).is_err());
+}
+
+#[tokio::test]
+async fn routes_fan_out_and_survive_unrelated_reloads() {
+    let input = free_udp_port().await;
+    let first = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let second = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let outputs = [first.local_addr().unwrap(), second.local_addr().unwrap()];
+    let path = config_file("daemon_reload", &route("a", input, &outputs));
+
+    let mut supervisor = Supervisor::default();
+    supervisor.apply(Config::load(&path).unwrap());
+
+    // Resend whole payloads until the route has bound its input
+    let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let payload = (0..PACKETS_PER_PAYLOAD as u8)
+        .flat_map(ts_packet)
+        .collect::<Vec<_>>();
+    let received = loop {
+        sender.send_to(&payload, input).await.unwrap();
+        if let Some(received) = recv(&first, Duration::from_millis(100)).await {
+            break received;
+        }
+    };
+    assert_eq!(received, payload);
+    assert_eq!(
+        recv(&second, Duration::from_secs(1)).await.unwrap(),
+        payload
+    );
+    while recv(&first, Duration::from_millis(100)).await.is_some() {}
+    while recv(&second, Duration::from_millis(100)).await.is_some() {}
+
+    // Leave half a payload buffered in the route, then add another route next to it
+    for n in 0..3 {
+        sender.send_to(&ts_packet(n), input).await.unwrap();
+    }
+    let other = route("b", free_udp_port().await, &[free_udp_port().await]);
+    std::fs::write(&path, format!("{}{other}", route("a", input, &outputs))).unwrap();
+    supervisor.apply(Config::load(&path).unwrap());
+    assert_eq!(supervisor.route_names(), ["a", "b"]);
+
+    // A restarted route would have lost the buffered packets
+    for n in 3..PACKETS_PER_PAYLOAD as u8 {
+        sender.send_to(&ts_packet(n), input).await.unwrap();
+    }
+    assert_eq!(recv(&first, Duration::from_secs(1)).await.unwrap(), payload);
+
+    std::fs::write(&path, other).unwrap();
+    supervisor.apply(Config::load(&path).unwrap());
+    assert_eq!(supervisor.route_names(), ["b"]);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    sender.send_to(&payload, input).await.unwrap();
+    assert_eq!(recv(&first, Duration::from_millis(300)).await, None);
+}
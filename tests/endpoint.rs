@@ -0,0 +1,74 @@
+//! `srt://`, `udp://` and `tcp://` URLs as given on the command line and in daemon configs.
+
+use rust_srt::endpoint::{Endpoint, Scheme};
+
+#[test]
+fn hosts_ports_and_parameters_parse() {
+    let endpoint: Endpoint = "srt://relay.example:9000?latency=1000&streamid=door&listen"
+        .parse()
+        .unwrap();
+    assert_eq!(endpoint.scheme, Scheme::Srt);
+    assert_eq!(endpoint.host, "relay.example");
+    assert_eq!(endpoint.port, 9000);
+    assert_eq!(endpoint.param("latency"), Some("1000"));
+    assert_eq!(endpoint.param("streamid"), Some("door"));
+    assert_eq!(endpoint.param("listen"), Some(""));
+    assert_eq!(endpoint.param("passphrase"), None);
+    assert!(endpoint.is_listener());
+    assert_eq!(endpoint.to_string(), "srt://relay.example:9000");
+
+    let listener: Endpoint = "srt://:1234".parse().unwrap();
+    assert_eq!(listener.host, "");
+    assert!(listener.is_listener());
+
+    let udp: Endpoint = "udp://239.0.0.1:5000?iface=10.0.0.2".parse().unwrap();
+    assert_eq!(udp.scheme, Scheme::Udp);
+    assert_eq!(udp.param("iface"), Some("10.0.0.2"));
+    assert!(!udp.is_listener());
+
+    let tcp: Endpoint = "tcp://127.0.0.1:5000?mode=listener".parse().unwrap();
+    assert_eq!(tcp.scheme, Scheme::Tcp);
+    assert!(tcp.is_listener());
+}
+
+#[test]
+fn ipv6_hosts_keep_their_brackets_in_urls() {
+    let endpoint: Endpoint = "srt://[::1]:1234?latency=200".parse().unwrap();
+    assert_eq!(endpoint.host, "::1");
+    assert_eq!(endpoint.port, 1234);
+    assert_eq!(endpoint.param("latency"), Some("200"));
+    assert_eq!(endpoint.to_string(), "srt://[::1]:1234");
+    let reparsed: Endpoint = endpoint.to_string().parse().unwrap();
+    assert_eq!((reparsed.host, reparsed.port), ("::1".to_string(), 1234));
+
+    let multicast: Endpoint = "udp://[ff02::1234]:5000".parse().unwrap();
+    assert_eq!(multicast.host, "ff02::1234");
+    assert_eq!(multicast.to_string(), "udp://[ff02::1234]:5000");
+}
+
+#[test]
+fn malformed_urls_are_rejected() {
+    for invalid in [
+        "relay.example:9000",
+        "http://relay.example:9000",
+        "srt://relay.example",
+        "srt://relay.example:port",
+        "srt://relay.example:70000",
+        "srt://::1:1234",
+        "srt://[::1:1234",
+    ] {
+        assert!(invalid.parse::<Endpoint>().is_err(), "{invalid}");
+    }
+}
+
+#[tokio::test]
+async fn ipv6_listener_accepts_a_caller() {
+    let listener: Endpoint = "srt://[::1]:24391?listen".parse().unwrap();
+    let caller: Endpoint = "srt://[::1]:24391".parse().unwrap();
+    let (listened, called) = tokio::join!(listener.connect_srt("ipv6"), async {
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        caller.connect_srt("ipv6").await
+    });
+    listened.unwrap();
+    called.unwrap();
+}
@@ -0,0 +1,82 @@
+//! Failures carry their `rust_srt::error::Error` kind up to `main`, through any added context.
+
+use anyhow::Context;
+use bytes::Bytes;
+use rust_srt::{
+    endpoint::Endpoint,
+    error::{self, Error},
+    protocol::{self, Message},
+    shutdown,
+};
+
+#[test]
+fn garbage_is_a_protocol_error() {
+    let error = Message::decode(Bytes::from_static(b"hello")).unwrap_err();
+    assert!(
+        matches!(error.downcast_ref(), Some(Error::Protocol)),
+        "{error:#}"
+    );
+
+    // Decodes fine, but has no business on a data stream
+    let probe = Message::ClockProbe { sent_at_us: 1 }.encode();
+    let error = protocol::unwrap_data(probe).unwrap_err();
+    assert!(
+        matches!(error.downcast_ref(), Some(Error::Protocol)),
+        "{error:#}"
+    );
+}
+
+#[test]
+fn kind_survives_added_context() {
+    let error = Message::decode(Bytes::new())
+        .context("receiving")
+        .context("recv")
+        .unwrap_err();
+    assert!(
+        matches!(error.downcast_ref(), Some(Error::Protocol)),
+        "{error:#}"
+    );
+    assert_eq!(
+        format!("{error:#}"),
+        "recv: receiving: invalid message: empty message"
+    );
+}
+
+#[tokio::test]
+async fn refused_tcp_peer_is_a_connection_error() {
+    // Bind and drop to find a port nobody listens on
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let endpoint: Endpoint = format!("tcp://127.0.0.1:{port}").parse().unwrap();
+
+    let error = endpoint.open_input().await.err().unwrap();
+    assert!(
+        matches!(error.downcast_ref(), Some(Error::Connection { endpoint }) if endpoint.contains(&port.to_string())),
+        "{error:#}"
+    );
+}
+
+#[test]
+fn exit_codes_are_distinct_and_clear_of_clap() {
+    let mut codes = vec![
+        error::EXIT_CONNECTION,
+        error::EXIT_HANDSHAKE_REJECTED,
+        error::EXIT_PROTOCOL,
+        error::EXIT_DEMUX,
+        error::EXIT_MUX,
+        error::EXIT_CODEC,
+        error::EXIT_CAPTURE,
+        error::EXIT_TRUNCATED,
+        error::EXIT_CHECKSUM_MISMATCH,
+        shutdown::EXIT_INTERRUPTED,
+    ];
+    // 1 is anyhow's catch-all, 2 clap's usage error
+    assert!(codes.iter().all(|&code| code >= 4), "{codes:?}");
+    let count = codes.len();
+    codes.sort_unstable();
+    codes.dedup();
+    assert_eq!(codes.len(), count, "{codes:?}");
+}
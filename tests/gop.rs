@@ -0,0 +1,191 @@
+//! A late joiner of a live TS gets the tables and the GOP from the last video keyframe.
+
+use bytes::Bytes;
+use rust_srt::{
+    protocol,
+    ts::{GopCache, SRT_PAYLOAD_SIZE, SYNC_BYTE, TS_PACKET_SIZE, pid},
+};
+
+const PMT_PID: u16 = 0x1000;
+const VIDEO_PID: u16 = 0x100;
+const AUDIO_PID: u16 = 0x101;
+
+/// A packet on `pid`, its first payload byte `tag` so it can be told apart.
+fn packet(pid: u16, unit_start: bool, random_access: bool, tag: u8) -> Vec<u8> {
+    let mut packet = vec![0xff; TS_PACKET_SIZE];
+    packet[0] = SYNC_BYTE;
+    packet[1] = (u8::from(unit_start) << 6) | (pid >> 8) as u8;
+    packet[2] = pid as u8;
+    if random_access {
+        // Adaptation field and payload, a one-byte field holding just the flags
+        packet[3] = 0x30;
+        packet[4] = 1;
+        packet[5] = 0x40;
+        packet[6] = tag;
+    } else {
+        packet[3] = 0x10;
+        packet[4] = tag;
+    }
+    packet
+}
+
+/// A single-packet PSI section: header, `body`, and a CRC nobody checks.
+fn section(pid: u16, table_id: u8, number: u16, body: &[u8]) -> Vec<u8> {
+    let length = 5 + body.len() + 4;
+    let mut payload = vec![0, table_id, 0xb0, length as u8];
+    payload.extend_from_slice(&number.to_be_bytes());
+    payload.extend_from_slice(&[0xc1, 0, 0]);
+    payload.extend_from_slice(body);
+    payload.extend_from_slice(&[0; 4]);
+
+    let mut packet = packet(pid, true, false, 0);
+    packet[4..4 + payload.len()].copy_from_slice(&payload);
+    packet
+}
+
+fn pat() -> Vec<u8> {
+    pat_of(1)
+}
+
+/// A PAT listing `programs` programs, numbered from 1 with PMTs from [`PMT_PID`] on.
+fn pat_of(programs: u16) -> Vec<u8> {
+    let mut body = Vec::new();
+    for number in 1..=programs {
+        body.extend_from_slice(&number.to_be_bytes());
+        body.extend_from_slice(&(0xe000 | (PMT_PID + number - 1)).to_be_bytes());
+    }
+    section(0, 0x00, 1, &body)
+}
+
+fn pmt() -> Vec<u8> {
+    pmt_of(1)
+}
+
+/// The PMT of program `number`, with a video and an audio stream.
+fn pmt_of(number: u16) -> Vec<u8> {
+    let mut body = (0xe000 | VIDEO_PID).to_be_bytes().to_vec();
+    body.extend_from_slice(&[0xf0, 0]);
+    for (stream_type, pid) in [(0x1b, VIDEO_PID), (0x0f, AUDIO_PID)] {
+        body.push(stream_type);
+        body.extend_from_slice(&(0xe000 | pid).to_be_bytes());
+        body.extend_from_slice(&[0xf0, 0]);
+    }
+    section(PMT_PID + number - 1, 0x02, number, &body)
+}
+
+fn payload(packets: &[Vec<u8>]) -> Bytes {
+    packets.concat().into()
+}
+
+/// `(pid, tag)` of every packet in `payloads`.
+fn contents(payloads: &[Bytes]) -> Vec<(u16, u8)> {
+    payloads
+        .iter()
+        .flat_map(|payload| payload.chunks_exact(TS_PACKET_SIZE))
+        .map(|packet| {
+            let tag = if packet[3] & 0x20 != 0 {
+                packet[6]
+            } else {
+                packet[4]
+            };
+            (pid(packet), tag)
+        })
+        .collect()
+}
+
+#[test]
+fn snapshot_starts_with_the_tables_and_the_last_keyframe() {
+    let mut cache = GopCache::default();
+    assert!(cache.snapshot(SRT_PAYLOAD_SIZE).is_empty());
+
+    cache.push(&payload(&[pat(), pmt(), packet(VIDEO_PID, true, true, 1)]));
+    cache.push(&payload(&[packet(VIDEO_PID, false, false, 2)]));
+    // Audio frames are random access points too, but don't start a GOP
+    cache.push(&payload(&[
+        packet(AUDIO_PID, true, true, 3),
+        packet(VIDEO_PID, true, false, 4),
+        packet(VIDEO_PID, true, true, 5),
+        packet(AUDIO_PID, true, true, 6),
+    ]));
+    cache.push(&payload(&[packet(VIDEO_PID, true, false, 7)]));
+
+    let snapshot = cache.snapshot(SRT_PAYLOAD_SIZE);
+    assert_eq!(snapshot[0].len(), 2 * TS_PACKET_SIZE);
+    assert_eq!(
+        contents(&snapshot),
+        [
+            (0, 0),
+            (PMT_PID, 0),
+            (VIDEO_PID, 5),
+            (AUDIO_PID, 6),
+            (VIDEO_PID, 7),
+        ]
+    );
+}
+
+#[test]
+fn without_a_keyframe_only_the_tables_are_cached() {
+    let mut cache = GopCache::default();
+    cache.push(&payload(&[pat(), pmt()]));
+    cache.push(&payload(&[
+        packet(VIDEO_PID, true, false, 1),
+        packet(AUDIO_PID, true, true, 2),
+    ]));
+    assert_eq!(
+        contents(&cache.snapshot(SRT_PAYLOAD_SIZE)),
+        [(0, 0), (PMT_PID, 0)]
+    );
+    assert_eq!(cache.gop_bytes(), 0);
+}
+
+#[test]
+fn oversized_gop_is_dropped_until_the_next_keyframe() {
+    let mut cache = GopCache::new(3 * TS_PACKET_SIZE);
+    cache.push(&payload(&[pat(), pmt(), packet(VIDEO_PID, true, true, 1)]));
+    for tag in 2..5 {
+        cache.push(&payload(&[packet(VIDEO_PID, false, false, tag)]));
+    }
+    assert_eq!(
+        contents(&cache.snapshot(SRT_PAYLOAD_SIZE)),
+        [(0, 0), (PMT_PID, 0)]
+    );
+
+    // Not picked up again mid-GOP
+    cache.push(&payload(&[packet(VIDEO_PID, false, false, 5)]));
+    assert_eq!(cache.gop_bytes(), 0);
+
+    cache.push(&payload(&[packet(VIDEO_PID, true, true, 6)]));
+    assert_eq!(
+        contents(&cache.snapshot(SRT_PAYLOAD_SIZE)),
+        [(0, 0), (PMT_PID, 0), (VIDEO_PID, 6)]
+    );
+}
+
+#[test]
+fn tables_fit_numbered_messages() {
+    // Eight table packets: more than the six a numbered message carries behind its header
+    let programs = 7;
+    let mut tables = vec![pat_of(programs)];
+    tables.extend((1..=programs).map(pmt_of));
+    let mut cache = GopCache::default();
+    cache.push(&payload(&tables));
+
+    let payload_size = protocol::ts_payload_size(true);
+    let snapshot = cache.snapshot(payload_size);
+    let sizes = snapshot.iter().map(Bytes::len).collect::<Vec<_>>();
+    assert_eq!(sizes, [6 * TS_PACKET_SIZE, 2 * TS_PACKET_SIZE]);
+    let pids = contents(&snapshot)
+        .into_iter()
+        .map(|(pid, _)| pid)
+        .collect::<Vec<_>>();
+    let expected = [0].into_iter().chain((0..programs).map(|n| PMT_PID + n));
+    assert_eq!(pids, expected.collect::<Vec<_>>());
+
+    // Raw TS still takes seven a message
+    let sizes = cache
+        .snapshot(SRT_PAYLOAD_SIZE)
+        .iter()
+        .map(Bytes::len)
+        .collect::<Vec<_>>();
+    assert_eq!(sizes, [7 * TS_PACKET_SIZE, TS_PACKET_SIZE]);
+}
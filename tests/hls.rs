@@ -0,0 +1,162 @@
+//! The test source segmented into a rolling HLS playlist, fetched over HTTP like a player would.
+
+use std::{
+    fs::File,
+    io::{Cursor, Read},
+    path::Path,
+    time::Duration,
+};
+
+use ac_ffmpeg::format::{
+    demuxer::{Demuxer, DemuxerWithStreamInfo},
+    io::IO,
+};
+use rust_srt::{
+    hls::{HlsConfig, HlsPackager},
+    http,
+    testsrc::TestSource,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::sleep,
+};
+
+const ADDR: &str = "127.0.0.1:24393";
+
+async fn get(path: &str) -> (String, Vec<u8>) {
+    let mut stream = TcpStream::connect(ADDR).await.unwrap();
+    let request = format!("GET {path} HTTP/1.1\r\nHost: {ADDR}\r\n\r\n");
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    let split = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+    let head = String::from_utf8(response[..split].to_vec()).unwrap();
+    (head, response[split + 4..].to_vec())
+}
+
+fn demuxer<T: Read>(input: T) -> DemuxerWithStreamInfo<T> {
+    Demuxer::builder()
+        .build(IO::from_read_stream(input))
+        .unwrap()
+        .find_stream_info(None)
+        .map_err(|(_, e)| e)
+        .unwrap()
+}
+
+/// `#EXT-X-<tag>:<value>` from a playlist.
+fn tag<'a>(playlist: &'a str, tag: &str) -> &'a str {
+    playlist
+        .lines()
+        .find_map(|line| line.strip_prefix(tag)?.strip_prefix(':'))
+        .unwrap_or_else(|| panic!("{tag} missing from {playlist}"))
+}
+
+fn media_sequence(playlist: &str) -> u64 {
+    tag(playlist, "#EXT-X-MEDIA-SEQUENCE").parse().unwrap()
+}
+
+/// The `#EXTINF` duration and URI of every segment in a playlist.
+fn segments(playlist: &str) -> Vec<(f64, String)> {
+    let mut lines = playlist.lines();
+    let mut segments = Vec::new();
+    while let Some(line) = lines.next() {
+        if let Some(duration) = line.strip_prefix("#EXTINF:") {
+            let duration = duration.trim_end_matches(',').parse().unwrap();
+            segments.push((duration, lines.next().unwrap().to_string()));
+        }
+    }
+    segments
+}
+
+#[tokio::test]
+async fn test_source_segments_into_a_rolling_playlist() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("hls");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("input.ts");
+    let output = dir.join("out");
+    let source: TestSource = "testsrc:160x120@25:10".parse().unwrap();
+    source.write_to(File::create(&input).unwrap()).unwrap();
+
+    // Keyframes every second, so every one is a cut point
+    let mut config = HlsConfig::new(&output);
+    config.target_duration = Duration::from_secs(1);
+    config.playlist_size = 3;
+    let playlist_path = output.join(&config.playlist_name);
+
+    let mut input = demuxer(File::open(&input).unwrap());
+    let streams = input
+        .streams()
+        .iter()
+        .map(|stream| stream.codec_parameters())
+        .collect();
+    let mut packager = HlsPackager::new(config, streams).unwrap();
+    let mut sequences = Vec::new();
+    while let Some(packet) = input.take().unwrap() {
+        packager.push(packet).unwrap();
+        if let Ok(playlist) = std::fs::read_to_string(&playlist_path) {
+            let sequence = media_sequence(&playlist);
+            if sequences.last() != Some(&sequence) {
+                sequences.push(sequence);
+            }
+            assert!(segments(&playlist).len() <= 3, "{playlist}");
+            // Fixed for the life of the playlist, as RFC 8216 requires
+            assert_eq!(tag(&playlist, "#EXT-X-TARGETDURATION"), "1", "{playlist}");
+        }
+    }
+    packager.finish().unwrap();
+
+    // Older segments roll off the front one at a time
+    assert!(sequences.len() > 3, "{sequences:?}");
+    assert!(
+        sequences.windows(2).all(|w| w[1] == w[0] + 1),
+        "{sequences:?}"
+    );
+
+    tokio::spawn(http::serve_dir(ADDR, output.clone()));
+    sleep(Duration::from_millis(100)).await;
+
+    let (head, body) = get("/index.m3u8").await;
+    assert!(head.starts_with("HTTP/1.1 200 OK"), "{head}");
+    let playlist = String::from_utf8(body).unwrap();
+    assert!(playlist.starts_with("#EXTM3U\n"), "{playlist}");
+    assert!(playlist.ends_with("#EXT-X-ENDLIST\n"), "{playlist}");
+    assert!(media_sequence(&playlist) >= *sequences.last().unwrap());
+
+    let segments = segments(&playlist);
+    assert_eq!(segments.len(), 3, "{playlist}");
+    let target: f64 = tag(&playlist, "#EXT-X-TARGETDURATION").parse().unwrap();
+    let longest = segments
+        .iter()
+        .map(|(duration, _)| *duration)
+        .fold(0.0, f64::max);
+    assert!(target >= longest.round(), "{playlist}");
+
+    // Every segment decodes on its own, starting at a video keyframe
+    for (_, uri) in &segments {
+        let (head, body) = get(&format!("/{uri}")).await;
+        assert!(head.starts_with("HTTP/1.1 200 OK"), "{uri}: {head}");
+        let mut segment = demuxer(Cursor::new(body));
+        let video = segment
+            .streams()
+            .iter()
+            .position(|stream| stream.codec_parameters().is_video_codec())
+            .unwrap();
+        let first = loop {
+            let packet = segment.take().unwrap().expect("segment without video");
+            if packet.stream_index() == video {
+                break packet;
+            }
+        };
+        assert!(first.is_key(), "{uri} starts between keyframes");
+    }
+
+    // The latest to roll off stay a while for players holding an older playlist, then go
+    let sequence = media_sequence(&playlist);
+    let (head, _) = get(&format!("/segment{}.ts", sequence - 1)).await;
+    assert!(head.starts_with("HTTP/1.1 200 OK"), "{head}");
+    let (head, _) = get("/segment0.ts").await;
+    assert!(head.starts_with("HTTP/1.1 404"), "{head}");
+}
@@ -0,0 +1,178 @@
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use rust_srt::impair::{ImpairProxy, Impairment, ProxyConfig};
+use tokio::{net::UdpSocket, time::timeout};
+
+/// Sends `count` numbered datagrams through a proxy with `impairment` and returns the numbers
+/// that reached the upstream socket, in arrival order.
+async fn run(impairment: Impairment, seed: u64, count: u32) -> (Vec<u32>, ImpairProxy) {
+    let upstream = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let config = ProxyConfig::new(
+        "127.0.0.1:0".parse().unwrap(),
+        upstream.local_addr().unwrap(),
+    )
+    .both(impairment)
+    .seed(seed);
+    let proxy = ImpairProxy::start(config).await.unwrap();
+
+    let caller = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    for n in 0..count {
+        caller
+            .send_to(&n.to_be_bytes(), proxy.local_addr())
+            .await
+            .unwrap();
+    }
+
+    let mut arrived = Vec::new();
+    let mut buf = [0; 16];
+    while let Ok(Ok(len)) = timeout(Duration::from_millis(300), upstream.recv(&mut buf)).await {
+        arrived.push(u32::from_be_bytes(buf[..len].try_into().unwrap()));
+    }
+    (arrived, proxy)
+}
+
+#[tokio::test]
+async fn seeded_loss_and_duplication_are_reproducible() {
+    let impairment = Impairment {
+        loss: 0.2,
+        duplicate: 0.1,
+        ..Impairment::default()
+    };
+    let (first, proxy) = run(impairment, 7, 500).await;
+    let stats = proxy.forward_stats();
+    assert_eq!(stats.received, 500);
+    assert!(stats.lost > 50 && stats.lost < 150, "{stats:?}");
+    assert!(stats.duplicated > 10, "{stats:?}");
+    assert_eq!(first.len() as u64, stats.sent);
+    assert_eq!(stats.sent, 500 - stats.lost + stats.duplicated);
+
+    let (second, _proxy) = run(impairment, 7, 500).await;
+    assert_eq!(first, second);
+
+    let (other_seed, _proxy) = run(impairment, 8, 500).await;
+    assert_ne!(first, other_seed);
+}
+
+#[tokio::test]
+async fn delay_holds_packets_back_and_jitter_reorders() {
+    let start = Instant::now();
+    let delayed = Impairment {
+        delay: Duration::from_millis(100),
+        ..Impairment::default()
+    };
+    let (arrived, _proxy) = run(delayed, 1, 1).await;
+    assert_eq!(arrived, [0]);
+    assert!(start.elapsed() >= Duration::from_millis(100));
+
+    let jittery = Impairment {
+        delay: Duration::from_millis(20),
+        jitter: Duration::from_millis(20),
+        ..Impairment::default()
+    };
+    let (arrived, _proxy) = run(jittery, 1, 200).await;
+    assert_eq!(arrived.len(), 200);
+    assert!(arrived.windows(2).any(|pair| pair[0] > pair[1]));
+}
+
+#[tokio::test]
+async fn replies_go_back_to_the_caller() {
+    let upstream = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let config = ProxyConfig::new(
+        "127.0.0.1:0".parse().unwrap(),
+        upstream.local_addr().unwrap(),
+    );
+    let proxy = ImpairProxy::start(config).await.unwrap();
+
+    let caller = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    caller.send_to(b"ping", proxy.local_addr()).await.unwrap();
+
+    let mut buf = [0; 16];
+    let (len, from) = upstream.recv_from(&mut buf).await.unwrap();
+    assert_eq!(&buf[..len], b"ping");
+    upstream.send_to(b"pong", from).await.unwrap();
+
+    let (len, from) = timeout(Duration::from_secs(1), caller.recv_from(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(&buf[..len], b"pong");
+    assert_eq!(from, proxy.local_addr());
+    assert_eq!(proxy.backward_stats().sent, 1);
+}
+
+const BURST_SIZE: usize = 1000;
+
+/// Sends `count` datagrams of [`BURST_SIZE`] bytes as fast as possible and returns when each one
+/// that made it through arrived at `to`, along with the address they came from.
+async fn burst(
+    from: &UdpSocket,
+    target: SocketAddr,
+    to: &UdpSocket,
+    count: usize,
+) -> (Vec<Instant>, SocketAddr) {
+    for n in 0..count {
+        from.send_to(&[0; BURST_SIZE], target).await.unwrap();
+        // Let the proxy catch up now and then, so the socket buffer doesn't overflow first
+        if n % 10 == 9 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+    let mut arrivals = Vec::new();
+    let mut sender = None;
+    let mut buf = [0; 2 * BURST_SIZE];
+    while let Ok(Ok((len, from))) =
+        timeout(Duration::from_millis(300), to.recv_from(&mut buf)).await
+    {
+        assert_eq!(len, BURST_SIZE);
+        arrivals.push(Instant::now());
+        sender = Some(from);
+    }
+    (arrivals, sender.expect("nothing arrived"))
+}
+
+/// Bits per second between the first and the last arrival.
+fn throughput(arrivals: &[Instant]) -> f64 {
+    let elapsed = arrivals.last().unwrap().duration_since(arrivals[0]);
+    (arrivals.len() - 1) as f64 * BURST_SIZE as f64 * 8.0 / elapsed.as_secs_f64()
+}
+
+#[tokio::test]
+async fn rate_cap_paces_packets_and_overflows_its_queue() {
+    // 100 packets/s, 20 of them queued at most
+    let capped = Impairment {
+        rate: Some(800_000),
+        queue: Duration::from_millis(200),
+        ..Impairment::default()
+    };
+    let upstream = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let config = ProxyConfig::new(
+        "127.0.0.1:0".parse().unwrap(),
+        upstream.local_addr().unwrap(),
+    )
+    .both(capped);
+    let proxy = ImpairProxy::start(config).await.unwrap();
+    let caller = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+    // Five times what the link carries in its queue time
+    let (arrivals, proxy_upstream) = burst(&caller, proxy.local_addr(), &upstream, 100).await;
+    let stats = proxy.forward_stats();
+    assert_eq!(stats.received, 100);
+    assert_eq!(stats.sent, arrivals.len() as u64);
+    assert_eq!(stats.sent + stats.overflowed, 100, "{stats:?}");
+    assert!((20..=30).contains(&stats.sent), "{stats:?}");
+    let rate = throughput(&arrivals);
+    assert!((640_000.0..=960_000.0).contains(&rate), "{rate} b/s");
+
+    // The way back is capped the same, once the proxy knows where the caller is
+    let (arrivals, _) = burst(&upstream, proxy_upstream, &caller, 100).await;
+    let stats = proxy.backward_stats();
+    assert_eq!(stats.received, 100);
+    assert_eq!(stats.sent, arrivals.len() as u64);
+    assert_eq!(stats.sent + stats.overflowed, 100, "{stats:?}");
+    assert!((20..=30).contains(&stats.sent), "{stats:?}");
+    let rate = throughput(&arrivals);
+    assert!((640_000.0..=960_000.0).contains(&rate), "{rate} b/s");
+}
@@ -0,0 +1,845 @@
+//! End-to-end runs over localhost: real binaries (or the library pieces behind them) on both ends.
+//!
+//! The binaries use fixed ports, so every test holds `serial()` for its whole run.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, ChildStdout, Command, ExitStatus, Stdio},
+    sync::{
+        Mutex, MutexGuard, PoisonError,
+        mpsc::{self, Receiver, RecvTimeoutError},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use futures::{SinkExt, TryStreamExt};
+use opencv::{
+    core::{self, CV_8UC3, Mat, Scalar, Vector},
+    imgcodecs,
+    prelude::*,
+};
+use rust_srt::{
+    error, latency,
+    probe::{self, CodecType},
+    protocol::{self, CLOCK_PROBES, Message, now_us},
+    sequence::{Arrival, SequenceTracker},
+    shutdown,
+    testsrc::TestSource,
+    ts,
+};
+use srt_tokio::SrtSocket;
+use tokio::time::{sleep, timeout};
+
+static PORTS: Mutex<()> = Mutex::new(());
+
+fn serial() -> MutexGuard<'static, ()> {
+    PORTS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn scratch_dir(test: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(test);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A spawned `rust-srt` subcommand whose stdout and stderr lines are collected; killed when dropped.
+struct Bin {
+    child: Child,
+    lines: Receiver<String>,
+    seen: Vec<String>,
+}
+
+impl Bin {
+    fn spawn(args: &[&str]) -> Self {
+        Self::start(args, Stdio::inherit(), true)
+    }
+
+    /// Spawns with stdin and stdout left to the caller, as the ends of a pipeline; only stderr
+    /// lines are collected.
+    fn spawn_piped(args: &[&str]) -> (Self, ChildStdin, ChildStdout) {
+        let mut bin = Self::start(args, Stdio::piped(), false);
+        let stdin = bin.child.stdin.take().unwrap();
+        let stdout = bin.child.stdout.take().unwrap();
+        (bin, stdin, stdout)
+    }
+
+    fn start(args: &[&str], stdin: Stdio, collect_stdout: bool) -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_rust-srt"))
+            .args(args)
+            .env_remove("SRT_STATS")
+            .env_remove("SRT_METRICS_ADDR")
+            .env_remove("RUST_LOG")
+            .stdin(stdin)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap_or_else(|e| panic!("failed to spawn rust-srt {args:?}: {e}"));
+
+        let (tx, lines) = mpsc::channel();
+        let mut outputs: Vec<Box<dyn Read + Send>> = vec![Box::new(child.stderr.take().unwrap())];
+        if collect_stdout {
+            outputs.push(Box::new(child.stdout.take().unwrap()));
+        }
+        for output in outputs {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                for line in BufReader::new(output).lines().map_while(Result::ok) {
+                    if tx.send(line).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        Self {
+            child,
+            lines,
+            seen: Vec::new(),
+        }
+    }
+
+    /// Waits until a line containing `needle` was printed.
+    fn wait_for(&mut self, needle: &str, limit: Duration) {
+        let deadline = Instant::now() + limit;
+        if self.seen.iter().any(|line| line.contains(needle)) {
+            return;
+        }
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.lines.recv_timeout(left) {
+                Ok(line) => {
+                    let found = line.contains(needle);
+                    self.seen.push(line);
+                    if found {
+                        return;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => {
+                    panic!(
+                        "no line with {needle:?} within {limit:?}, got {:#?}",
+                        self.seen
+                    )
+                }
+            }
+        }
+    }
+
+    /// Sends SIGTERM, as a service manager stopping it would.
+    fn terminate(&self) {
+        let status = Command::new("kill")
+            .args(["-TERM", &self.child.id().to_string()])
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    /// Waits for the process to exit and returns its status with everything it printed.
+    fn finish(mut self, limit: Duration) -> (ExitStatus, Vec<String>) {
+        let deadline = Instant::now() + limit;
+        let status = loop {
+            if let Some(status) = self.child.try_wait().unwrap() {
+                break status;
+            }
+            assert!(
+                Instant::now() < deadline,
+                "process didn't exit within {limit:?}, got {:#?}",
+                self.seen
+            );
+            thread::sleep(Duration::from_millis(50));
+        };
+        // Readers end at EOF once the process is gone
+        while let Ok(line) = self.lines.recv_timeout(Duration::from_secs(1)) {
+            self.seen.push(line);
+        }
+        (status, std::mem::take(&mut self.seen))
+    }
+}
+
+impl Drop for Bin {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Writes `seconds` of 160x120 bars and tone at 25 fps as mpegts.
+fn generate_ts(path: &Path, seconds: u32) {
+    let source: TestSource = format!("testsrc:160x120@25:{seconds}").parse().unwrap();
+    source.write_to(File::create(path).unwrap()).unwrap();
+}
+
+#[test]
+fn client_messages_reach_server_in_order() {
+    let _ports = serial();
+    let mut server = Bin::spawn(&["text"]);
+    server.wait_for("Waiting for a connection", Duration::from_secs(10));
+
+    let start = Instant::now();
+    let client = Bin::spawn(&["text", "hello", "world", "camera"]);
+    let (status, _) = client.finish(Duration::from_secs(20));
+    assert!(status.success());
+    // Three messages 50 ms apart plus the client's 1 s grace period
+    assert!(
+        start.elapsed() < Duration::from_secs(10),
+        "{:?}",
+        start.elapsed()
+    );
+
+    server.wait_for("Client disconnected", Duration::from_secs(10));
+    let received = server
+        .seen
+        .iter()
+        .filter_map(|line| line.strip_prefix("Received: "))
+        .collect::<Vec<_>>();
+    assert_eq!(received, ["hello", "world", "camera"]);
+}
+
+#[test]
+fn recv_rejects_text_as_a_protocol_error() {
+    let _ports = serial();
+    let mut client = Bin::spawn(&["text", "--srt", "srt://:2223", "hello"]);
+    client.wait_for("Waiting for a connection", Duration::from_secs(10));
+
+    let receiver = Bin::spawn(&["recv"]);
+    let (status, output) = receiver.finish(Duration::from_secs(20));
+    assert_eq!(
+        status.code(),
+        Some(rust_srt::error::EXIT_PROTOCOL.into()),
+        "{output:#?}"
+    );
+    assert!(
+        output.iter().any(|line| line.contains("invalid message")),
+        "{output:#?}"
+    );
+}
+
+#[test]
+fn test_source_plays_to_recv() {
+    let _ports = serial();
+    let mut player = Bin::spawn(&["play", "testsrc:160x120@25:2", "--sequence"]);
+    player.wait_for("Waiting for a connection", Duration::from_secs(10));
+
+    let start = Instant::now();
+    let client = Bin::spawn(&["recv", "--srt", "srt://127.0.0.1:1234?latency=1000"]);
+    let (status, output) = client.finish(Duration::from_secs(30));
+    let elapsed = start.elapsed();
+
+    // Exit code 0 means the end-of-stream totals matched with nothing lost or out of order
+    assert!(status.success(), "{output:#?}");
+    assert!(
+        output.iter().any(|line| line.contains("complete")),
+        "{output:#?}"
+    );
+    // Paced by PTS: a 2 s clip takes about 2 s
+    assert!(elapsed >= Duration::from_secs(1), "{elapsed:?}");
+    assert!(elapsed < Duration::from_secs(20), "{elapsed:?}");
+
+    let (status, output) = player.finish(Duration::from_secs(10));
+    assert!(status.success(), "{output:#?}");
+}
+
+#[test]
+fn terminated_player_ends_the_stream_cleanly() {
+    let _ports = serial();
+    let mut player = Bin::spawn(&["play", "testsrc:160x120@25:60", "--sequence"]);
+    player.wait_for("Waiting for a connection", Duration::from_secs(10));
+
+    let receiver = Bin::spawn(&["recv", "--srt", "srt://127.0.0.1:1234?latency=1000"]);
+    thread::sleep(Duration::from_secs(2));
+    let start = Instant::now();
+    player.terminate();
+
+    // The player finishes the muxer and announces its totals, so nothing counts as lost
+    let (status, output) = player.finish(Duration::from_secs(10));
+    assert!(status.success(), "{output:#?}");
+    let (status, output) = receiver.finish(Duration::from_secs(10));
+    assert!(status.success(), "{output:#?}");
+    assert!(
+        output.iter().any(|line| line.contains("complete")),
+        "{output:#?}"
+    );
+    // Long before the 60 s clip would have ended
+    assert!(
+        start.elapsed() < Duration::from_secs(15),
+        "{:?}",
+        start.elapsed()
+    );
+}
+
+#[test]
+fn late_receiver_starts_at_a_keyframe() {
+    let _ports = serial();
+    let path = scratch_dir("late_receiver").join("late.ts");
+    let mut player = Bin::spawn(&["play", "testsrc:160x120@25:6", "--sequence"]);
+    player.wait_for("Waiting for a connection", Duration::from_secs(10));
+
+    let first = Bin::spawn(&["recv", "--srt", "srt://127.0.0.1:1234?latency=1000"]);
+    // Mid-GOP: the test source has a keyframe every second
+    thread::sleep(Duration::from_millis(2500));
+    let late = Bin::spawn(&[
+        "recv",
+        path.to_str().unwrap(),
+        "--srt",
+        "srt://127.0.0.1:1234?latency=1000",
+    ]);
+
+    // Each receiver checks its own numbering, so joining late still completes
+    for receiver in [first, late] {
+        let (status, output) = receiver.finish(Duration::from_secs(30));
+        assert!(status.success(), "{output:#?}");
+        assert!(
+            output.iter().any(|line| line.contains("complete")),
+            "{output:#?}"
+        );
+    }
+    let (status, output) = player.finish(Duration::from_secs(10));
+    assert!(status.success(), "{output:#?}");
+
+    // The tables come first, and the video picks up at a random access point
+    let data = std::fs::read(&path).unwrap();
+    assert_eq!(ts::pid(&data), ts::PAT_PID);
+    let report = probe::probe_path(path.to_str().unwrap(), Duration::from_secs(2)).unwrap();
+    let video_pid = report
+        .streams
+        .iter()
+        .find(|stream| stream.codec_type == CodecType::Video)
+        .and_then(|stream| stream.pid)
+        .unwrap();
+    let first_video = data
+        .chunks_exact(ts::TS_PACKET_SIZE)
+        .find(|packet| ts::pid(packet) == video_pid)
+        .unwrap();
+    assert!(ts::random_access(first_video));
+}
+
+#[test]
+fn live_srt_input_is_probed() {
+    let _ports = serial();
+    let mut player = Bin::spawn(&["play", "testsrc:320x180@25:10"]);
+    player.wait_for("Waiting for a connection", Duration::from_secs(10));
+
+    let prober = Bin::spawn(&[
+        "probe",
+        "srt://127.0.0.1:1234?latency=1000",
+        "--seconds",
+        "3",
+    ]);
+    let (status, lines) = prober.finish(Duration::from_secs(20));
+    assert!(status.success(), "{lines:#?}");
+    let report: serde_json::Value = lines
+        .iter()
+        .find_map(|line| serde_json::from_str(line).ok())
+        .unwrap_or_else(|| panic!("no report in {lines:#?}"));
+
+    let streams = report["streams"].as_array().unwrap();
+    let video = streams
+        .iter()
+        .find(|stream| stream["codec_type"] == "video")
+        .unwrap_or_else(|| panic!("no video in {report:#}"));
+    assert_eq!(
+        (video["width"].as_u64(), video["height"].as_u64()),
+        (Some(320), Some(180))
+    );
+    assert!(
+        streams.iter().any(|stream| stream["codec_type"] == "audio"),
+        "{report:#}"
+    );
+    assert_eq!(
+        report["programs"].as_array().unwrap().len(),
+        1,
+        "{report:#}"
+    );
+    assert!(report["format"]["probed_bytes"].as_u64().unwrap() > 0);
+}
+
+#[test]
+fn probe_rejects_a_window_that_isnt_positive() {
+    for seconds in ["-1", "0", "NaN", "1e30"] {
+        let prober = Bin::spawn(&["probe", "testsrc", "--seconds", seconds]);
+        let (status, lines) = prober.finish(Duration::from_secs(10));
+        assert_eq!(status.code(), Some(2), "{seconds}: {lines:#?}");
+    }
+}
+
+const FRAMES: u64 = 30;
+
+/// Solid colour frame whose blue channel encodes `seq`, so decoding can be checked.
+fn synthetic_frame(seq: u64) -> Mat {
+    let blue = (seq * 8) as f64;
+    Mat::new_rows_cols_with_default(120, 160, CV_8UC3, Scalar::new(blue, 64.0, 128.0, 0.0)).unwrap()
+}
+
+#[test]
+fn jpeg_frames_arrive_in_order_and_on_time() {
+    let _ports = serial();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        // Same wire format as slave -> master
+        let listener = tokio::spawn(
+            SrtSocket::builder()
+                .latency(Duration::from_millis(120))
+                .listen_on(":3333"),
+        );
+        let mut slave = SrtSocket::builder()
+            .latency(Duration::from_millis(120))
+            .call("127.0.0.1:3333", None)
+            .await
+            .unwrap();
+        let mut master = listener.await.unwrap().unwrap();
+
+        let sender = tokio::spawn(async move {
+            for seq in 0..FRAMES {
+                let mut buf = Vector::new();
+                imgcodecs::imencode(".jpg", &synthetic_frame(seq), &mut buf, &Vector::new())
+                    .unwrap();
+                let message = Message::Frame {
+                    seq,
+                    captured_at_us: now_us(),
+                    payload: buf.to_vec().into(),
+                };
+                slave
+                    .send((std::time::Instant::now(), message.encode()))
+                    .await
+                    .unwrap();
+                sleep(Duration::from_millis(33)).await;
+            }
+            slave.close().await.unwrap();
+        });
+
+        let mut sequence = SequenceTracker::new("loopback_master");
+        let mut received = Vec::new();
+        while let Some((_instant, bytes)) = timeout(Duration::from_secs(5), master.try_next())
+            .await
+            .expect("frames stopped arriving")
+            .unwrap()
+        {
+            let Message::Frame {
+                seq,
+                captured_at_us,
+                payload,
+            } = Message::decode(bytes).unwrap()
+            else {
+                panic!("expected a frame");
+            };
+            assert_eq!(sequence.observe(seq), Arrival::InOrder);
+
+            // 120 ms SRT latency plus scheduling, well under a second on loopback
+            let latency = Duration::from_micros(now_us().saturating_sub(captured_at_us));
+            assert!(latency < Duration::from_secs(1), "frame {seq}: {latency:?}");
+
+            let frame = imgcodecs::imdecode(&Vector::from_slice(&payload), imgcodecs::IMREAD_COLOR)
+                .unwrap();
+            assert_eq!((frame.cols(), frame.rows()), (160, 120));
+            let blue = core::mean(&frame, &core::no_array()).unwrap()[0];
+            assert!(
+                (blue - (seq * 8) as f64).abs() < 8.0,
+                "frame {seq}: blue {blue}"
+            );
+            received.push(seq);
+        }
+
+        sender.await.unwrap();
+        assert_eq!(received, (0..FRAMES).collect::<Vec<_>>());
+        assert_eq!(sequence.totals().lost, 0);
+    });
+}
+
+/// A camera named `stream_id` sending `frames` frames to `view`, after answering its clock probes.
+async fn fake_camera(stream_id: &str, frames: u64) {
+    let mut socket = SrtSocket::builder()
+        .latency(Duration::from_millis(120))
+        .call("127.0.0.1:2223", Some(stream_id))
+        .await
+        .unwrap();
+    latency::answer_clock_probes(&mut socket, CLOCK_PROBES, Duration::from_secs(5))
+        .await
+        .unwrap();
+    for seq in 0..frames {
+        let mut buf = Vector::new();
+        imgcodecs::imencode(".jpg", &synthetic_frame(seq), &mut buf, &Vector::new()).unwrap();
+        let message = Message::Frame {
+            seq,
+            captured_at_us: now_us(),
+            payload: buf.to_vec().into(),
+        };
+        socket
+            .send((std::time::Instant::now(), message.encode()))
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(40)).await;
+    }
+    socket.close().await.unwrap();
+}
+
+#[test]
+fn headless_view_saves_a_mosaic_of_every_camera() {
+    let _ports = serial();
+    let dir = scratch_dir("mosaic");
+    let _ = std::fs::remove_dir_all(&dir);
+    let mut view = Bin::spawn(&["view", "--headless", "--save", dir.to_str().unwrap()]);
+    view.wait_for("Waiting for a connection", Duration::from_secs(10));
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        tokio::join!(fake_camera("door", 25), fake_camera("yard", 25));
+    });
+    view.terminate();
+    let (status, output) = view.finish(Duration::from_secs(10));
+    assert!(status.success(), "{output:#?}");
+
+    // Two tiles side by side, each also saved on its own
+    let (width, height) = rust_srt::mosaic::TILE_SIZE;
+    let mosaic = imgcodecs::imread(
+        dir.join("mosaic.png").to_str().unwrap(),
+        imgcodecs::IMREAD_COLOR,
+    )
+    .unwrap();
+    assert_eq!((mosaic.cols(), mosaic.rows()), (2 * width, height));
+    for camera in ["door", "yard"] {
+        let tile = dir.join("tiles").join(format!("{camera}.png"));
+        assert!(tile.exists(), "{output:#?}");
+    }
+}
+
+#[test]
+fn view_rejects_a_second_camera_with_the_same_stream_id() {
+    let _ports = serial();
+    let mut view = Bin::spawn(&["view", "--headless"]);
+    view.wait_for("Waiting for a connection", Duration::from_secs(10));
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let first = tokio::spawn(async { fake_camera("door", 50).await });
+        sleep(Duration::from_millis(500)).await;
+        let second = SrtSocket::builder()
+            .latency(Duration::from_millis(120))
+            .call("127.0.0.1:2223", Some("door"))
+            .await;
+        // srt-tokio reports a rejected handshake as a refused connection
+        let error = second.err().expect("the second door was let in");
+        assert_eq!(error.kind(), std::io::ErrorKind::ConnectionRefused);
+        first.await.unwrap();
+
+        // Once the first one left, the stream ID is free again
+        sleep(Duration::from_millis(500)).await;
+        fake_camera("door", 5).await;
+    });
+    view.terminate();
+    let (status, output) = view.finish(Duration::from_secs(10));
+    assert!(status.success(), "{output:#?}");
+    assert!(
+        output
+            .iter()
+            .any(|line| line.contains("Stream ID already connected")),
+        "{output:#?}"
+    );
+}
+
+#[test]
+fn sent_file_arrives_byte_exact() {
+    let _ports = serial();
+    let dir = scratch_dir("send_recv");
+    let input = dir.join("input.ts");
+    let output = dir.join("output.ts");
+    generate_ts(&input, 3);
+    let _ = std::fs::remove_file(&output);
+
+    // Pace the whole file over about a second
+    let size = std::fs::metadata(&input).unwrap().len();
+    let bitrate = (size * 8).to_string();
+    let mut sender = Bin::spawn(&["send", input.to_str().unwrap(), "--bitrate", &bitrate]);
+    sender.wait_for("Waiting for a connection", Duration::from_secs(10));
+
+    let start = Instant::now();
+    let receiver = Bin::spawn(&["recv", output.to_str().unwrap()]);
+    let (status, lines) = receiver.finish(Duration::from_secs(30));
+    let elapsed = start.elapsed();
+
+    assert!(status.success(), "{lines:#?}");
+    assert_eq!(
+        std::fs::read(&output).unwrap(),
+        std::fs::read(&input).unwrap()
+    );
+    assert!(elapsed >= Duration::from_millis(800), "{elapsed:?}");
+    assert!(elapsed < Duration::from_secs(20), "{elapsed:?}");
+
+    let (status, lines) = sender.finish(Duration::from_secs(10));
+    assert!(status.success(), "{lines:#?}");
+}
+
+/// Writes `data` into a piped stdin from another thread, closing it at the end.
+fn feed(mut stdin: ChildStdin, data: Vec<u8>) {
+    thread::spawn(move || stdin.write_all(&data).unwrap());
+}
+
+/// Everything written to a piped stdout, once it closes.
+fn drain(mut stdout: ChildStdout) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut data = Vec::new();
+        stdout.read_to_end(&mut data).unwrap();
+        data
+    })
+}
+
+#[test]
+fn piped_ts_arrives_byte_exact_on_stdout() {
+    let _ports = serial();
+    let input = scratch_dir("pipe").join("input.ts");
+    generate_ts(&input, 3);
+    let data = std::fs::read(&input).unwrap();
+
+    // As in `ffmpeg ... -f mpegts - | rust-srt send - | ...`, paced over about a second
+    let bitrate = (data.len() * 8).to_string();
+    let (mut sender, stdin, _) = Bin::spawn_piped(&["send", "-", "--bitrate", &bitrate]);
+    feed(stdin, data.clone());
+    sender.wait_for("Waiting for a connection", Duration::from_secs(10));
+
+    let (receiver, _, stdout) = Bin::spawn_piped(&["recv", "-"]);
+    let received = drain(stdout);
+    let (status, lines) = receiver.finish(Duration::from_secs(30));
+    assert!(status.success(), "{lines:#?}");
+    assert!(
+        received.join().unwrap() == data,
+        "output differs from input"
+    );
+
+    let (status, lines) = sender.finish(Duration::from_secs(10));
+    assert!(status.success(), "{lines:#?}");
+}
+
+#[test]
+fn piped_ts_plays_through_a_forward_only_demuxer() {
+    let _ports = serial();
+    let input = scratch_dir("pipe_play").join("input.ts");
+    generate_ts(&input, 2);
+
+    // Stdin can't seek, so the demuxer reads it as a plain stream
+    let (mut player, stdin, _) = Bin::spawn_piped(&["play", "-", "--sequence"]);
+    feed(stdin, std::fs::read(&input).unwrap());
+    player.wait_for("Waiting for a connection", Duration::from_secs(10));
+
+    let (receiver, _, stdout) =
+        Bin::spawn_piped(&["recv", "-", "--srt", "srt://127.0.0.1:1234?latency=1000"]);
+    let received = drain(stdout);
+    let (status, lines) = receiver.finish(Duration::from_secs(30));
+    assert!(status.success(), "{lines:#?}");
+    assert!(
+        lines.iter().any(|line| line.contains("complete")),
+        "{lines:#?}"
+    );
+
+    // Remuxed rather than copied, but still whole TS packets starting with the tables
+    let received = received.join().unwrap();
+    assert!(!received.is_empty());
+    assert_eq!(received.len() % ts::TS_PACKET_SIZE, 0);
+    assert_eq!(ts::pid(&received), ts::PAT_PID);
+
+    let (status, lines) = player.finish(Duration::from_secs(10));
+    assert!(status.success(), "{lines:#?}");
+}
+
+/// Every message `send` puts on the wire for `input`, called on its default port.
+async fn wire_messages(input: &Path, extra: &[&str]) -> Vec<bytes::Bytes> {
+    let size = std::fs::metadata(input).unwrap().len();
+    let bitrate = (size * 8).to_string();
+    let mut args = vec!["send", input.to_str().unwrap(), "--bitrate", &bitrate];
+    args.extend_from_slice(extra);
+    let mut sender = Bin::spawn(&args);
+    sender.wait_for("Waiting for a connection", Duration::from_secs(10));
+
+    let mut socket = SrtSocket::builder()
+        .latency(Duration::from_millis(120))
+        .call("127.0.0.1:2223", None)
+        .await
+        .unwrap();
+    let mut messages = Vec::new();
+    while let Some((_instant, bytes)) = timeout(Duration::from_secs(5), socket.try_next())
+        .await
+        .expect("messages stopped arriving")
+        .unwrap()
+    {
+        messages.push(bytes);
+    }
+    let (status, lines) = sender.finish(Duration::from_secs(10));
+    assert!(status.success(), "{lines:#?}");
+    messages
+}
+
+#[test]
+fn ts_is_raw_on_the_wire_unless_numbered() {
+    let _ports = serial();
+    let input = scratch_dir("wire").join("input.ts");
+    generate_ts(&input, 1);
+    let ts = std::fs::read(&input).unwrap();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    // What ffplay and libsrt expect: nothing but whole packets, 1316 bytes a message
+    let raw = runtime.block_on(wire_messages(&input, &[]));
+    let (last, full) = raw.split_last().unwrap();
+    assert!(
+        full.iter()
+            .all(|message| message.len() == ts::SRT_PAYLOAD_SIZE)
+    );
+    assert!(last.len() <= ts::SRT_PAYLOAD_SIZE);
+    assert!(raw.iter().all(|message| message[0] == ts::SYNC_BYTE));
+    assert_eq!(raw.concat(), ts);
+
+    // Numbered messages still fit 1316 bytes, with six packets behind the header
+    let numbered = runtime.block_on(wire_messages(&input, &["--sequence"]));
+    assert!(
+        numbered
+            .iter()
+            .all(|message| message.len() <= ts::SRT_PAYLOAD_SIZE)
+    );
+    let (end, data) = numbered.split_last().unwrap();
+    let mut payloads = Vec::new();
+    for (seq, message) in data.iter().enumerate() {
+        let Message::Data {
+            seq: numbered,
+            payload,
+        } = Message::decode(message.clone()).unwrap()
+        else {
+            panic!("expected data, got {message:?}");
+        };
+        assert_eq!(numbered, seq as u64);
+        payloads.push(payload);
+    }
+    let (last, full) = payloads.split_last().unwrap();
+    assert!(
+        full.iter()
+            .all(|p| p.len() == protocol::SEQUENCED_TS_PAYLOAD_SIZE)
+    );
+    assert!(last.len() <= protocol::SEQUENCED_TS_PAYLOAD_SIZE);
+    assert_eq!(payloads.concat(), ts);
+    assert_eq!(
+        Message::decode(end.clone()).unwrap(),
+        Message::EndOfStream {
+            frames: data.len() as u64,
+            bytes: ts.len() as u64
+        }
+    );
+}
+
+const TRANSFER_SIZE: usize = 2 * 1024 * 1024;
+
+/// Starts a `--transfer` of a fresh `input.bin` in `dir` and stops the receiver midway, leaving
+/// the sender waiting for it to come back.
+fn interrupted_transfer(dir: &Path) -> (Vec<u8>, PathBuf, Bin) {
+    let input = dir.join("input.bin");
+    let received = dir.join("received");
+    let _ = std::fs::remove_dir_all(&received);
+    let data = (0..TRANSFER_SIZE as u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+        .collect::<Vec<_>>();
+    std::fs::write(&input, &data).unwrap();
+
+    // About four seconds for the whole file
+    let bitrate = (TRANSFER_SIZE * 2).to_string();
+    let mut sender = Bin::spawn(&[
+        "send",
+        input.to_str().unwrap(),
+        "--transfer",
+        "--bitrate",
+        &bitrate,
+    ]);
+    sender.wait_for("Waiting for a connection", Duration::from_secs(10));
+
+    let part = received.join("input.bin.part");
+    let receiver = Bin::spawn(&["recv", "--transfer", received.to_str().unwrap()]);
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while std::fs::metadata(&part).map_or(0, |meta| meta.len()) < TRANSFER_SIZE as u64 / 4 {
+        assert!(
+            Instant::now() < deadline,
+            "no progress on {}",
+            part.display()
+        );
+        thread::sleep(Duration::from_millis(20));
+    }
+    receiver.terminate();
+    let (status, lines) = receiver.finish(Duration::from_secs(10));
+    assert_eq!(
+        status.code(),
+        Some(shutdown::EXIT_INTERRUPTED.into()),
+        "{lines:#?}"
+    );
+    assert!(
+        lines
+            .iter()
+            .any(|line| line.contains("partial file is kept")),
+        "{lines:#?}"
+    );
+
+    let kept = std::fs::metadata(&part).unwrap().len();
+    assert!(kept > 0 && kept < TRANSFER_SIZE as u64, "{kept}");
+    (data, part, sender)
+}
+
+#[test]
+fn interrupted_transfer_resumes_byte_exact() {
+    let _ports = serial();
+    let dir = scratch_dir("transfer_resume");
+    let (data, part, sender) = interrupted_transfer(&dir);
+    let kept = std::fs::metadata(&part).unwrap().len();
+
+    let received = part.parent().unwrap();
+    let receiver = Bin::spawn(&["recv", "--transfer", received.to_str().unwrap()]);
+    let (status, lines) = receiver.finish(Duration::from_secs(30));
+    assert!(status.success(), "{lines:#?}");
+    let offset = lines
+        .iter()
+        .find_map(|line| line.split("from offset ").nth(1))
+        .and_then(|rest| {
+            rest.split(|c: char| !c.is_ascii_digit())
+                .next()?
+                .parse::<u64>()
+                .ok()
+        })
+        .unwrap_or_else(|| panic!("no resume offset in {lines:#?}"));
+    assert!(
+        offset > 0 && offset <= kept,
+        "resumed from {offset}, kept {kept}"
+    );
+
+    assert_eq!(std::fs::read(received.join("input.bin")).unwrap(), data);
+    assert!(!part.exists());
+    let (status, lines) = sender.finish(Duration::from_secs(10));
+    assert!(status.success(), "{lines:#?}");
+    assert!(
+        lines.iter().any(|line| line.contains("Receiver verified")),
+        "{lines:#?}"
+    );
+}
+
+#[test]
+fn corrupted_partial_file_fails_the_checksum() {
+    let _ports = serial();
+    let dir = scratch_dir("transfer_corrupt");
+    let (_data, part, sender) = interrupted_transfer(&dir);
+
+    // Same length, so the receiver still resumes from the end of it
+    let mut partial = std::fs::read(&part).unwrap();
+    for byte in &mut partial[..1024] {
+        *byte = !*byte;
+    }
+    std::fs::write(&part, partial).unwrap();
+
+    let received = part.parent().unwrap();
+    let receiver = Bin::spawn(&["recv", "--transfer", received.to_str().unwrap()]);
+    let (status, lines) = receiver.finish(Duration::from_secs(30));
+    assert_eq!(
+        status.code(),
+        Some(error::EXIT_CHECKSUM_MISMATCH.into()),
+        "{lines:#?}"
+    );
+    assert!(!received.join("input.bin").exists());
+    assert!(!part.exists(), "a corrupt partial file must not be resumed");
+
+    let (status, lines) = sender.finish(Duration::from_secs(10));
+    assert_eq!(
+        status.code(),
+        Some(error::EXIT_CHECKSUM_MISMATCH.into()),
+        "{lines:#?}"
+    );
+}
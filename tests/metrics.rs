@@ -0,0 +1,137 @@
+use std::{
+    io::Write,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use futures::{SinkExt, TryStreamExt};
+use rust_srt::{
+    bridge::WriteBridge,
+    metrics,
+    stats::{StatsConfig, StatsReporter, StatsSocket},
+};
+use srt_tokio::SrtSocket;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+    time::sleep,
+};
+
+async fn get(addr: std::net::SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!("GET {path} HTTP/1.1\r\nHost: {addr}\r\n\r\n");
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+/// The value of the series `name` (including its labels) in a scrape.
+fn value(response: &str, name: &str) -> f64 {
+    response
+        .lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+        .unwrap_or_else(|| panic!("{name} missing from {response}"))
+        .parse()
+        .unwrap()
+}
+
+fn stats_socket(socket: SrtSocket, label: &str) -> StatsSocket {
+    let config = StatsConfig {
+        interval: Duration::from_millis(100),
+        print: false,
+        output: None,
+    };
+    let reporter = StatsReporter::new(label, config).unwrap();
+    StatsSocket::new(socket, label, Some(reporter))
+}
+
+#[tokio::test]
+async fn scrape_srt_and_bridge_metrics_over_loopback() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(metrics::serve_listener(listener));
+
+    // A loopback SRT pair, sampled like every socket the commands open
+    let listening = tokio::spawn(
+        SrtSocket::builder()
+            .latency(Duration::from_millis(120))
+            .listen_on(":24392"),
+    );
+    sleep(Duration::from_millis(100)).await;
+    let caller = SrtSocket::builder()
+        .latency(Duration::from_millis(120))
+        .call("127.0.0.1:24392", None)
+        .await
+        .unwrap();
+    // Both ends share a label, as every camera of a `view` does, but each gets its own series
+    let mut caller = stats_socket(caller, "metrics");
+    let mut listener = stats_socket(listening.await.unwrap().unwrap(), "metrics");
+    let caller_series = format!("{{socket=\"metrics\",session=\"{}\"}}", caller.session());
+    let listener_series = format!("{{socket=\"metrics\",session=\"{}\"}}", listener.session());
+
+    // Keep both sides busy past srt-tokio's statistics interval
+    let receiver = tokio::spawn(async move {
+        let mut received = 0;
+        while let Some((_instant, bytes)) = listener.try_next().await.unwrap() {
+            received += bytes.len();
+        }
+        (received, listener)
+    });
+    let started = Instant::now();
+    let mut sent = 0;
+    while started.elapsed() < Duration::from_millis(2500) {
+        caller
+            .send((Instant::now(), Bytes::from(vec![0x47; 1316])))
+            .await
+            .unwrap();
+        sent += 1316;
+        sleep(Duration::from_millis(5)).await;
+    }
+    // Only a socket the application measured a round trip on reports one
+    caller.record_round_trip(Duration::from_millis(250));
+    caller.close().await.unwrap();
+    let (received, listener) = receiver.await.unwrap();
+    assert_eq!(received, sent);
+
+    // Nobody drains the channel, so everything past its one slot is dropped
+    let (sender, _unread) = mpsc::channel(1);
+    let mut bridge = WriteBridge::new(sender);
+    bridge.write_all(&[0x47; 3 * 1316]).unwrap();
+
+    let response = get(addr, "/metrics").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+    assert!(response.contains("# TYPE srt_tx_mbps gauge"));
+    assert!(response.contains("# TYPE srt_rx_lost_packets_total counter"));
+    assert!(value(&response, &format!("srt_tx_mbps{caller_series}")) > 0.0);
+    assert!(value(&response, &format!("srt_rx_mbps{listener_series}")) > 0.0);
+    assert_eq!(
+        value(&response, &format!("srt_probe_rtt_ms{caller_series}")),
+        250.0
+    );
+    assert!(!response.contains(&format!("srt_probe_rtt_ms{listener_series}")));
+    for series in [
+        format!("srt_tx_buffered_ms{caller_series}"),
+        format!("srt_rx_buffered_ms{listener_series}"),
+        format!("srt_tx_retransmitted_packets_total{caller_series}"),
+        format!("srt_rx_dropped_packets_total{listener_series}"),
+    ] {
+        value(&response, &series);
+    }
+    assert!(response.contains("# TYPE ts_chunks_dropped_total counter"));
+    assert_eq!(value(&response, "ts_chunks_dropped_total"), 2.0);
+
+    // A closed socket's rates drop to zero
+    drop(listener);
+    let response = get(addr, "/metrics").await;
+    assert_eq!(
+        value(&response, &format!("srt_rx_mbps{listener_series}")),
+        0.0
+    );
+    assert!(value(&response, &format!("srt_tx_mbps{caller_series}")) > 0.0);
+
+    let response = get(addr, "/nope").await;
+    assert!(response.starts_with("HTTP/1.1 404"), "{response}");
+}
@@ -0,0 +1,144 @@
+//! Cameras are laid out by stream ID, each tile showing its own state.
+
+use std::time::{Duration, Instant};
+
+use opencv::{
+    core::{self, CV_8UC3, Mat, Rect, Scalar, Size},
+    prelude::*,
+};
+use rust_srt::mosaic::{Mosaic, TileState};
+
+const TILE: Size = Size {
+    width: 160,
+    height: 90,
+};
+
+fn solid(blue: f64, green: f64, red: f64) -> Mat {
+    Mat::new_rows_cols_with_default(240, 320, CV_8UC3, Scalar::new(blue, green, red, 0.0)).unwrap()
+}
+
+/// Mean BGR of the grid cell at `column`, `row`.
+fn cell_mean(mosaic: &Mat, column: i32, row: i32) -> [f64; 3] {
+    let cell = mosaic
+        .roi(Rect::new(
+            column * TILE.width,
+            row * TILE.height,
+            TILE.width,
+            TILE.height,
+        ))
+        .unwrap();
+    let mean = core::mean(&*cell, &core::no_array()).unwrap();
+    [mean[0], mean[1], mean[2]]
+}
+
+/// Within `tolerance` per channel, leaving room for the overlays.
+fn close(actual: [f64; 3], expected: [f64; 3], tolerance: f64) -> bool {
+    actual
+        .iter()
+        .zip(expected)
+        .all(|(actual, expected)| (actual - expected).abs() < tolerance)
+}
+
+#[test]
+fn tiles_fill_a_square_grid_in_stream_id_order() {
+    let mut mosaic = Mosaic::new(TILE, Duration::from_secs(2));
+    let now = Instant::now();
+    mosaic.update("yard", &solid(0.0, 200.0, 0.0), now).unwrap();
+    mosaic.update("door", &solid(200.0, 0.0, 0.0), now).unwrap();
+    mosaic.update("gate", &solid(0.0, 0.0, 200.0), now).unwrap();
+    assert_eq!(mosaic.ids().collect::<Vec<_>>(), ["door", "gate", "yard"]);
+
+    let grid = mosaic.compose(now).unwrap();
+    assert_eq!(
+        (grid.cols(), grid.rows()),
+        (2 * TILE.width, 2 * TILE.height)
+    );
+    assert!(close(cell_mean(&grid, 0, 0), [200.0, 0.0, 0.0], 40.0));
+    assert!(close(cell_mean(&grid, 1, 0), [0.0, 0.0, 200.0], 40.0));
+    assert!(close(cell_mean(&grid, 0, 1), [0.0, 200.0, 0.0], 40.0));
+    assert!(close(cell_mean(&grid, 1, 1), [0.0, 0.0, 0.0], 1.0));
+}
+
+#[test]
+fn tiles_turn_stale_and_offline() {
+    let mut mosaic = Mosaic::new(TILE, Duration::from_secs(2));
+    let start = Instant::now();
+    for frame in 0..10 {
+        let at = start + Duration::from_millis(100) * frame;
+        mosaic.update("door", &solid(50.0, 50.0, 50.0), at).unwrap();
+    }
+    let last = start + Duration::from_millis(900);
+    assert_eq!(mosaic.state("door", last), Some(TileState::Live));
+    assert_eq!(mosaic.fps("door", last), Some(10.0));
+
+    let later = last + Duration::from_secs(3);
+    assert_eq!(mosaic.state("door", later), Some(TileState::Stale));
+    assert_eq!(mosaic.fps("door", later), Some(0.0));
+
+    mosaic.disconnected("door");
+    assert_eq!(mosaic.state("door", later), Some(TileState::Disconnected));
+    // Its last frame stays up
+    assert_eq!(mosaic.len(), 1);
+    assert!(mosaic.render_tile("door", later).unwrap().is_some());
+    assert_eq!(mosaic.state("gate", later), None);
+}
+
+#[test]
+fn mosaic_and_tiles_are_saved_as_png() {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("mosaic_save");
+    let _ = std::fs::remove_dir_all(&dir);
+    let mut mosaic = Mosaic::new(TILE, Duration::from_secs(2));
+    let now = Instant::now();
+    mosaic
+        .update("lobby/1", &solid(0.0, 0.0, 200.0), now)
+        .unwrap();
+    mosaic.save(&dir, now).unwrap();
+
+    let tile = opencv::imgcodecs::imread(
+        dir.join("tiles/lobby_1.png").to_str().unwrap(),
+        opencv::imgcodecs::IMREAD_COLOR,
+    )
+    .unwrap();
+    assert_eq!((tile.cols(), tile.rows()), (TILE.width, TILE.height));
+    assert!(dir.join("mosaic.png").exists());
+}
+
+#[test]
+fn tile_file_names_are_unique() {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("mosaic_names");
+    let _ = std::fs::remove_dir_all(&dir);
+    let mut mosaic = Mosaic::new(TILE, Duration::from_secs(2));
+    let now = Instant::now();
+    for id in ["cam/1", "cam_1", "cam 1", "mosaic"] {
+        mosaic.update(id, &solid(0.0, 0.0, 200.0), now).unwrap();
+    }
+
+    // Grid order is stream ID order: "cam 1", "cam/1", "cam_1", "mosaic"
+    assert_eq!(
+        mosaic.tile_file_names(),
+        [
+            ("cam 1", "cam_1".to_string()),
+            ("cam/1", "cam_1-2".to_string()),
+            ("cam_1", "cam_1-3".to_string()),
+            ("mosaic", "mosaic".to_string()),
+        ]
+    );
+
+    // A camera called "mosaic" doesn't overwrite the mosaic itself
+    mosaic.save(&dir, now).unwrap();
+    let saved = opencv::imgcodecs::imread(
+        dir.join("mosaic.png").to_str().unwrap(),
+        opencv::imgcodecs::IMREAD_COLOR,
+    )
+    .unwrap();
+    assert_eq!(
+        (saved.cols(), saved.rows()),
+        (2 * TILE.width, 2 * TILE.height)
+    );
+    for name in ["cam_1", "cam_1-2", "cam_1-3", "mosaic"] {
+        assert!(
+            dir.join("tiles").join(format!("{name}.png")).exists(),
+            "{name}"
+        );
+    }
+}
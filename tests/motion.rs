@@ -0,0 +1,179 @@
+//! Motion is found against a learned background, within the watched regions, and every event
+//! is saved as a clip starting before it plus a line of the event log.
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use opencv::{
+    core::{CV_8UC3, Mat, Rect, Scalar},
+    imgproc,
+    prelude::*,
+    videoio::{self, VideoCapture},
+};
+use rust_srt::motion::{EVENT_LOG, EventRecorder, MotionDetector, Region};
+
+/// 25 fps
+const FRAME_US: u64 = 40_000;
+const START_US: u64 = 1_700_000_000_000_000;
+
+fn scratch_dir(test: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(test);
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn background() -> Mat {
+    Mat::new_rows_cols_with_default(240, 320, CV_8UC3, Scalar::all(60.0)).unwrap()
+}
+
+/// The background with a white square where `rect` is.
+fn with_object(rect: Rect) -> Mat {
+    let mut frame = background();
+    imgproc::rectangle(
+        &mut frame,
+        rect,
+        Scalar::all(255.0),
+        imgproc::FILLED,
+        imgproc::LINE_8,
+        0,
+    )
+    .unwrap();
+    frame
+}
+
+fn learned(regions: Vec<Region>) -> MotionDetector {
+    let mut detector = MotionDetector::new(regions).unwrap();
+    for _ in 0..30 {
+        assert_eq!(detector.changed(&background()).unwrap(), 0.0);
+    }
+    detector
+}
+
+#[test]
+fn regions_parse_and_print_back() {
+    let region: Region = "320x240+10+20".parse().unwrap();
+    assert_eq!(
+        region,
+        Region {
+            width: 320,
+            height: 240,
+            x: 10,
+            y: 20
+        }
+    );
+    assert_eq!(region.to_string(), "320x240+10+20");
+    for invalid in ["320x240", "0x240+0+0", "320x240+0", "a+b+c"] {
+        assert!(invalid.parse::<Region>().is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn an_object_on_the_background_is_motion() {
+    let mut detector = learned(vec![]);
+    let changed = detector
+        .changed(&with_object(Rect::new(0, 0, 160, 120)))
+        .unwrap();
+    // A quarter of the frame, give or take the blurred edges
+    assert!((0.2..0.3).contains(&changed), "{changed}");
+}
+
+#[test]
+fn only_the_watched_regions_count() {
+    let door = "160x120+160+120".parse().unwrap();
+
+    let mut detector = learned(vec![door]);
+    let outside = detector
+        .changed(&with_object(Rect::new(0, 0, 120, 100)))
+        .unwrap();
+    assert_eq!(outside, 0.0);
+
+    let mut detector = learned(vec![door]);
+    let inside = detector
+        .changed(&with_object(Rect::new(160, 120, 160, 120)))
+        .unwrap();
+    assert!(inside > 0.8, "{inside}");
+}
+
+#[test]
+fn events_are_clipped_from_before_the_motion() {
+    let dir = scratch_dir("motion_events");
+    let mut recorder = EventRecorder::new(
+        dir.clone(),
+        "front door",
+        Duration::from_millis(400),
+        Duration::from_millis(200),
+    );
+
+    // 1 s quiet, 0.4 s of it buffered; 0.5 s motion; quiet until the event ends
+    let mut at_us = START_US;
+    let mut ended = None;
+    for frame in 0..100 {
+        let motion = (25..38)
+            .contains(&frame)
+            .then_some(0.1 + frame as f64 / 1000.0);
+        let event = recorder.push(&background(), at_us, motion).unwrap();
+        if let Some(event) = event {
+            ended = Some((frame, event));
+            break;
+        }
+        at_us += FRAME_US;
+    }
+    let (last_frame, event) = ended.expect("the event should end");
+    assert!(!recorder.is_recording());
+
+    assert_eq!(event.stream, "front door");
+    assert_eq!(event.started_at_us, START_US + 25 * FRAME_US);
+    assert_eq!(event.ended_at_us, START_US + 37 * FRAME_US);
+    assert_eq!(last_frame, 37 + 5);
+    assert_eq!(event.pre_event_frames, 11);
+    assert_eq!(event.frames, 11 + (last_frame - 25 + 1));
+    assert!((event.peak - 0.137).abs() < 1e-9);
+    assert_eq!(
+        event.clip,
+        format!("front_door-{}.avi", event.started_at_us / 1000)
+    );
+
+    let clip = dir.join(&event.clip);
+    let capture = VideoCapture::from_file(clip.to_str().unwrap(), videoio::CAP_ANY).unwrap();
+    assert!(capture.is_opened().unwrap());
+    let frames = capture.get(videoio::CAP_PROP_FRAME_COUNT).unwrap();
+    assert_eq!(frames as u64, event.frames);
+
+    let log = std::fs::read_to_string(dir.join(EVENT_LOG)).unwrap();
+    let entries: Vec<serde_json::Value> = log
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["clip"], event.clip);
+    assert_eq!(entries[0]["frames"], event.frames);
+    assert_eq!(entries[0]["started_at_us"], event.started_at_us);
+}
+
+#[test]
+fn finishing_closes_the_event_in_progress() {
+    let dir = scratch_dir("motion_finish");
+    let mut recorder = EventRecorder::new(
+        dir.clone(),
+        "yard",
+        Duration::from_secs(1),
+        Duration::from_secs(10),
+    );
+    assert!(
+        recorder
+            .push(&background(), START_US, Some(0.5))
+            .unwrap()
+            .is_none()
+    );
+    assert!(recorder.is_recording());
+
+    let event = recorder.finish().unwrap().expect("an event was recording");
+    assert_eq!((event.frames, event.pre_event_frames), (1, 0));
+    assert!(dir.join(&event.clip).exists());
+    assert!(recorder.finish().unwrap().is_none());
+
+    let log = std::fs::read_to_string(dir.join(EVENT_LOG)).unwrap();
+    assert_eq!(log.lines().count(), 1);
+}
@@ -0,0 +1,153 @@
+//! Processors are configured from `--process` specs and run in order on every frame.
+
+use opencv::{
+    core::{CV_8UC3, Mat, Scalar},
+    prelude::*,
+};
+use rust_srt::pipeline::{
+    self, FrameContext, FrameProcessor, Pipeline, ProcessorSpec, format_utc_time,
+};
+
+fn frame(width: i32, height: i32) -> Mat {
+    Mat::new_rows_cols_with_default(height, width, CV_8UC3, Scalar::all(80.0)).unwrap()
+}
+
+fn context(seq: u64) -> FrameContext {
+    FrameContext {
+        stream: "door".to_string(),
+        seq,
+        captured_at_us: 0,
+        motion: false,
+        changed: 0.0,
+    }
+}
+
+fn specs(specs: &[&str]) -> Vec<ProcessorSpec> {
+    specs.iter().map(|spec| spec.parse().unwrap()).collect()
+}
+
+#[test]
+fn specs_parse_and_print_back() {
+    for spec in [
+        "resize=640x360",
+        "crop=320x240+16+8",
+        "rotate=270",
+        "timestamp",
+        "motion=0.05",
+        "snapshot=shots",
+        "motion=0.02,320x240+0+240,100x100+400+0",
+        "record=events",
+    ] {
+        let parsed: ProcessorSpec = spec.parse().unwrap();
+        assert_eq!(parsed.to_string(), spec);
+    }
+    assert_eq!(
+        "motion".parse::<ProcessorSpec>().unwrap(),
+        ProcessorSpec::Motion {
+            threshold: 0.01,
+            regions: vec![]
+        }
+    );
+
+    for invalid in [
+        "resize",
+        "resize=0x10",
+        "crop=10x10",
+        "rotate=45",
+        "motion=2",
+        "motion=0.1,320x240",
+        "record",
+        "blur",
+    ] {
+        assert!(invalid.parse::<ProcessorSpec>().is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn recording_needs_motion_first() {
+    assert!(pipeline::check_order(&specs(&["motion", "timestamp", "record=events"])).is_ok());
+    assert!(pipeline::check_order(&specs(&["record=events", "motion"])).is_err());
+    assert!(pipeline::check_order(&specs(&["resize=640x360", "record=events"])).is_err());
+}
+
+#[test]
+fn overlays_come_after_motion() {
+    assert!(pipeline::check_order(&specs(&["resize=640x360", "motion", "timestamp"])).is_ok());
+    assert!(pipeline::check_order(&specs(&["timestamp", "motion"])).is_err());
+    assert!(pipeline::check_order(&specs(&["motion=0.05", "motion=0.2"])).is_err());
+}
+
+#[test]
+fn processors_run_in_order() {
+    let mut pipeline = Pipeline::from_specs(&specs(&[
+        "crop=400x300+100+50",
+        "rotate=90",
+        "resize=150x200",
+    ]));
+    let mut mat = frame(640, 480);
+    pipeline.process(&mut mat, &mut context(0)).unwrap();
+    assert_eq!((mat.cols(), mat.rows()), (150, 200));
+
+    // Cropped to what's left of the frame, then turned on its side
+    let mut pipeline = Pipeline::from_specs(&specs(&["crop=400x300+500+400", "rotate=90"]));
+    let mut mat = frame(640, 480);
+    pipeline.process(&mut mat, &mut context(0)).unwrap();
+    assert_eq!((mat.cols(), mat.rows()), (80, 140));
+}
+
+#[test]
+fn crop_outside_the_frame_fails() {
+    let mut pipeline = Pipeline::from_specs(&specs(&["crop=10x10+700+0"]));
+    let error = pipeline
+        .process(&mut frame(640, 480), &mut context(3))
+        .unwrap_err();
+    assert!(
+        format!("{error:#}").starts_with("crop failed on frame 3"),
+        "{error:#}"
+    );
+}
+
+#[test]
+fn motion_flags_changed_frames() {
+    let mut pipeline = Pipeline::from_specs(&specs(&["motion=0.1"]));
+    let mut still = context(0);
+    pipeline.process(&mut frame(160, 120), &mut still).unwrap();
+    pipeline.process(&mut frame(160, 120), &mut still).unwrap();
+    assert!(!still.motion);
+
+    let mut moved = context(2);
+    let mut bright =
+        Mat::new_rows_cols_with_default(120, 160, CV_8UC3, Scalar::all(220.0)).unwrap();
+    pipeline.process(&mut bright, &mut moved).unwrap();
+    assert!(moved.motion);
+}
+
+#[test]
+fn custom_processors_join_the_chain() {
+    struct Count(u64);
+    impl FrameProcessor for Count {
+        fn name(&self) -> &'static str {
+            "count"
+        }
+
+        fn process(&mut self, _: &mut Mat, context: &mut FrameContext) -> anyhow::Result<()> {
+            self.0 += 1;
+            assert_eq!(context.seq, self.0);
+            Ok(())
+        }
+    }
+
+    let mut pipeline = Pipeline::default();
+    pipeline.push(Box::new(Count(0)));
+    for seq in 1..=3 {
+        pipeline
+            .process(&mut frame(8, 8), &mut context(seq))
+            .unwrap();
+    }
+}
+
+#[test]
+fn timestamps_are_utc_wall_clock() {
+    // 2024-01-02 03:04:05.678 UTC
+    assert_eq!(format_utc_time(1_704_164_645_678_000), "03:04:05.678 UTC");
+}
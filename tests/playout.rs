@@ -0,0 +1,98 @@
+//! Frames are shown on the schedule of their timestamps, whatever the jitter on arrival.
+
+use std::time::{Duration, Instant};
+
+use rust_srt::playout::{PlayoutBuffer, PlayoutTotals};
+
+const DELAY: Duration = Duration::from_millis(100);
+const FRAME: Duration = Duration::from_millis(40);
+
+fn ms(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+#[test]
+fn jittery_arrivals_play_out_evenly() {
+    let mut buffer = PlayoutBuffer::new("test", DELAY, 100);
+    let start = Instant::now();
+    // Timestamps 40 ms apart; the first frame takes 10 ms to arrive, the others up to 60 ms more
+    let transit = [10, 70, 20, 50, 15];
+    for (index, extra) in transit.into_iter().enumerate() {
+        let timestamp = start + FRAME * index as u32;
+        assert!(buffer.push(timestamp, timestamp + ms(extra), index));
+    }
+
+    let mut shown = Vec::new();
+    while let Some(due) = buffer.next_due() {
+        shown.push((due - start, buffer.pop(due).unwrap()));
+    }
+    let expected = (0..5)
+        .map(|index| (ms(10) + DELAY + FRAME * index as u32, index))
+        .collect::<Vec<_>>();
+    assert_eq!(shown, expected);
+    assert_eq!(buffer.totals().late, 0);
+}
+
+#[test]
+fn late_frames_are_dropped() {
+    let mut buffer = PlayoutBuffer::new("test", DELAY, 100);
+    let start = Instant::now();
+    assert!(buffer.push(start, start, 0));
+    // Due at 140 ms, arriving at 150 ms
+    assert!(!buffer.push(start + FRAME, start + ms(150), 1));
+
+    // Frames 0, 2 and 3 all due by the time the display catches up: only the newest is shown
+    assert!(buffer.push(start + FRAME * 2, start + ms(150), 2));
+    assert!(buffer.push(start + FRAME * 3, start + ms(150), 3));
+    assert_eq!(buffer.pop(start + ms(250)), Some(3));
+    assert_eq!(
+        buffer.totals(),
+        PlayoutTotals {
+            played: 1,
+            late: 3,
+            underruns: 0,
+            overruns: 0,
+        }
+    );
+}
+
+#[test]
+fn nothing_is_shown_early() {
+    let mut buffer = PlayoutBuffer::new("test", DELAY, 100);
+    let start = Instant::now();
+    buffer.push(start, start, 0);
+    assert_eq!(buffer.pop(start + ms(99)), None);
+    assert_eq!(buffer.pop(start + DELAY), Some(0));
+    assert!(buffer.is_empty());
+}
+
+#[test]
+fn underruns_and_overruns_are_counted() {
+    let mut buffer = PlayoutBuffer::new("test", DELAY, 2);
+    let start = Instant::now();
+    for index in 0..3 {
+        let timestamp = start + FRAME * index;
+        buffer.push(timestamp, timestamp, index);
+    }
+    // Frame 0 made room for frame 2
+    assert_eq!(buffer.len(), 2);
+    assert_eq!(buffer.totals().overruns, 1);
+
+    // Emptying the buffer isn't an underrun while the next frame comes in time for its slot
+    assert_eq!(buffer.pop(start + DELAY + FRAME), Some(1));
+    assert_eq!(buffer.pop(start + DELAY + FRAME * 2), Some(2));
+    assert!(buffer.is_empty());
+    assert!(buffer.push(start + FRAME * 3, start + FRAME * 3, 3));
+    assert_eq!(buffer.pop(start + DELAY + FRAME * 3), Some(3));
+    assert_eq!(buffer.totals().underruns, 0);
+
+    // Frame 4 is lost and frame 5 only arrives after the slot of frame 4 went by empty
+    assert!(buffer.push(start + FRAME * 5, start + DELAY + FRAME * 4 + ms(10), 5));
+    assert_eq!(buffer.totals().underruns, 1);
+    assert_eq!(buffer.pop(start + DELAY + FRAME * 5), Some(5));
+
+    // However long the gap, it counts once
+    assert!(!buffer.push(start + FRAME * 6, start + DELAY + FRAME * 8, 6));
+    assert!(!buffer.push(start + FRAME * 7, start + DELAY + FRAME * 9, 7));
+    assert_eq!(buffer.totals().underruns, 2);
+}
@@ -0,0 +1,51 @@
+use std::time::{Duration, Instant};
+
+use rust_srt::{
+    endpoint::Endpoint,
+    error::Error,
+    probe::{self, CodecType},
+};
+
+#[test]
+fn probe_reports_test_source_streams_and_program() {
+    let report = probe::probe_path("testsrc:320x180@25:3", Duration::from_secs(2)).unwrap();
+
+    let [video, audio] = report.streams.as_slice() else {
+        panic!("expected video and audio, got {:#?}", report.streams);
+    };
+    assert_eq!(video.codec_type, CodecType::Video);
+    assert_eq!((video.width, video.height), (Some(320), Some(180)));
+    let fps = video.frame_rate.unwrap();
+    assert!((fps - 25.0).abs() < 0.5, "{fps}");
+    assert!(video.bit_rate.is_some_and(|rate| rate > 0));
+
+    assert_eq!(audio.codec_type, CodecType::Audio);
+    assert_eq!(audio.sample_rate, Some(48_000));
+    assert_eq!(audio.channels, Some(2));
+
+    let [program] = report.programs.as_slice() else {
+        panic!("expected one program, got {:#?}", report.programs);
+    };
+    assert_eq!(program.streams.len(), 2);
+    assert_eq!(video.pid, Some(program.streams[0].pid));
+    assert_eq!(audio.pid, Some(program.streams[1].pid));
+    assert!(report.format.mux_rate.is_some_and(|rate| rate > 0));
+}
+
+#[tokio::test]
+async fn live_probe_without_a_peer_times_out() {
+    let endpoint: Endpoint = "srt://:24395".parse().unwrap();
+    let started = Instant::now();
+    let error = probe::probe_endpoint(&endpoint, Duration::from_millis(500))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(error.downcast_ref(), Some(Error::Connection { .. })),
+        "{error:#}"
+    );
+    assert!(
+        started.elapsed() < Duration::from_secs(5),
+        "{:?}",
+        started.elapsed()
+    );
+}
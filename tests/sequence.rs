@@ -0,0 +1,131 @@
+//! What `recv` and `hls` report for a stream once it stops, numbered or raw TS.
+
+use bytes::Bytes;
+use rust_srt::{
+    protocol::Data,
+    sequence::{Arrival, Completion, ContinuityTracker, StreamCheck},
+    ts::{NULL_PID, TS_PACKET_SIZE},
+};
+
+fn payload(seq: Option<u64>) -> Data {
+    Data::Payload {
+        seq,
+        payload: Bytes::from_static(&[0x47; 188]),
+    }
+}
+
+#[test]
+fn numbered_stream_is_checked_against_its_totals() {
+    let mut check = StreamCheck::new("test");
+    for seq in 0..3 {
+        assert!(check.accept(payload(Some(seq))).is_some());
+    }
+    assert!(
+        check
+            .accept(Data::EndOfStream {
+                frames: 3,
+                bytes: 3 * 188,
+            })
+            .is_none()
+    );
+    assert_eq!(
+        check.finish(),
+        Completion::Complete {
+            frames: 3,
+            bytes: 3 * 188,
+        }
+    );
+}
+
+#[test]
+fn raw_ts_is_unchecked_only_when_it_ends_cleanly() {
+    let mut clean = StreamCheck::new("test");
+    let mut dropped = StreamCheck::new("test");
+    for check in [&mut clean, &mut dropped] {
+        for _ in 0..3 {
+            check.accept(payload(None));
+        }
+    }
+    assert_eq!(
+        clean.finish(),
+        Completion::Unchecked {
+            frames: 3,
+            bytes: 3 * 188,
+        }
+    );
+
+    dropped.fail("connection reset");
+    let completion = dropped.finish();
+    assert!(
+        matches!(&completion, Completion::Truncated { reason } if reason.starts_with("connection reset")),
+        "{completion}"
+    );
+    assert_ne!(completion.exit_code(), clean.finish().exit_code());
+}
+
+/// A TS packet with payload on `pid`, counted `counter`.
+fn ts_packet(pid: u16, counter: u8) -> Vec<u8> {
+    let mut packet = vec![0xff; TS_PACKET_SIZE];
+    packet[..4].copy_from_slice(&[0x47, (pid >> 8) as u8, pid as u8, 0x10 | counter]);
+    packet
+}
+
+#[test]
+fn continuity_counters_are_followed_per_pid() {
+    let mut tracker = ContinuityTracker::default();
+    // Each PID counts on its own, wrapping after 15
+    for counter in [14, 15, 0] {
+        assert_eq!(
+            tracker.check(&ts_packet(256, counter)),
+            Some(Arrival::InOrder)
+        );
+        assert_eq!(
+            tracker.check(&ts_packet(257, (counter + 1) & 0x0f)),
+            Some(Arrival::InOrder)
+        );
+    }
+    assert_eq!(tracker.check(&ts_packet(256, 0)), Some(Arrival::Duplicate));
+    assert_eq!(
+        tracker.check(&ts_packet(256, 3)),
+        Some(Arrival::Gap { missing: 2 })
+    );
+    assert_eq!(tracker.check(&ts_packet(NULL_PID, 9)), None);
+
+    // Adaptation field only: the count doesn't move
+    let mut stuffing = ts_packet(256, 3);
+    stuffing[3] = 0x20 | 3;
+    assert_eq!(tracker.check(&stuffing), None);
+
+    // A flagged discontinuity may restart the count anywhere
+    let mut restart = ts_packet(256, 9);
+    restart[3] |= 0x20;
+    restart[4] = 1;
+    restart[5] = 0x80;
+    assert_eq!(tracker.check(&restart), Some(Arrival::InOrder));
+}
+
+#[test]
+fn raw_ts_loss_shows_in_the_totals() {
+    let mut check = StreamCheck::new("test");
+    for counters in [[0, 1, 2], [3, 6, 6]] {
+        let payload = counters
+            .into_iter()
+            .flat_map(|counter| ts_packet(256, counter))
+            .collect::<Vec<_>>();
+        // Duplicate TS packets are passed on, the demuxer drops them
+        let passed = check.accept(Data::Payload {
+            seq: None,
+            payload: payload.into(),
+        });
+        assert_eq!(passed.unwrap().len(), 3 * TS_PACKET_SIZE);
+    }
+
+    let totals = check.totals();
+    assert_eq!(totals.received, 6);
+    assert_eq!(totals.lost, 2);
+    assert_eq!(totals.duplicates, 1);
+    assert!(matches!(
+        check.finish(),
+        Completion::Unchecked { frames: 2, .. }
+    ));
+}
@@ -0,0 +1,109 @@
+//! Raw TS from files, UDP and TCP is re-chunked into whole packets, whatever the read sizes.
+
+use bytes::Bytes;
+use futures::{StreamExt, TryStreamExt, stream};
+use rust_srt::{
+    endpoint::Scheme,
+    gateway,
+    ts::{SRT_PAYLOAD_SIZE, SYNC_BYTE, TS_PACKET_SIZE, TsChunker},
+};
+
+/// `count` packets, each numbered in its first payload byte.
+fn packets(count: usize) -> Vec<u8> {
+    (0..count)
+        .flat_map(|index| {
+            let mut packet = vec![0xff; TS_PACKET_SIZE];
+            packet[0] = SYNC_BYTE;
+            packet[3] = 0x10;
+            packet[4] = index as u8;
+            packet
+        })
+        .collect()
+}
+
+fn assert_aligned(payloads: &[Bytes]) {
+    for payload in payloads {
+        assert_eq!(payload.len() % TS_PACKET_SIZE, 0);
+        assert!(
+            payload
+                .chunks(TS_PACKET_SIZE)
+                .all(|packet| packet[0] == SYNC_BYTE)
+        );
+    }
+}
+
+#[test]
+fn odd_reads_come_out_as_1316_byte_payloads() {
+    let ts = packets(70);
+    let mut chunker = TsChunker::default();
+    let mut payloads = Vec::new();
+    for read in ts.chunks(1000) {
+        payloads.extend(chunker.push(read));
+    }
+    for read in [&ts[..1], &ts[1..2], &ts[2..500]] {
+        payloads.extend(chunker.push(read));
+    }
+    payloads.extend(chunker.flush());
+
+    assert_aligned(&payloads);
+    let whole = payloads.iter().filter(|p| p.len() == SRT_PAYLOAD_SIZE);
+    assert_eq!(whole.count(), (70 + 2) / 7);
+    assert_eq!(payloads.concat()[..ts.len()], ts[..]);
+}
+
+#[test]
+fn garbage_is_skipped_until_the_stream_locks_again() {
+    let ts = packets(14);
+    let mut input = b"junk\x47 with a stray sync byte".to_vec();
+    input.extend_from_slice(&ts[..7 * TS_PACKET_SIZE]);
+    // Half a packet lost in the middle
+    input.extend_from_slice(&ts[7 * TS_PACKET_SIZE..7 * TS_PACKET_SIZE + 90]);
+    input.extend_from_slice(&ts[8 * TS_PACKET_SIZE..]);
+
+    let mut chunker = TsChunker::default();
+    let mut payloads = chunker.push(&input);
+    payloads.extend(chunker.flush());
+
+    assert_aligned(&payloads);
+    let numbers: Vec<u8> = payloads
+        .concat()
+        .chunks(TS_PACKET_SIZE)
+        .map(|packet| packet[4])
+        .collect();
+    let expected: Vec<u8> = (0..7).chain(8..14).collect();
+    assert_eq!(numbers, expected);
+}
+
+#[test]
+fn payload_size_rounds_down_to_whole_packets() {
+    let mut chunker = TsChunker::new(1000);
+    let payloads = chunker.push(&packets(10));
+    assert_eq!(payloads.len(), 2);
+    assert!(payloads.iter().all(|p| p.len() == 5 * TS_PACKET_SIZE));
+}
+
+#[tokio::test]
+async fn raw_inputs_are_aligned_across_reads() {
+    let ts = packets(30);
+    let reads: Vec<_> = [333, 1, 2000, 917, 1200, 1189]
+        .iter()
+        .scan(0, |start, &len| {
+            let read = Bytes::copy_from_slice(&ts[*start..(*start + len).min(ts.len())]);
+            *start += len;
+            Some(read)
+        })
+        .collect();
+    assert_eq!(reads.iter().map(Bytes::len).sum::<usize>(), ts.len());
+
+    for scheme in [Scheme::Udp, Scheme::Tcp] {
+        let input = stream::iter(reads.clone().into_iter().map(Ok)).boxed();
+        let payloads: Vec<Bytes> = gateway::align_ts(input, scheme)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_aligned(&payloads);
+        assert_eq!(payloads.len(), 5);
+        assert!(payloads[..4].iter().all(|p| p.len() == SRT_PAYLOAD_SIZE));
+        assert_eq!(payloads.concat(), ts);
+    }
+}